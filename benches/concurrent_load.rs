@@ -0,0 +1,194 @@
+// آزمون بار هم‌زمان روی سرور در حال اجرا:
+//   cargo bench --bench concurrent_load
+// متغیرها: BENCH_URL (پیش‌فرض http://127.0.0.1:8080)، BENCH_CONCURRENCY (64)، BENCH_DURATION_SECS (10)،
+// BENCH_SCENARIOS (پیش‌فرض me,permissions,sessions)، BENCH_METRICS_TOKEN (اگر سرور metrics_token دارد)
+// همه‌ی سناریوها با یک توکن که پیش از شروع گرفته شده اجرا می‌شوند و چیزی در sessions یا audit_events نمی‌نویسند:
+// `/me` (فقط کوئری‌های middleware)، دسترسی‌های مؤثر (کوئری بازگشتی نقش‌ها) و فهرست نشست‌ها
+// و هم‌زمان با هر کدام، تأخیر `/metrics` که به پایگاه داده نیاز ندارد و فقط به آزاد بودن executor بستگی دارد
+
+use reqwest::{Client, StatusCode};
+use serde_json::{json, Value};
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const BENCH_USERNAME: &str = "bench_user";
+const BENCH_PASSWORD: &str = "bench-password";
+const PROBE_INTERVAL: Duration = Duration::from_millis(20);
+
+#[derive(Default)]
+struct Samples {
+    latencies: Vec<Duration>,
+    errors: usize,
+}
+
+impl Samples {
+    fn merge(&mut self, other: Samples) {
+        self.latencies.extend(other.latencies);
+        self.errors += other.errors;
+    }
+
+    fn percentile(&self, p: f64) -> Duration {
+        if self.latencies.is_empty() {
+            return Duration::ZERO;
+        }
+        let index = ((self.latencies.len() - 1) as f64 * p).round() as usize;
+        self.latencies[index]
+    }
+
+    fn report(&mut self, name: &str, elapsed: Duration) {
+        self.latencies.sort();
+        println!(
+            "{:<14} {:>8} req {:>9.1} req/s  p50 {:>8.2?}  p95 {:>8.2?}  p99 {:>8.2?}  max {:>8.2?}  errors {}",
+            name,
+            self.latencies.len(),
+            self.latencies.len() as f64 / elapsed.as_secs_f64(),
+            self.percentile(0.50),
+            self.percentile(0.95),
+            self.percentile(0.99),
+            self.latencies.last().copied().unwrap_or_default(),
+            self.errors,
+        );
+    }
+}
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    env::var(name).ok().and_then(|value| value.parse().ok()).unwrap_or(default)
+}
+
+// توکن و شناسه‌ی کاربر آزمون که همه‌ی کارگرها از آن استفاده می‌کنند
+struct BenchUser {
+    token: String,
+    user_id: i64,
+}
+
+// مسیر هر سناریو؛ سناریوی ناشناخته None است
+fn scenario_path(scenario: &str, user_id: i64) -> Option<String> {
+    match scenario {
+        "me" => Some("/me".to_string()),
+        "permissions" => Some(format!("/users/{}/effective-permissions", user_id)),
+        "sessions" => Some("/me/sessions".to_string()),
+        _ => None,
+    }
+}
+
+// کاربر آزمون؛ اگر از قبل وجود داشته باشد ثبت‌نام 409 برمی‌گرداند
+// ورود فقط یک بار انجام می‌شود تا اجرای آزمون جدول‌های sessions و audit_events را پر نکند
+async fn prepare(client: &Client, base_url: &str) -> Result<BenchUser, String> {
+    let register = client
+        .post(format!("{}/register", base_url))
+        .json(&json!({ "username": BENCH_USERNAME, "password": BENCH_PASSWORD, "confirm_password": BENCH_PASSWORD }))
+        .send()
+        .await
+        .map_err(|err| format!("server at {} is not reachable: {}", base_url, err))?;
+    if !register.status().is_success() && register.status() != StatusCode::CONFLICT {
+        return Err(format!("register failed with {}", register.status()));
+    }
+
+    let response = client
+        .post(format!("{}/login", base_url))
+        .json(&json!({ "username": BENCH_USERNAME, "password": BENCH_PASSWORD }))
+        .send()
+        .await
+        .map_err(|err| err.to_string())?;
+    if response.status() != StatusCode::CREATED {
+        return Err(format!("login failed with {}", response.status()));
+    }
+    let body: Value = response.json().await.map_err(|err| err.to_string())?;
+    let token = body["token"].as_str().map(str::to_string).ok_or_else(|| "login response has no token".to_string())?;
+
+    let me: Value = client
+        .get(format!("{}/me", base_url))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| format!("/me failed: {}", err))?
+        .json()
+        .await
+        .map_err(|err| err.to_string())?;
+    let user_id = me["user_id"].as_i64().ok_or_else(|| "/me response has no user_id".to_string())?;
+    Ok(BenchUser { token, user_id })
+}
+
+// اجرای یک سناریو با `concurrency` کارگر و یک کاوشگر `/metrics` در کنار آن
+async fn run_scenario(client: &Client, base_url: &str, token: &str, scenario: &str, path: &str, concurrency: usize, duration: Duration) {
+    let deadline = Instant::now() + duration;
+    let started = Instant::now();
+
+    let workers = (0..concurrency)
+        .map(|_| {
+            let (client, url, token) = (client.clone(), format!("{}{}", base_url, path), token.to_string());
+            tokio::spawn(async move {
+                let mut samples = Samples::default();
+                while Instant::now() < deadline {
+                    let sent = Instant::now();
+                    match client.get(&url).bearer_auth(&token).send().await {
+                        Ok(response) if response.status().is_success() => samples.latencies.push(sent.elapsed()),
+                        _ => samples.errors += 1,
+                    }
+                }
+                samples
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let probing = Arc::new(AtomicBool::new(true));
+    let probe = {
+        let (client, base_url, probing) = (client.clone(), base_url.to_string(), probing.clone());
+        let metrics_token = env::var("BENCH_METRICS_TOKEN").ok();
+        tokio::spawn(async move {
+            let mut samples = Samples::default();
+            while probing.load(Ordering::Relaxed) {
+                let sent = Instant::now();
+                let mut request = client.get(format!("{}/metrics", base_url));
+                if let Some(metrics_token) = &metrics_token {
+                    request = request.bearer_auth(metrics_token);
+                }
+                match request.send().await {
+                    Ok(response) if response.status().is_success() => samples.latencies.push(sent.elapsed()),
+                    _ => samples.errors += 1,
+                }
+                tokio::time::sleep(PROBE_INTERVAL).await;
+            }
+            samples
+        })
+    };
+
+    let mut samples = Samples::default();
+    for worker in workers {
+        samples.merge(worker.await.expect("bench worker panicked"));
+    }
+    let elapsed = started.elapsed();
+    probing.store(false, Ordering::Relaxed);
+    let mut probe_samples = probe.await.expect("metrics probe panicked");
+
+    samples.report(scenario, elapsed);
+    probe_samples.report("  /metrics", elapsed);
+}
+
+#[tokio::main]
+async fn main() {
+    let base_url = env::var("BENCH_URL").unwrap_or_else(|_| "http://127.0.0.1:8080".to_string());
+    let concurrency = env_or("BENCH_CONCURRENCY", 64);
+    let duration = Duration::from_secs(env_or("BENCH_DURATION_SECS", 10));
+    let scenarios = env::var("BENCH_SCENARIOS").unwrap_or_else(|_| "me,permissions,sessions".to_string());
+
+    let client = Client::builder().pool_max_idle_per_host(concurrency).build().expect("Cannot build HTTP client");
+    let user = match prepare(&client, &base_url).await {
+        Ok(user) => user,
+        Err(reason) => {
+            println!("concurrent_load skipped: {}", reason);
+            return;
+        }
+    };
+
+    println!("{} concurrent clients for {:?} against {}", concurrency, duration, base_url);
+    for scenario in scenarios.split(',').map(str::trim) {
+        match scenario_path(scenario, user.user_id) {
+            Some(path) => run_scenario(&client, &base_url, &user.token, scenario, &path, concurrency, duration).await,
+            None => println!("unknown scenario '{}'", scenario),
+        }
+    }
+}
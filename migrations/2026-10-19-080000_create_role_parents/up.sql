@@ -0,0 +1,11 @@
+-- Your SQL goes here
+-- ایجاد جدول role_parents برای سلسله‌مراتب نقش‌ها
+-- نقش role_id همه‌ی دسترسی‌های نقش parent_role_id را به ارث می‌برد
+CREATE TABLE role_parents (
+    role_id INTEGER NOT NULL,
+    parent_role_id INTEGER NOT NULL,
+    PRIMARY KEY (role_id, parent_role_id),
+    FOREIGN KEY (role_id) REFERENCES roles(id) ON DELETE CASCADE,
+    FOREIGN KEY (parent_role_id) REFERENCES roles(id) ON DELETE CASCADE,
+    CHECK (role_id <> parent_role_id)
+);
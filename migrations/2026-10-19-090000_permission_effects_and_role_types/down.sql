@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+-- داده‌های ایجادشده (نقش admin) حذف نمی‌شوند چون ممکن است به کاربران اختصاص یافته باشند
+ALTER TABLE roles DROP CONSTRAINT IF EXISTS roles_role_type_check;
+ALTER TABLE permissions DROP CONSTRAINT IF EXISTS permissions_permission_type_check;
@@ -0,0 +1,19 @@
+-- Your SQL goes here
+-- permission_type اثر دسترسی است: allow یا deny
+UPDATE permissions SET permission_type = 'allow' WHERE permission_type NOT IN ('allow', 'deny');
+ALTER TABLE permissions
+    ADD CONSTRAINT permissions_permission_type_check CHECK (permission_type IN ('allow', 'deny'));
+
+-- role_type نقش‌های سیستمی (غیرقابل حذف، ایجادشده توسط migration) را از نقش‌های سفارشی جدا می‌کند
+UPDATE roles SET role_type = 'custom' WHERE role_type NOT IN ('system', 'custom');
+ALTER TABLE roles
+    ADD CONSTRAINT roles_role_type_check CHECK (role_type IN ('system', 'custom'));
+
+-- نقش سیستمی admin با دسترسی کامل
+INSERT INTO roles (name, role_type) VALUES ('admin', 'system')
+    ON CONFLICT (name) DO UPDATE SET role_type = 'system';
+INSERT INTO permissions (name, permission_type) VALUES ('*', 'allow')
+    ON CONFLICT (name) DO UPDATE SET permission_type = 'allow';
+INSERT INTO role_permissions (role_id, permission_id)
+    SELECT r.id, p.id FROM roles r, permissions p WHERE r.name = 'admin' AND p.name = '*'
+    ON CONFLICT DO NOTHING;
@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER IF EXISTS permissions_notify_permission_change ON permissions;
+DROP TRIGGER IF EXISTS role_parents_notify_permission_change ON role_parents;
+DROP TRIGGER IF EXISTS role_permissions_notify_permission_change ON role_permissions;
+DROP TRIGGER IF EXISTS users_roles_truncate_notify_permission_change ON users_roles;
+DROP TRIGGER IF EXISTS users_roles_notify_permission_change ON users_roles;
+DROP FUNCTION IF EXISTS notify_all_permission_change();
+DROP FUNCTION IF EXISTS notify_user_permission_change();
@@ -0,0 +1,42 @@
+-- Your SQL goes here
+-- اعلان تغییرات دسترسی روی کانال permission_changes برای ابطال کش RbacMiddleware
+-- payload شناسه‌ی کاربر است یا `*` وقتی تغییر ممکن است روی همه‌ی کاربران اثر بگذارد
+
+CREATE OR REPLACE FUNCTION notify_user_permission_change() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP <> 'INSERT' THEN
+        PERFORM pg_notify('permission_changes', OLD.user_id::text);
+    END IF;
+    IF TG_OP <> 'DELETE' THEN
+        PERFORM pg_notify('permission_changes', NEW.user_id::text);
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION notify_all_permission_change() RETURNS trigger AS $$
+BEGIN
+    PERFORM pg_notify('permission_changes', '*');
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER users_roles_notify_permission_change
+    AFTER INSERT OR UPDATE OR DELETE ON users_roles
+    FOR EACH ROW EXECUTE PROCEDURE notify_user_permission_change();
+
+CREATE TRIGGER users_roles_truncate_notify_permission_change
+    AFTER TRUNCATE ON users_roles
+    FOR EACH STATEMENT EXECUTE PROCEDURE notify_all_permission_change();
+
+CREATE TRIGGER role_permissions_notify_permission_change
+    AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON role_permissions
+    FOR EACH STATEMENT EXECUTE PROCEDURE notify_all_permission_change();
+
+CREATE TRIGGER role_parents_notify_permission_change
+    AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON role_parents
+    FOR EACH STATEMENT EXECUTE PROCEDURE notify_all_permission_change();
+
+CREATE TRIGGER permissions_notify_permission_change
+    AFTER UPDATE OR DELETE OR TRUNCATE ON permissions
+    FOR EACH STATEMENT EXECUTE PROCEDURE notify_all_permission_change();
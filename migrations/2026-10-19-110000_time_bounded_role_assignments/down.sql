@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS role_elevation_requests;
+
+DROP INDEX IF EXISTS users_roles_pending_expiry_idx;
+ALTER TABLE users_roles
+    DROP CONSTRAINT IF EXISTS users_roles_validity_check,
+    DROP COLUMN IF EXISTS expiry_processed_at,
+    DROP COLUMN IF EXISTS valid_until,
+    DROP COLUMN IF EXISTS valid_from;
@@ -0,0 +1,26 @@
+-- Your SQL goes here
+-- بازه‌ی اعتبار اختصاص نقش؛ NULL یعنی بدون محدودیت
+ALTER TABLE users_roles
+    ADD COLUMN valid_from TIMESTAMP,
+    ADD COLUMN valid_until TIMESTAMP,
+    -- زمانی که sweeper انقضای این اختصاص را پردازش کرده است
+    ADD COLUMN expiry_processed_at TIMESTAMP,
+    ADD CONSTRAINT users_roles_validity_check CHECK (valid_from IS NULL OR valid_until IS NULL OR valid_from < valid_until);
+
+CREATE INDEX users_roles_pending_expiry_idx ON users_roles (valid_until)
+    WHERE valid_until IS NOT NULL AND expiry_processed_at IS NULL;
+
+-- درخواست‌های ارتقای موقت نقش (just-in-time) که باید توسط تأییدکننده پذیرفته شوند
+CREATE TABLE role_elevation_requests (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role_id INTEGER NOT NULL REFERENCES roles(id) ON DELETE CASCADE,
+    reason TEXT NOT NULL,
+    duration_minutes INTEGER NOT NULL CHECK (duration_minutes > 0),
+    status VARCHAR(20) NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'approved', 'rejected')),
+    approver_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    decided_at TIMESTAMP
+);
+
+CREATE INDEX role_elevation_requests_status_idx ON role_elevation_requests (status);
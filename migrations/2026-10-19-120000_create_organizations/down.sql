@@ -0,0 +1,19 @@
+-- This file should undo anything in `up.sql`
+DROP POLICY IF EXISTS items_tenant_isolation ON items;
+ALTER TABLE items DISABLE ROW LEVEL SECURITY;
+
+ALTER TABLE role_elevation_requests DROP COLUMN IF EXISTS tenant_id;
+
+-- اختصاص‌های تکراری (در tenantهای مختلف) پیش از بازگرداندن کلید اصلی قبلی حذف می‌شوند
+DROP INDEX IF EXISTS users_roles_assignment_key;
+DELETE FROM users_roles a USING users_roles b
+    WHERE a.user_id = b.user_id AND a.role_id = b.role_id AND a.id > b.id;
+ALTER TABLE users_roles DROP COLUMN IF EXISTS tenant_id;
+ALTER TABLE users_roles DROP COLUMN IF EXISTS id;
+ALTER TABLE users_roles ADD PRIMARY KEY (user_id, role_id);
+
+DROP INDEX IF EXISTS items_tenant_id_idx;
+ALTER TABLE items DROP COLUMN IF EXISTS tenant_id;
+
+DROP TABLE IF EXISTS organization_members;
+DROP TABLE IF EXISTS organizations;
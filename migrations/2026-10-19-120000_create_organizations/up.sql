@@ -0,0 +1,38 @@
+-- Your SQL goes here
+-- سازمان‌ها (tenantها) و اعضای آن‌ها
+CREATE TABLE organizations (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(255) UNIQUE NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE organization_members (
+    organization_id INTEGER NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    PRIMARY KEY (organization_id, user_id)
+);
+
+-- داده‌های موجود به سازمان پیش‌فرض منتقل می‌شوند
+INSERT INTO organizations (name) VALUES ('default');
+INSERT INTO organization_members (organization_id, user_id)
+    SELECT o.id, u.id FROM organizations o, users u WHERE o.name = 'default';
+
+ALTER TABLE items ADD COLUMN tenant_id INTEGER REFERENCES organizations(id) ON DELETE CASCADE;
+UPDATE items SET tenant_id = (SELECT id FROM organizations WHERE name = 'default');
+ALTER TABLE items ALTER COLUMN tenant_id SET NOT NULL;
+CREATE INDEX items_tenant_id_idx ON items (tenant_id);
+
+-- tenant_id = NULL یعنی اختصاص سراسری که در همه‌ی سازمان‌ها معتبر است
+ALTER TABLE users_roles DROP CONSTRAINT users_roles_pkey;
+ALTER TABLE users_roles ADD COLUMN id SERIAL PRIMARY KEY;
+ALTER TABLE users_roles ADD COLUMN tenant_id INTEGER REFERENCES organizations(id) ON DELETE CASCADE;
+CREATE UNIQUE INDEX users_roles_assignment_key ON users_roles (user_id, role_id, (COALESCE(tenant_id, 0)));
+
+ALTER TABLE role_elevation_requests ADD COLUMN tenant_id INTEGER REFERENCES organizations(id) ON DELETE CASCADE;
+
+-- جداسازی اختیاری با RLS: برای اتصال‌هایی که مالک جدول نیستند (یا پس از FORCE ROW LEVEL SECURITY)
+-- فقط ردیف‌های tenant تنظیم‌شده در app.tenant_id دیده می‌شوند
+ALTER TABLE items ENABLE ROW LEVEL SECURITY;
+CREATE POLICY items_tenant_isolation ON items
+    USING (tenant_id = NULLIF(current_setting('app.tenant_id', true), '')::integer)
+    WITH CHECK (tenant_id = NULLIF(current_setting('app.tenant_id', true), '')::integer);
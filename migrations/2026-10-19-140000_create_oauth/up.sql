@@ -0,0 +1,47 @@
+-- Your SQL goes here
+-- کلاینت‌های OAuth؛ کلاینت عمومی (مثل SPA) secret ندارد و باید از PKCE استفاده کند
+CREATE TABLE oauth_clients (
+    id SERIAL PRIMARY KEY,
+    client_id VARCHAR(64) UNIQUE NOT NULL,
+    client_secret_hash VARCHAR(64),
+    name VARCHAR(255) NOT NULL,
+    redirect_uris TEXT[] NOT NULL DEFAULT '{}' CHECK (array_position(redirect_uris, NULL) IS NULL),
+    grant_types TEXT[] NOT NULL DEFAULT '{}' CHECK (grant_types <@ ARRAY['authorization_code', 'client_credentials', 'refresh_token']),
+    scopes TEXT[] NOT NULL DEFAULT '{}' CHECK (array_position(scopes, NULL) IS NULL),
+    -- کاربر سرویسی که توکن‌های client_credentials به نام او صادر می‌شوند
+    service_user_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE oauth_authorization_codes (
+    id SERIAL PRIMARY KEY,
+    code_hash VARCHAR(64) UNIQUE NOT NULL,
+    oauth_client_id INTEGER NOT NULL REFERENCES oauth_clients(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    tenant_id INTEGER REFERENCES organizations(id) ON DELETE CASCADE,
+    redirect_uri TEXT NOT NULL,
+    scopes TEXT[] NOT NULL DEFAULT '{}' CHECK (array_position(scopes, NULL) IS NULL),
+    code_challenge VARCHAR(128),
+    code_challenge_method VARCHAR(10) CHECK (code_challenge_method IN ('S256')),
+    expires_at TIMESTAMP NOT NULL,
+    consumed_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE oauth_refresh_tokens (
+    id SERIAL PRIMARY KEY,
+    token_hash VARCHAR(64) UNIQUE NOT NULL,
+    oauth_client_id INTEGER NOT NULL REFERENCES oauth_clients(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    tenant_id INTEGER REFERENCES organizations(id) ON DELETE CASCADE,
+    scopes TEXT[] NOT NULL DEFAULT '{}' CHECK (array_position(scopes, NULL) IS NULL),
+    expires_at TIMESTAMP NOT NULL,
+    revoked_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- شناسه‌ی (jti) توکن‌های دسترسی ابطال‌شده تا زمان انقضای خود توکن نگه داشته می‌شود
+CREATE TABLE oauth_revoked_access_tokens (
+    jti VARCHAR(64) PRIMARY KEY,
+    expires_at TIMESTAMP NOT NULL
+);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE oauth_authorization_codes DROP COLUMN IF EXISTS nonce;
+ALTER TABLE users DROP COLUMN IF EXISTS email;
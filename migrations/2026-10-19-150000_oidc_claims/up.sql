@@ -0,0 +1,6 @@
+-- Your SQL goes here
+-- ایمیل برای claim استاندارد `email` در ID token و /userinfo
+ALTER TABLE users ADD COLUMN email VARCHAR(255) UNIQUE;
+
+-- nonce درخواست مجوز تا صدور ID token همراه کد نگه داشته می‌شود
+ALTER TABLE oauth_authorization_codes ADD COLUMN nonce VARCHAR(255);
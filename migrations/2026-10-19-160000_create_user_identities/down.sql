@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE federated_login_states;
+DROP TABLE user_identities;
@@ -0,0 +1,27 @@
+-- Your SQL goes here
+-- پیوند هویت‌های ارائه‌دهندگان بیرونی OIDC به کاربران؛ هر subject فقط به یک کاربر متصل است
+CREATE TABLE user_identities (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    provider VARCHAR(64) NOT NULL,
+    subject VARCHAR(255) NOT NULL,
+    email VARCHAR(255),
+    last_login_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (provider, subject),
+    UNIQUE (user_id, provider)
+);
+
+-- ورودهای در جریان به ارائه‌دهنده‌ی بیرونی؛ state یک‌بارمصرف است و nonce و PKCE verifier را نگه می‌دارد
+CREATE TABLE federated_login_states (
+    id SERIAL PRIMARY KEY,
+    state_hash VARCHAR(64) UNIQUE NOT NULL,
+    provider VARCHAR(64) NOT NULL,
+    nonce VARCHAR(64) NOT NULL,
+    code_verifier VARCHAR(128) NOT NULL,
+    -- در جریان اتصال هویت به حساب موجود، کاربری که اتصال را شروع کرده است
+    link_user_id INTEGER REFERENCES users(id) ON DELETE CASCADE,
+    expires_at TIMESTAMP NOT NULL,
+    consumed_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
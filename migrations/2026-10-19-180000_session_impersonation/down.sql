@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS sessions_impersonator_id_idx;
+ALTER TABLE sessions DROP COLUMN IF EXISTS impersonation_reason;
+ALTER TABLE sessions DROP COLUMN IF EXISTS impersonator_id;
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+-- نشست جعل هویت: کاربر نشست همان کاربر هدف است و impersonator_id کارمند پشتیبانی که توکن را گرفته است
+ALTER TABLE sessions ADD COLUMN impersonator_id INTEGER REFERENCES users(id) ON DELETE CASCADE;
+ALTER TABLE sessions ADD COLUMN impersonation_reason TEXT;
+
+CREATE INDEX sessions_impersonator_id_idx ON sessions (impersonator_id) WHERE impersonator_id IS NOT NULL;
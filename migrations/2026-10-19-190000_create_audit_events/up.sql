@@ -0,0 +1,29 @@
+-- Your SQL goes here
+-- رویدادهای امنیتی (ورود، ثبت‌نام، تغییر نقش‌ها و دسترسی‌ها، ردهای RbacMiddleware)
+-- actor_id کلید خارجی ندارد تا سابقه با حذف کاربر از بین نرود
+CREATE TABLE audit_events (
+    id SERIAL PRIMARY KEY,
+    event_type VARCHAR(64) NOT NULL,
+    outcome VARCHAR(16) NOT NULL CHECK (outcome IN ('success', 'failure', 'denied')),
+    actor_id INTEGER,
+    impersonator_id INTEGER,
+    ip_address VARCHAR(45),
+    target TEXT,
+    detail TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX audit_events_created_at_idx ON audit_events (created_at);
+CREATE INDEX audit_events_actor_id_idx ON audit_events (actor_id, id);
+CREATE INDEX audit_events_event_type_idx ON audit_events (event_type, id);
+
+-- جدول فقط افزودنی است؛ ویرایش و حذف رویدادها رد می‌شود
+CREATE FUNCTION reject_audit_event_change() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_events is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_events_append_only
+    BEFORE UPDATE OR DELETE OR TRUNCATE ON audit_events
+    FOR EACH STATEMENT EXECUTE PROCEDURE reject_audit_event_change();
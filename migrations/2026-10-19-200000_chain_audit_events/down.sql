@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER audit_events_chain ON audit_events;
+DROP FUNCTION chain_audit_event();
+ALTER TABLE audit_events ALTER COLUMN id SET DEFAULT nextval('audit_events_id_seq'::regclass);
+DROP FUNCTION audit_event_hash(audit_events);
+DROP FUNCTION audit_hash_field(TEXT);
+ALTER TABLE audit_events
+    DROP COLUMN prev_hash,
+    DROP COLUMN hash;
@@ -0,0 +1,65 @@
+-- Your SQL goes here
+-- زنجیره‌ی هش رویدادهای امنیتی: هر ردیف هش ردیف قبلی (prev_hash) و هش خودش (hash) را دارد
+-- قالب هش باید با services::audit::event_hash یکی بماند؛ هر فیلد به‌صورت `طول:مقدار` و NULL به‌صورت `-`
+ALTER TABLE audit_events
+    ADD COLUMN prev_hash VARCHAR(64),
+    ADD COLUMN hash VARCHAR(64);
+
+CREATE FUNCTION audit_hash_field(value TEXT) RETURNS TEXT AS $$
+    SELECT CASE WHEN value IS NULL THEN '-' ELSE octet_length(value) || ':' || value END;
+$$ LANGUAGE sql IMMUTABLE;
+
+CREATE FUNCTION audit_event_hash(e audit_events) RETURNS VARCHAR AS $$
+    SELECT encode(sha256(convert_to(
+        audit_hash_field(e.prev_hash)
+        || audit_hash_field(e.id::text)
+        || audit_hash_field(e.event_type)
+        || audit_hash_field(e.outcome)
+        || audit_hash_field(e.actor_id::text)
+        || audit_hash_field(e.impersonator_id::text)
+        || audit_hash_field(e.ip_address)
+        || audit_hash_field(e.target)
+        || audit_hash_field(e.detail)
+        || audit_hash_field(to_char(e.created_at, 'YYYY-MM-DD"T"HH24:MI:SS.US')),
+        'UTF8')), 'hex');
+$$ LANGUAGE sql IMMUTABLE;
+
+-- درج‌ها با قفل advisory یکی‌یکی انجام می‌شوند و شناسه پس از گرفتن قفل تخصیص می‌یابد
+-- تا ترتیب شناسه‌ها همان ترتیب زنجیره باشد
+CREATE FUNCTION chain_audit_event() RETURNS trigger AS $$
+BEGIN
+    PERFORM pg_advisory_xact_lock(hashtext('audit_events'));
+    NEW.id := nextval(pg_get_serial_sequence('audit_events', 'id'));
+    SELECT hash INTO NEW.prev_hash FROM audit_events ORDER BY id DESC LIMIT 1;
+    NEW.prev_hash := COALESCE(NEW.prev_hash, repeat('0', 64));
+    NEW.hash := audit_event_hash(NEW);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+-- زنجیره برای رویدادهای موجود ساخته می‌شود
+ALTER TABLE audit_events DISABLE TRIGGER audit_events_append_only;
+DO $$
+DECLARE
+    e audit_events;
+    prev VARCHAR(64) := repeat('0', 64);
+BEGIN
+    FOR e IN SELECT * FROM audit_events ORDER BY id LOOP
+        e.prev_hash := prev;
+        prev := audit_event_hash(e);
+        UPDATE audit_events SET prev_hash = e.prev_hash, hash = prev WHERE id = e.id;
+    END LOOP;
+END;
+$$;
+ALTER TABLE audit_events ENABLE TRIGGER audit_events_append_only;
+
+ALTER TABLE audit_events
+    ALTER COLUMN prev_hash SET NOT NULL,
+    ALTER COLUMN hash SET NOT NULL;
+
+-- شناسه فقط در تریگر تخصیص می‌یابد
+ALTER TABLE audit_events ALTER COLUMN id DROP DEFAULT;
+
+CREATE TRIGGER audit_events_chain
+    BEFORE INSERT ON audit_events
+    FOR EACH ROW EXECUTE PROCEDURE chain_audit_event();
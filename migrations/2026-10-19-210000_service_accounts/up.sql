@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- حساب سرویس: کاربر غیرانسانی که توکن‌های client_credentials یک کلاینت OAuth به نام او صادر می‌شوند و نمی‌تواند وارد شود
+ALTER TABLE users ADD COLUMN is_service_account BOOLEAN NOT NULL DEFAULT FALSE;
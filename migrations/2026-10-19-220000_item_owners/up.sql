@@ -0,0 +1,4 @@
+-- Your SQL goes here
+-- سازنده‌ی آیتم برای ویژگی `item.owner_id` سیاست‌های ABAC؛ آیتم‌های قدیمی و آیتم‌های کاربران حذف‌شده مالک ندارند
+ALTER TABLE items ADD COLUMN owner_id INTEGER REFERENCES users(id) ON DELETE SET NULL;
+CREATE INDEX items_owner_id_idx ON items (owner_id);
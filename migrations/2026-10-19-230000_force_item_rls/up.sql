@@ -0,0 +1,5 @@
+-- Your SQL goes here
+-- سیاست items_tenant_isolation بدون FORCE فقط برای نقش‌هایی غیر از مالک جدول اعمال می‌شد،
+-- در حالی که سرویس معمولاً با همان نقش مالک وصل می‌شود؛ با FORCE مالک هم فقط ردیف‌های app.tenant_id را می‌بیند.
+-- superuser و نقش‌های BYPASSRLS همچنان از RLS معاف‌اند
+ALTER TABLE items FORCE ROW LEVEL SECURITY;
@@ -1,12 +1,393 @@
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
 use diesel::prelude::*;
-use diesel::r2d2::{self, ConnectionManager};
-use std::env;
+use diesel::r2d2::{self, ConnectionManager, CustomizeConnection};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use url::Url;
+use crate::services::pool_metrics::{PoolEventRecorder, PoolMetrics};
 
 pub type DbPool = r2d2::Pool<ConnectionManager<PgConnection>>;
 
-pub fn establish_connection() -> DbPool {
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in .env file");
+// فایل پیش‌فرض تنظیمات؛ اگر وجود نداشته باشد فقط متغیرهای محیطی و پرچم‌ها خوانده می‌شوند
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+// تنظیمات برنامه به ترتیب اولویت: پرچم خط فرمان، متغیر محیطی (و .env)، فایل TOML، مقدار پیش‌فرض
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub host: String,
+    pub database_url: String,
+    pub jwt_secret: String,
+    pub permission_cache_ttl_secs: u64,
+    pub assignment_sweep_interval_secs: u64,
+    pub policy_file: PathBuf,
+    // ارائه‌دهندگان بیرونی برای ورود فدرال؛ بدون فایل این قابلیت غیرفعال است
+    pub federation_providers_file: Option<PathBuf>,
+    // توکن Bearer برای `/metrics`؛ بدون مقدار این مسیر عمومی است
+    pub metrics_token: Option<String>,
+    pub pool: PoolSettings,
+    pub oidc: OidcSettings,
+    pub audit: AuditSettings,
+    pub samfa: SamfaSettings,
+}
+
+// pool اتصال پایگاه داده؛ مقدار صفر برای زمان‌ها یعنی بدون محدودیت
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PoolSettings {
+    pub max_size: u32,
+    // بدون مقدار برابر max_size است
+    pub min_idle: Option<u32>,
+    pub connection_timeout_secs: u64,
+    pub idle_timeout_secs: u64,
+    pub max_lifetime_secs: u64,
+    // اجرای `SELECT 1` پیش از تحویل هر اتصال تا اتصال‌های قطع‌شده به handler نرسند
+    pub test_on_check_out: bool,
+    // `SET statement_timeout` روی هر اتصال تازه
+    pub statement_timeout_ms: u64,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OidcSettings {
+    // آدرس عمومی سرویس؛ پیش‌فرض `http://{host}`
+    pub issuer: Option<String>,
+    pub signing_key_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuditSettings {
+    // بدون فایل checkpointی نوشته نمی‌شود
+    pub checkpoint_file: Option<PathBuf>,
+    pub checkpoint_interval_secs: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SamfaSettings {
+    pub base_url: String,
+    pub token: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            host: "127.0.0.1:8080".to_string(),
+            database_url: String::new(),
+            jwt_secret: String::new(),
+            permission_cache_ttl_secs: 60,
+            assignment_sweep_interval_secs: 30,
+            policy_file: PathBuf::from("policies.json"),
+            federation_providers_file: None,
+            metrics_token: None,
+            pool: PoolSettings::default(),
+            oidc: OidcSettings::default(),
+            audit: AuditSettings::default(),
+            samfa: SamfaSettings::default(),
+        }
+    }
+}
+
+impl Default for PoolSettings {
+    fn default() -> Self {
+        PoolSettings {
+            max_size: 10,
+            min_idle: None,
+            connection_timeout_secs: 30,
+            idle_timeout_secs: 600,
+            max_lifetime_secs: 1800,
+            test_on_check_out: true,
+            statement_timeout_ms: 0,
+        }
+    }
+}
+
+impl Default for AuditSettings {
+    fn default() -> Self {
+        AuditSettings {
+            checkpoint_file: None,
+            checkpoint_interval_secs: 3600,
+        }
+    }
+}
+
+impl Default for SamfaSettings {
+    fn default() -> Self {
+        SamfaSettings {
+            base_url: "https://seller.samfaa.ir/api/v1/".to_string(),
+            token: None,
+        }
+    }
+}
+
+// پرچم‌های خط فرمان؛ هر پرچم در صورت نبود از متغیر محیطی هم‌نام خوانده می‌شود
+#[derive(Debug, Parser)]
+#[command(version, about = "Authorization and identity service")]
+pub struct Cli {
+    #[arg(long, env = "CONFIG_FILE", help = "TOML settings file [default: config.toml when present]")]
+    config: Option<PathBuf>,
+    #[arg(long, env = "HOST", help = "Listen address (host:port)")]
+    host: Option<String>,
+    #[arg(long, env = "DATABASE_URL", hide_env_values = true)]
+    database_url: Option<String>,
+    #[arg(long, env = "JWT_SECRET", hide_env_values = true)]
+    jwt_secret: Option<String>,
+    #[arg(long, env = "PERMISSION_CACHE_TTL_SECS")]
+    permission_cache_ttl_secs: Option<u64>,
+    #[arg(long, env = "ASSIGNMENT_SWEEP_INTERVAL_SECS")]
+    assignment_sweep_interval_secs: Option<u64>,
+    #[arg(long, env = "POLICY_FILE")]
+    policy_file: Option<PathBuf>,
+    #[arg(long, env = "FEDERATION_PROVIDERS_FILE")]
+    federation_providers_file: Option<PathBuf>,
+    #[arg(long, env = "METRICS_TOKEN", hide_env_values = true)]
+    metrics_token: Option<String>,
+    #[arg(long, env = "DB_POOL_MAX_SIZE")]
+    db_pool_max_size: Option<u32>,
+    #[arg(long, env = "DB_POOL_MIN_IDLE")]
+    db_pool_min_idle: Option<u32>,
+    #[arg(long, env = "DB_POOL_CONNECTION_TIMEOUT_SECS")]
+    db_pool_connection_timeout_secs: Option<u64>,
+    #[arg(long, env = "DB_POOL_IDLE_TIMEOUT_SECS")]
+    db_pool_idle_timeout_secs: Option<u64>,
+    #[arg(long, env = "DB_POOL_MAX_LIFETIME_SECS")]
+    db_pool_max_lifetime_secs: Option<u64>,
+    #[arg(long, env = "DB_POOL_TEST_ON_CHECK_OUT")]
+    db_pool_test_on_check_out: Option<bool>,
+    #[arg(long, env = "DB_STATEMENT_TIMEOUT_MS")]
+    db_statement_timeout_ms: Option<u64>,
+    #[arg(long, env = "OIDC_ISSUER")]
+    oidc_issuer: Option<String>,
+    #[arg(long, env = "OIDC_SIGNING_KEY_FILE")]
+    oidc_signing_key_file: Option<PathBuf>,
+    #[arg(long, env = "AUDIT_CHECKPOINT_FILE")]
+    audit_checkpoint_file: Option<PathBuf>,
+    #[arg(long, env = "AUDIT_CHECKPOINT_INTERVAL_SECS")]
+    audit_checkpoint_interval_secs: Option<u64>,
+    #[arg(long, env = "SAMFA_BASE_URL")]
+    samfa_base_url: Option<String>,
+    #[arg(long, env = "SAMFA_TOKEN", hide_env_values = true)]
+    samfa_token: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    #[command(about = "Verify the audit log hash chain and signed checkpoints, then exit")]
+    VerifyAudit,
+}
+
+impl Settings {
+    // بارگذاری و بررسی تنظیمات؛ خطا همه‌ی مشکلات را یک‌جا گزارش می‌کند
+    pub fn load(cli: &Cli) -> Result<Settings> {
+        let mut settings = match &cli.config {
+            Some(file) => Settings::from_file(file)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => Settings::from_file(Path::new(DEFAULT_CONFIG_FILE))?,
+            None => Settings::default(),
+        };
+        settings.apply(cli);
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    fn from_file(file: &Path) -> Result<Settings> {
+        let contents = fs::read_to_string(file).with_context(|| format!("Cannot read settings file {}", file.display()))?;
+        toml::from_str(&contents).with_context(|| format!("Invalid settings file {}", file.display()))
+    }
+
+    fn apply(&mut self, cli: &Cli) {
+        fn set<T: Clone>(target: &mut T, value: &Option<T>) {
+            if let Some(value) = value {
+                *target = value.clone();
+            }
+        }
+        fn set_optional<T: Clone>(target: &mut Option<T>, value: &Option<T>) {
+            if value.is_some() {
+                *target = value.clone();
+            }
+        }
+
+        set(&mut self.host, &cli.host);
+        set(&mut self.database_url, &cli.database_url);
+        set(&mut self.jwt_secret, &cli.jwt_secret);
+        set(&mut self.permission_cache_ttl_secs, &cli.permission_cache_ttl_secs);
+        set(&mut self.assignment_sweep_interval_secs, &cli.assignment_sweep_interval_secs);
+        set(&mut self.policy_file, &cli.policy_file);
+        set_optional(&mut self.federation_providers_file, &cli.federation_providers_file);
+        set_optional(&mut self.metrics_token, &cli.metrics_token);
+        set(&mut self.pool.max_size, &cli.db_pool_max_size);
+        set_optional(&mut self.pool.min_idle, &cli.db_pool_min_idle);
+        set(&mut self.pool.connection_timeout_secs, &cli.db_pool_connection_timeout_secs);
+        set(&mut self.pool.idle_timeout_secs, &cli.db_pool_idle_timeout_secs);
+        set(&mut self.pool.max_lifetime_secs, &cli.db_pool_max_lifetime_secs);
+        set(&mut self.pool.test_on_check_out, &cli.db_pool_test_on_check_out);
+        set(&mut self.pool.statement_timeout_ms, &cli.db_statement_timeout_ms);
+        set_optional(&mut self.oidc.issuer, &cli.oidc_issuer);
+        set_optional(&mut self.oidc.signing_key_file, &cli.oidc_signing_key_file);
+        set_optional(&mut self.audit.checkpoint_file, &cli.audit_checkpoint_file);
+        set(&mut self.audit.checkpoint_interval_secs, &cli.audit_checkpoint_interval_secs);
+        set(&mut self.samfa.base_url, &cli.samfa_base_url);
+        set_optional(&mut self.samfa.token, &cli.samfa_token);
+    }
+
+    fn validate(&self) -> Result<()> {
+        let mut problems = Vec::new();
+
+        if self.host.rsplit_once(':').and_then(|(_, port)| port.parse::<u16>().ok()).is_none() {
+            problems.push(format!("host must be host:port, got '{}'", self.host));
+        }
+        if self.database_url.is_empty() {
+            problems.push("database_url (DATABASE_URL) must be set".to_string());
+        }
+        if self.jwt_secret.is_empty() {
+            problems.push("jwt_secret (JWT_SECRET) must be set".to_string());
+        }
+        for (name, secs) in [
+            ("permission_cache_ttl_secs", self.permission_cache_ttl_secs),
+            ("assignment_sweep_interval_secs", self.assignment_sweep_interval_secs),
+            ("pool.connection_timeout_secs", self.pool.connection_timeout_secs),
+            ("audit.checkpoint_interval_secs", self.audit.checkpoint_interval_secs),
+        ] {
+            if secs == 0 {
+                problems.push(format!("{} must be greater than zero", name));
+            }
+        }
+        if self.metrics_token.as_deref().is_some_and(str::is_empty) {
+            problems.push("metrics_token must not be empty; leave it unset to keep /metrics public".to_string());
+        }
+        if self.pool.max_size == 0 {
+            problems.push("pool.max_size must be greater than zero".to_string());
+        }
+        if let Some(min_idle) = self.pool.min_idle.filter(|min_idle| *min_idle > self.pool.max_size) {
+            problems.push(format!("pool.min_idle ({}) must not exceed pool.max_size ({})", min_idle, self.pool.max_size));
+        }
+        for (name, file) in [
+            ("federation_providers_file", self.federation_providers_file.as_deref()),
+            ("oidc.signing_key_file", self.oidc.signing_key_file.as_deref()),
+        ] {
+            if let Some(file) = file.filter(|file| !file.is_file()) {
+                problems.push(format!("{} {} does not exist", name, file.display()));
+            }
+        }
+        // بدون کلید پایدار، checkpointها با کلید موقت هر اجرا امضا می‌شوند و بعداً قابل بررسی نیستند
+        if self.audit.checkpoint_file.is_some() && self.oidc.signing_key_file.is_none() {
+            problems.push("audit.checkpoint_file requires oidc.signing_key_file".to_string());
+        }
+        if let Some(issuer) = &self.oidc.issuer {
+            if Url::parse(issuer).is_err() {
+                problems.push(format!("oidc.issuer must be an absolute URL, got '{}'", issuer));
+            }
+        }
+        if !self.samfa.base_url.ends_with('/') || Url::parse(&self.samfa.base_url).is_err() {
+            problems.push(format!("samfa.base_url must be an absolute URL ending in '/', got '{}'", self.samfa.base_url));
+        }
+
+        if !problems.is_empty() {
+            bail!("Invalid settings:\n  - {}", problems.join("\n  - "));
+        }
+        Ok(())
+    }
+
+    pub fn oidc_issuer(&self) -> String {
+        self.oidc.issuer.clone().unwrap_or(format!("http://{}", self.host))
+    }
+}
+
+// تنظیمات نشست هر اتصال تازه‌ی pool
+#[derive(Debug)]
+struct SessionSetup {
+    statement_timeout_ms: u64,
+}
+
+impl CustomizeConnection<PgConnection, r2d2::Error> for SessionSetup {
+    fn on_acquire(&self, conn: &mut PgConnection) -> Result<(), r2d2::Error> {
+        diesel::sql_query(format!("SET statement_timeout = {}", self.statement_timeout_ms))
+            .execute(conn)
+            .map(|_| ())
+            .map_err(r2d2::Error::QueryError)
+    }
+}
+
+// ساخت pool؛ اگر اتصال‌های اولیه (min_idle) تا connection_timeout برقرار نشوند خطا برمی‌گردد
+pub fn establish_connection(database_url: &str, settings: &PoolSettings, metrics: Arc<PoolMetrics>) -> Result<DbPool> {
+    let non_zero_secs = |secs: u64| (secs > 0).then(|| Duration::from_secs(secs));
     let manager = ConnectionManager::<PgConnection>::new(database_url);
-    // r2d2::Pool::builder().build(manager).expect("Failed to create pool.")
-    r2d2::Pool::new(manager).expect("Failed to create pool.")
+    let mut builder = r2d2::Pool::builder()
+        .max_size(settings.max_size)
+        .min_idle(settings.min_idle)
+        .connection_timeout(Duration::from_secs(settings.connection_timeout_secs))
+        .idle_timeout(non_zero_secs(settings.idle_timeout_secs))
+        .max_lifetime(non_zero_secs(settings.max_lifetime_secs))
+        .test_on_check_out(settings.test_on_check_out)
+        .event_handler(Box::new(PoolEventRecorder(metrics)));
+    if settings.statement_timeout_ms > 0 {
+        builder = builder.connection_customizer(Box::new(SessionSetup {
+            statement_timeout_ms: settings.statement_timeout_ms,
+        }));
+    }
+    builder.build(manager).context("Failed to create database pool")
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use super::*;
+
+    #[test]
+    fn example_file_is_valid() {
+        let settings = Settings::from_file(Path::new("config.example.toml")).expect("Invalid config.example.toml");
+        assert!(settings.validate().is_ok());
+    }
+
+    #[test]
+    fn flags_override_file_values() {
+        let mut settings: Settings = toml::from_str("jwt_secret = \"from-file\"\n[audit]\ncheckpoint_interval_secs = 10").unwrap();
+        let cli = Cli::try_parse_from(["crate", "--jwt-secret", "from-flag"]).unwrap();
+        settings.apply(&cli);
+
+        assert_eq!(settings.jwt_secret, "from-flag");
+        assert_eq!(settings.audit.checkpoint_interval_secs, 10);
+        assert_eq!(settings.permission_cache_ttl_secs, 60);
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(toml::from_str::<Settings>("jwt_secert = \"typo\"").is_err());
+    }
+
+    #[test]
+    fn checkpoints_require_a_signing_key() {
+        let settings: Settings = toml::from_str(
+            "database_url = \"postgres://localhost/crate\"\njwt_secret = \"secret\"\n[audit]\ncheckpoint_file = \"checkpoints.jsonl\"",
+        )
+        .unwrap();
+        let message = settings.validate().unwrap_err().to_string();
+        assert!(message.contains("audit.checkpoint_file requires oidc.signing_key_file"), "{}", message);
+    }
+
+    #[test]
+    fn validation_lists_every_problem() {
+        let settings = Settings {
+            host: "localhost".to_string(),
+            permission_cache_ttl_secs: 0,
+            metrics_token: Some(String::new()),
+            pool: PoolSettings {
+                min_idle: Some(20),
+                ..PoolSettings::default()
+            },
+            ..Settings::default()
+        };
+        let message = settings.validate().unwrap_err().to_string();
+
+        for expected in ["host must be host:port", "database_url", "jwt_secret", "permission_cache_ttl_secs", "metrics_token", "pool.min_idle"] {
+            assert!(message.contains(expected), "{}", message);
+        }
+    }
 }
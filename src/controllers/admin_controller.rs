@@ -0,0 +1,238 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::iter;
+use crate::config::{DbPool, Settings};
+use crate::db;
+use crate::errors::ApiError;
+use crate::controllers::user_controller::{issue_login_token, select_login_tenant};
+use crate::middleware::auth_user::AuthUser;
+use crate::middleware::jwt::load_user_access;
+use crate::models::audit::{AuditOutcome, NewAuditEvent};
+use crate::models::session::Session;
+use crate::schema::{organization_members, sessions, users};
+use crate::services::audit::record_event;
+use crate::services::sessions::{create_impersonation_session, SessionOrigin};
+
+// نقش سیستمی مدیر و دسترسی جعل هویت؛ دارندگان هیچ‌کدام قابل جعل هویت نیستند
+pub const ADMIN_ROLE: &str = "admin";
+pub const IMPERSONATE_PERMISSION: &str = "impersonate_users";
+
+#[derive(Deserialize)]
+pub struct ImpersonateForm {
+    // دلیل جعل هویت (مثلاً شماره‌ی تیکت پشتیبانی) برای سابقه
+    pub reason: String,
+    pub organization_id: Option<i32>,
+}
+
+#[derive(Serialize)]
+struct ImpersonationResponse {
+    token: String,
+    session_id: i32,
+    expires_at: NaiveDateTime,
+}
+
+enum Impersonation {
+    Started(Session, Option<i32>),
+    NotFound,
+    Protected,
+    Rejected(&'static str),
+}
+
+// مدیر در هر سازمانی (یا به‌صورت سراسری) یعنی کاربر محافظت‌شده است
+fn is_protected(conn: &mut PgConnection, target: i32) -> QueryResult<bool> {
+    let tenants = organization_members::table
+        .filter(organization_members::user_id.eq(target))
+        .select(organization_members::organization_id)
+        .load::<i32>(conn)?;
+
+    for tenant in iter::once(None).chain(tenants.into_iter().map(Some)) {
+        let access = load_user_access(conn, target, tenant)?;
+        if access.has_role(ADMIN_ROLE) || access.permissions.allows(IMPERSONATE_PERMISSION) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// تابع شروع جعل هویت: توکن کوتاه‌عمر کاربر هدف با claim `act` کارمند پشتیبانی
+pub async fn impersonate(
+    req: HttpRequest,
+    user: AuthUser,
+    target_path: web::Path<i32>,
+    form: web::Json<ImpersonateForm>,
+    pool: web::Data<DbPool>,
+    settings: web::Data<Settings>,
+) -> Result<HttpResponse, ApiError> {
+    if user.api_key_id.is_some() {
+        return Err(ApiError::forbidden("API keys cannot impersonate users"));
+    }
+
+    let actor = user.user_id;
+    let target = target_path.into_inner();
+    let form = form.into_inner();
+    if target == actor {
+        return Err(ApiError::bad_request("Cannot impersonate yourself"));
+    }
+    if form.reason.trim().is_empty() {
+        return Err(ApiError::bad_request("reason is required"));
+    }
+    let origin = SessionOrigin::from_request(&req);
+    let peer = req.peer_addr();
+
+    let result = db::run(&pool, move |conn| {
+        // کارمند actor رویداد است و کاربر هدف target آن
+        let audit = |outcome| NewAuditEvent::new("impersonation.start", outcome).actor(actor, None).peer(peer).target(format!("user:{}", target));
+
+        let exists = diesel::select(diesel::dsl::exists(users::table.find(target))).get_result::<bool>(conn)?;
+        if !exists {
+            return Ok(Impersonation::NotFound);
+        }
+        if is_protected(conn, target)? {
+            record_event(conn, audit(AuditOutcome::Denied).detail("Administrators cannot be impersonated"));
+            return Ok(Impersonation::Protected);
+        }
+        let tenant = match select_login_tenant(conn, target, form.organization_id)? {
+            Ok(tenant) => tenant,
+            Err(reason) => return Ok(Impersonation::Rejected(reason)),
+        };
+        let session = create_impersonation_session(conn, target, actor, form.reason.trim(), origin)?;
+        record_event(conn, audit(AuditOutcome::Success).detail(format!("session:{}: {}", session.id, form.reason.trim())));
+        Ok::<_, diesel::result::Error>(Impersonation::Started(session, tenant))
+    })
+    .await??;
+
+    let (session, tenant) = match result {
+        Impersonation::Started(session, tenant) => (session, tenant),
+        Impersonation::NotFound => return Err(ApiError::not_found("User not found")),
+        Impersonation::Protected => {
+            warn!("User {} was refused impersonation of administrator {}", actor, target);
+            return Err(ApiError::forbidden("Administrators cannot be impersonated"));
+        }
+        Impersonation::Rejected(reason) => return Err(ApiError::bad_request(reason)),
+    };
+
+    let token = issue_login_token(&settings.jwt_secret, &session, tenant).map_err(|err| ApiError::internal(format!("Error generating token: {}", err)))?;
+    info!(
+        "User {} started impersonating user {} (session {}): {}",
+        actor,
+        target,
+        session.id,
+        session.impersonation_reason.as_deref().unwrap_or_default()
+    );
+
+    Ok(HttpResponse::Created().json(ImpersonationResponse {
+        token,
+        session_id: session.id,
+        expires_at: session.expires_at,
+    }))
+}
+
+// تابع پایان جعل هویت با همان توکن جعل هویت؛ نشست ابطال و توکن بی‌اعتبار می‌شود
+pub async fn end_impersonation(req: HttpRequest, user: AuthUser, pool: web::Data<DbPool>) -> Result<HttpResponse, ApiError> {
+    let (Some(actor), Some(session_id)) = (user.actor_id, user.session_id) else {
+        return Err(ApiError::bad_request("Not an impersonation token"));
+    };
+    let peer = req.peer_addr();
+    let by = user.clone();
+
+    let result = db::run(&pool, move |conn| {
+        let ended = diesel::update(sessions::table.find(session_id))
+            .filter(sessions::impersonator_id.eq(actor))
+            .filter(sessions::revoked_at.is_null())
+            .set(sessions::revoked_at.eq(Some(Utc::now().naive_utc())))
+            .execute(conn)?;
+        // actor کاربر هدف و impersonator کارمند است، مثل بقیه‌ی رویدادهای درخواست‌های جعل هویت
+        if ended > 0 {
+            record_event(
+                conn,
+                NewAuditEvent::new("impersonation.end", AuditOutcome::Success)
+                    .by(Some(&by))
+                    .peer(peer)
+                    .target(format!("user:{}", by.user_id))
+                    .detail(format!("session:{}", session_id)),
+            );
+        }
+        Ok::<_, diesel::result::Error>(ended)
+    })
+    .await;
+
+    match result?? {
+        0 => Err(ApiError::not_found("Impersonation session not found")),
+        _ => {
+            info!("User {} ended impersonating user {} (session {})", actor, user.user_id, session_id);
+            Ok(HttpResponse::NoContent().finish())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::{header, StatusCode};
+    use actix_web::{test, web, App};
+    use diesel::prelude::*;
+    use serde_json::{json, Value};
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use super::IMPERSONATE_PERMISSION;
+    use crate::models::user::Claims;
+    use crate::routes::admin::config_routes;
+    use crate::schema::{audit_events, users};
+    use crate::test_support::{cached_access, database_pool, test_settings, token_for};
+
+    // شروع و پایان جعل هویت با شناسه‌ی کارمند و کاربر هدف در سابقه‌ی امنیتی ثبت می‌شوند
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn impersonation_is_audited() {
+        let pool = database_pool();
+        let suffix = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+        let mut conn = pool.get().unwrap();
+        let [staff, target] = ["staff", "customer"].map(|name| {
+            diesel::insert_into(users::table)
+                .values((users::username.eq(format!("{}-{}", name, suffix)), users::password.eq("!")))
+                .returning(users::id)
+                .get_result::<i32>(&mut conn)
+                .unwrap()
+        });
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(test_settings()))
+                .app_data(cached_access(staff, &[IMPERSONATE_PERMISSION]))
+                .configure(config_routes),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/admin/impersonate/{}", target))
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", token_for(Claims { sub: staff, ..Default::default() }))))
+            .set_json(json!({ "reason": "ticket 42" }))
+            .to_request();
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body: Value = test::read_body_json(response).await;
+        let token = body["token"].as_str().unwrap().to_string();
+
+        let req = test::TestRequest::post()
+            .uri("/admin/impersonate/end")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NO_CONTENT);
+
+        let events = audit_events::table
+            .filter(audit_events::target.eq(format!("user:{}", target)))
+            .order(audit_events::id.asc())
+            .select((audit_events::event_type, audit_events::outcome, audit_events::actor_id, audit_events::impersonator_id))
+            .load::<(String, String, Option<i32>, Option<i32>)>(&mut conn)
+            .unwrap();
+        assert_eq!(
+            events,
+            [
+                ("impersonation.start".to_string(), "success".to_string(), Some(staff), None),
+                ("impersonation.end".to_string(), "success".to_string(), Some(target), Some(staff)),
+            ]
+        );
+    }
+}
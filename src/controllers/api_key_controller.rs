@@ -0,0 +1,152 @@
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::config::DbPool;
+use crate::db;
+use crate::errors::ApiError;
+use crate::middleware::auth_user::AuthUser;
+use crate::models::api_key::{ApiKey, NewApiKey};
+use crate::schema::api_keys::dsl::*;
+use crate::services::api_keys::generate_key;
+
+#[derive(Deserialize)]
+pub struct CreateApiKeyForm {
+    pub name: String,
+    // نام یا الگوی دسترسی‌ها، مثل `items.*`؛ کلید هرگز بیش از دسترسی‌های مالکش را ندارد
+    pub scopes: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+struct CreatedApiKey {
+    api_key: ApiKey,
+    // کلید کامل فقط همین یک بار برگردانده می‌شود
+    key: String,
+}
+
+// تابع ساخت کلید API برای کاربر جاری در سازمان جاری
+pub async fn create_api_key(
+    user: AuthUser,
+    form: web::Json<CreateApiKeyForm>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, ApiError> {
+    // کلید نباید بتواند کلیدی با scope گسترده‌تر از خودش بسازد
+    if user.api_key_id.is_some() {
+        return Err(ApiError::forbidden("API keys cannot manage API keys"));
+    }
+    // توکن OAuth با scope محدود نباید کلیدی با همه‌ی دسترسی‌های مالک بسازد
+    if user.scopes.is_some() {
+        return Err(ApiError::forbidden("Scoped OAuth tokens cannot manage API keys"));
+    }
+    // کلید ساخته‌شده پس از پایان جعل هویت هم باقی می‌ماند
+    if user.actor_id.is_some() {
+        return Err(ApiError::forbidden("API keys cannot be created while impersonating"));
+    }
+
+    let form = form.into_inner();
+    if form.expires_at.is_some_and(|at| at <= Utc::now()) {
+        return Err(ApiError::bad_request("expires_at must be in the future"));
+    }
+    if form.scopes.iter().any(|scope| scope.trim().is_empty()) {
+        return Err(ApiError::bad_request("scopes must not be empty strings"));
+    }
+
+    let generated = generate_key();
+    let new_key = NewApiKey {
+        user_id: user.user_id,
+        tenant_id: user.tenant_id,
+        name: form.name,
+        prefix: generated.prefix,
+        secret_hash: generated.secret_hash,
+        scopes: form.scopes,
+        expires_at: form.expires_at.map(|at| at.naive_utc()),
+    };
+
+    let api_key = db::run(&pool, move |conn| {
+        diesel::insert_into(api_keys)
+            .values(&new_key)
+            .returning(ApiKey::as_returning())
+            .get_result(conn)
+    })
+    .await??;
+
+    Ok(HttpResponse::Created().json(CreatedApiKey { api_key, key: generated.key }))
+}
+
+// تابع فهرست کلیدهای کاربر جاری (بدون بخش محرمانه)
+pub async fn list_api_keys(user: AuthUser, pool: web::Data<DbPool>) -> Result<HttpResponse, ApiError> {
+    let owner = user.user_id;
+
+    let keys = db::run(&pool, move |conn| {
+        api_keys
+            .filter(user_id.eq(owner))
+            .order(created_at.asc())
+            .select(ApiKey::as_select())
+            .load(conn)
+    })
+    .await??;
+
+    Ok(HttpResponse::Ok().json(keys))
+}
+
+// تابع ابطال کلید؛ کلید ابطال‌شده حذف نمی‌شود تا سابقه‌ی استفاده باقی بماند
+pub async fn revoke_api_key(
+    user: AuthUser,
+    key_path: web::Path<i32>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, ApiError> {
+    if user.api_key_id.is_some() {
+        return Err(ApiError::forbidden("API keys cannot manage API keys"));
+    }
+
+    let owner = user.user_id;
+    let target_id = key_path.into_inner();
+
+    let result = db::run(&pool, move |conn| {
+        diesel::update(api_keys.find(target_id))
+            .filter(user_id.eq(owner))
+            .filter(revoked_at.is_null())
+            .set(revoked_at.eq(Some(Utc::now().naive_utc())))
+            .execute(conn)
+    })
+    .await;
+
+    match result?? {
+        0 => Err(ApiError::not_found("API key not found")),
+        _ => Ok(HttpResponse::Ok().body("API key revoked")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::{header, StatusCode};
+    use actix_web::{test, web, App};
+    use serde_json::json;
+    use crate::models::user::Claims;
+    use crate::routes::api_keys::config_routes;
+    use crate::test_support::{cached_access, problem, test_settings, token_for, unavailable_pool};
+
+    // توکن OAuth با scope `items.read` نباید بتواند کلیدی با scope `*` بسازد
+    #[actix_web::test]
+    async fn scoped_oauth_tokens_cannot_create_api_keys() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(unavailable_pool()))
+                .app_data(web::Data::new(test_settings()))
+                .app_data(cached_access(1, &[]))
+                .configure(config_routes),
+        )
+        .await;
+        let token = token_for(Claims { sub: 1, scope: Some("items.read".to_string()), ..Default::default() });
+        let req = test::TestRequest::post()
+            .uri("/api_keys")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+            .set_json(json!({ "name": "escape", "scopes": ["*"] }))
+            .to_request();
+
+        let (status, code) = problem(&app, req).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(code, "forbidden");
+    }
+}
@@ -0,0 +1,82 @@
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::config::DbPool;
+use crate::db;
+use crate::errors::ApiError;
+use crate::models::audit::AuditEvent;
+use crate::schema::audit_events;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+#[derive(Deserialize)]
+pub struct AuditQuery {
+    pub event_type: Option<String>,
+    pub outcome: Option<String>,
+    pub actor_id: Option<i32>,
+    pub target: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    // صفحه‌بندی با شناسه: رویدادهای قدیمی‌تر از این شناسه
+    pub before: Option<i32>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct AuditPage {
+    events: Vec<AuditEvent>,
+    // مقدار `before` برای صفحه‌ی بعد؛ None یعنی صفحه‌ی آخر
+    next_before: Option<i32>,
+}
+
+// تابع جستجوی رویدادهای امنیتی، از جدیدترین به قدیمی‌ترین
+pub async fn list_audit_events(query: web::Query<AuditQuery>, pool: web::Data<DbPool>) -> Result<HttpResponse, ApiError> {
+    let query = query.into_inner();
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(ApiError::bad_request(format!("limit must be between 1 and {}", MAX_PAGE_SIZE)));
+    }
+
+    let mut events = db::run(&pool, move |conn| {
+        let mut events = audit_events::table.into_boxed();
+        if let Some(event_type) = query.event_type {
+            events = events.filter(audit_events::event_type.eq(event_type));
+        }
+        if let Some(outcome) = query.outcome {
+            events = events.filter(audit_events::outcome.eq(outcome));
+        }
+        if let Some(actor_id) = query.actor_id {
+            events = events.filter(audit_events::actor_id.eq(actor_id));
+        }
+        if let Some(target) = query.target {
+            events = events.filter(audit_events::target.eq(target));
+        }
+        if let Some(since) = query.since {
+            events = events.filter(audit_events::created_at.ge(since.naive_utc()));
+        }
+        if let Some(until) = query.until {
+            events = events.filter(audit_events::created_at.lt(until.naive_utc()));
+        }
+        if let Some(before) = query.before {
+            events = events.filter(audit_events::id.lt(before));
+        }
+
+        // یک رویداد بیشتر خوانده می‌شود تا وجود صفحه‌ی بعد مشخص شود
+        events
+            .order(audit_events::id.desc())
+            .limit(limit + 1)
+            .select(AuditEvent::as_select())
+            .load(conn)
+    })
+    .await??;
+
+    let next_before = if events.len() as i64 > limit {
+        events.truncate(limit as usize);
+        events.last().map(|event| event.id)
+    } else {
+        None
+    };
+    Ok(HttpResponse::Ok().json(AuditPage { events, next_before }))
+}
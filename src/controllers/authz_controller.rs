@@ -0,0 +1,70 @@
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, FixedOffset, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use crate::config::DbPool;
+use crate::db;
+use crate::errors::ApiError;
+use crate::middleware::jwt::load_user_access;
+use crate::services::authorization::{Attributes, AuthorizationService, Decision, RequestFacts};
+use crate::services::items::item_owner;
+
+#[derive(Deserialize)]
+pub struct EvaluateRequest {
+    pub user_id: i32,
+    // سازمان فرضی توکن؛ در صورت نبود فقط نقش‌های سراسری
+    pub tenant_id: Option<i32>,
+    pub method: String,
+    pub path: String,
+    // الگوی مسیر، مثل `/roles/{user_id}`؛ در صورت نبود همان path استفاده می‌شود
+    pub route: Option<String>,
+    #[serde(default)]
+    pub path_params: BTreeMap<String, String>,
+    pub ip: Option<IpAddr>,
+    // زمان فرضی درخواست (RFC 3339)؛ در صورت نبود زمان فعلی
+    pub time: Option<DateTime<FixedOffset>>,
+}
+
+#[derive(Serialize)]
+struct EvaluateResponse {
+    attributes: Attributes,
+    decision: Decision,
+}
+
+// تابع ارزیابی آزمایشی سیاست‌ها برای یک درخواست فرضی (بدون اجرای آن)
+pub async fn evaluate_policy(
+    form: web::Json<EvaluateRequest>,
+    pool: web::Data<DbPool>,
+    authz: web::Data<AuthorizationService>,
+) -> Result<HttpResponse, ApiError> {
+    let form = form.into_inner();
+    let user_id = form.user_id;
+    let tenant = form.tenant_id;
+
+    let mut facts = RequestFacts {
+        method: form.method.to_uppercase(),
+        route: form.route.unwrap_or_else(|| form.path.clone()),
+        path: form.path,
+        path_params: form.path_params,
+        ip: form.ip,
+        now: form.time.map(|time| time.with_timezone(&Local)).unwrap_or_else(Local::now),
+        item_owner: None,
+    };
+    let item = tenant.zip(facts.item_id());
+
+    let (access, owner) = db::run(&pool, move |conn| {
+        let access = load_user_access(conn, user_id, tenant)?;
+        let owner = match item {
+            Some((tenant, item)) => item_owner(conn, tenant, item)?,
+            None => None,
+        };
+        Ok::<_, diesel::result::Error>((access, owner))
+    })
+    .await??;
+    facts.item_owner = owner;
+    let attributes = facts.attributes(user_id, tenant, Some(&access));
+    let decision = authz.evaluate(&attributes);
+
+    Ok(HttpResponse::Ok().json(EvaluateResponse { attributes, decision }))
+}
@@ -0,0 +1,348 @@
+use actix_web::{web, HttpResponse};
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::sql_types::{Integer, Nullable};
+use serde::Deserialize;
+use crate::config::DbPool;
+use crate::db;
+use crate::errors::ApiError;
+use crate::middleware::auth_user::AuthUser;
+use crate::middleware::jwt::load_role_permission_set;
+use crate::middleware::validated_json::ValidatedJson;
+use crate::models::elevation::{ElevationStatus, NewRoleElevationRequest, RoleElevationRequest};
+use crate::models::user::Role;
+use crate::schema::role_elevation_requests::dsl::*;
+use crate::schema::roles;
+use crate::services::permission_cache::PermissionCache;
+use crate::validation::{Rule, Validate, ValidationErrors, Validator};
+
+// حداکثر مدت ارتقای موقت (۲۴ ساعت)
+const MAX_ELEVATION_MINUTES: i32 = 24 * 60;
+
+#[derive(Deserialize)]
+pub struct ElevationRequestForm {
+    pub role_id: i32,
+    pub reason: String,
+    pub duration_minutes: i32,
+}
+
+impl Validate for ElevationRequestForm {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        Validator::new()
+            .field("reason", self.reason.trim(), &[Rule::Length { min: 1, max: 500 }])
+            .finish()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ElevationListQuery {
+    pub status: Option<String>,
+}
+
+// نتیجه‌ی تصمیم‌گیری روی یک درخواست
+enum Decision {
+    Done(RoleElevationRequest),
+    NotFound,
+    AlreadyDecided,
+    Forbidden(&'static str),
+}
+
+// تابع ثبت درخواست ارتقای موقت نقش توسط کاربر جاری
+pub async fn request_elevation(
+    user: AuthUser,
+    form: ValidatedJson<ElevationRequestForm>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, ApiError> {
+    let form = form.into_inner();
+    if form.duration_minutes <= 0 || form.duration_minutes > MAX_ELEVATION_MINUTES {
+        return Err(ApiError::bad_request(format!("duration_minutes must be between 1 and {}", MAX_ELEVATION_MINUTES)));
+    }
+
+    let new_request = NewRoleElevationRequest {
+        user_id: user.user_id,
+        role_id: form.role_id,
+        reason: form.reason.trim().to_string(),
+        duration_minutes: form.duration_minutes,
+        tenant_id: user.tenant_id,
+    };
+
+    let request = db::run(&pool, move |conn| {
+        let role = roles::table
+            .find(new_request.role_id)
+            .first::<Role>(conn)
+            .optional()?
+            .ok_or_else(|| ApiError::not_found("Role not found"))?;
+        // نقش‌های سیستمی (مثل admin) با ارتقای موقت اعطا نمی‌شوند
+        if role.is_system() {
+            return Err(ApiError::forbidden("System roles cannot be requested"));
+        }
+
+        diesel::insert_into(role_elevation_requests)
+            .values(&new_request)
+            .returning(RoleElevationRequest::as_returning())
+            .get_result(conn)
+            .map_err(ApiError::from)
+    })
+    .await??;
+
+    Ok(HttpResponse::Created().json(request))
+}
+
+// تابع فهرست درخواست‌های سازمان تأییدکننده (پیش‌فرض: در انتظار)
+pub async fn list_elevation_requests(
+    user: AuthUser,
+    query: web::Query<ElevationListQuery>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, ApiError> {
+    let wanted = match query.status.as_deref().map(ElevationStatus::parse) {
+        None => ElevationStatus::Pending,
+        Some(Some(wanted)) => wanted,
+        Some(None) => return Err(ApiError::bad_request("status must be 'pending', 'approved' or 'rejected'")),
+    };
+
+    let requests = db::run(&pool, move |conn| {
+        role_elevation_requests
+            .filter(status.eq(wanted.as_str()))
+            .filter(tenant_id.is_not_distinct_from(user.tenant_id))
+            .order(created_at.asc())
+            .select(RoleElevationRequest::as_select())
+            .load(conn)
+    })
+    .await??;
+
+    Ok(HttpResponse::Ok().json(requests))
+}
+
+// تابع تأیید درخواست: نقش از همین لحظه به مدت درخواست‌شده فعال می‌شود
+// تأییدکننده باید خودش نقش را داشته باشد یا همه‌ی دسترسی‌های آن را، تا نتواند بیش از دسترسی خودش اعطا کند
+pub async fn approve_elevation(
+    user: AuthUser,
+    request_id: web::Path<i32>,
+    pool: web::Data<DbPool>,
+    cache: web::Data<PermissionCache>,
+) -> Result<HttpResponse, ApiError> {
+    decide(user, request_id.into_inner(), ElevationStatus::Approved, pool, cache).await
+}
+
+// تابع رد درخواست
+pub async fn reject_elevation(
+    user: AuthUser,
+    request_id: web::Path<i32>,
+    pool: web::Data<DbPool>,
+    cache: web::Data<PermissionCache>,
+) -> Result<HttpResponse, ApiError> {
+    decide(user, request_id.into_inner(), ElevationStatus::Rejected, pool, cache).await
+}
+
+async fn decide(
+    user: AuthUser,
+    request_id: i32,
+    decision: ElevationStatus,
+    pool: web::Data<DbPool>,
+    cache: web::Data<PermissionCache>,
+) -> Result<HttpResponse, ApiError> {
+    let approver = user.user_id;
+    let approver_tenant = user.tenant_id;
+    let result = db::run(&pool, move |conn| {
+        conn.transaction::<Decision, diesel::result::Error, _>(|conn| {
+            // درخواست‌های سازمان‌های دیگر برای تأییدکننده وجود ندارند
+            let request = match role_elevation_requests
+                .find(request_id)
+                .filter(tenant_id.is_not_distinct_from(approver_tenant))
+                .for_update()
+                .select(RoleElevationRequest::as_select())
+                .first(conn)
+                .optional()?
+            {
+                Some(request) => request,
+                None => return Ok(Decision::NotFound),
+            };
+
+            if ElevationStatus::parse(&request.status) != Some(ElevationStatus::Pending) {
+                return Ok(Decision::AlreadyDecided);
+            }
+            if request.user_id == approver {
+                return Ok(Decision::Forbidden("Cannot decide on your own elevation request"));
+            }
+
+            if decision == ElevationStatus::Approved {
+                let role = roles::table.find(request.role_id).first::<Role>(conn)?;
+                if role.is_system() {
+                    return Ok(Decision::Forbidden("System roles cannot be granted through elevation"));
+                }
+                if !user.has_role(&role.name) && !user.permissions.covers(&load_role_permission_set(conn, role.id)?) {
+                    return Ok(Decision::Forbidden("Cannot approve a role with permissions you do not hold"));
+                }
+
+                // اختصاص دائمی موجود به اختصاص موقت تبدیل نمی‌شود
+                diesel::sql_query(
+                    "INSERT INTO users_roles (user_id, role_id, tenant_id, valid_from, valid_until)
+                    VALUES ($1, $2, $4, NOW(), NOW() + make_interval(mins => $3))
+                    ON CONFLICT (user_id, role_id, (COALESCE(tenant_id, 0))) DO UPDATE
+                        SET valid_from = EXCLUDED.valid_from,
+                            valid_until = EXCLUDED.valid_until,
+                            expiry_processed_at = NULL
+                        WHERE users_roles.valid_until IS NOT NULL",
+                )
+                .bind::<Integer, _>(request.user_id)
+                .bind::<Integer, _>(request.role_id)
+                .bind::<Integer, _>(request.duration_minutes)
+                .bind::<Nullable<Integer>, _>(request.tenant_id)
+                .execute(conn)?;
+            }
+
+            diesel::update(role_elevation_requests.find(request_id))
+                .set((
+                    status.eq(decision.as_str()),
+                    approver_id.eq(Some(approver)),
+                    decided_at.eq(Some(Utc::now().naive_utc())),
+                ))
+                .returning(RoleElevationRequest::as_returning())
+                .get_result(conn)
+                .map(Decision::Done)
+        })
+    })
+    .await??;
+
+    match result {
+        Decision::Done(request) => {
+            // اعلان NOTIFY با کمی تأخیر می‌رسد؛ کش همین نمونه فوراً باطل می‌شود تا نقش بلافاصله فعال باشد
+            cache.invalidate_user(request.user_id);
+            Ok(HttpResponse::Ok().json(request))
+        }
+        Decision::NotFound => Err(ApiError::not_found("Elevation request not found")),
+        Decision::AlreadyDecided => Err(ApiError::conflict("Elevation request already decided")),
+        Decision::Forbidden(message) => Err(ApiError::forbidden(message)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::{header, StatusCode};
+    use actix_web::{test, web, App};
+    use diesel::prelude::*;
+    use serde_json::json;
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use crate::models::elevation::NewRoleElevationRequest;
+    use crate::models::user::Claims;
+    use crate::routes::elevation::config_routes;
+    use crate::schema::{permissions, role_elevation_requests, role_permissions, roles, users};
+    use crate::test_support::{cached_access, database_pool, problem, test_settings, token_for, unavailable_pool};
+
+    fn bearer(user_id: i32) -> (header::HeaderName, String) {
+        (header::AUTHORIZATION, format!("Bearer {}", token_for(Claims { sub: user_id, ..Default::default() })))
+    }
+
+    #[actix_web::test]
+    async fn blank_reason_is_rejected() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_settings()))
+                .app_data(web::Data::new(unavailable_pool()))
+                .app_data(cached_access(1, &[]))
+                .configure(config_routes),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/elevation_requests")
+            .insert_header(bearer(1))
+            .set_json(json!({ "role_id": 1, "reason": "   ", "duration_minutes": 30 }))
+            .to_request();
+        let (status, code) = problem(&app, req).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(code, "validation_failed");
+    }
+
+    // تأییدکننده‌ای که فقط approve_elevation دارد نمی‌تواند admin یا نقشی با دسترسی‌هایی که ندارد اعطا کند
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn approvers_cannot_grant_more_than_they_hold() {
+        let pool = database_pool();
+        let suffix = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+        let mut conn = pool.get().unwrap();
+        let [requester, approver] = ["requester", "approver"].map(|name| {
+            diesel::insert_into(users::table)
+                .values((users::username.eq(format!("{}-{}", name, suffix)), users::password.eq("!")))
+                .returning(users::id)
+                .get_result::<i32>(&mut conn)
+                .unwrap()
+        });
+        let admin = roles::table.filter(roles::name.eq("admin")).select(roles::id).first::<i32>(&mut conn).unwrap();
+        let permission = format!("elevation-test-{}.read", suffix);
+        let custom = diesel::insert_into(roles::table)
+            .values((roles::name.eq(format!("elevation-test-{}", suffix)), roles::role_type.eq("custom")))
+            .returning(roles::id)
+            .get_result::<i32>(&mut conn)
+            .unwrap();
+        let permission_id = diesel::insert_into(permissions::table)
+            .values((permissions::name.eq(&permission), permissions::permission_type.eq("allow")))
+            .returning(permissions::id)
+            .get_result::<i32>(&mut conn)
+            .unwrap();
+        diesel::insert_into(role_permissions::table)
+            .values((role_permissions::role_id.eq(custom), role_permissions::permission_id.eq(permission_id)))
+            .execute(&mut conn)
+            .unwrap();
+        let [admin_request, custom_request] = [admin, custom].map(|role_id| {
+            diesel::insert_into(role_elevation_requests::table)
+                .values(&NewRoleElevationRequest {
+                    user_id: requester,
+                    role_id,
+                    reason: "on call".to_string(),
+                    duration_minutes: 30,
+                    tenant_id: None,
+                })
+                .returning(role_elevation_requests::id)
+                .get_result::<i32>(&mut conn)
+                .unwrap()
+        });
+
+        let approve = |request: i32| {
+            test::TestRequest::post()
+                .uri(&format!("/elevation_requests/{}/approve", request))
+                .insert_header(bearer(approver))
+                .to_request()
+        };
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_settings()))
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(cached_access(approver, &["approve_elevation"]))
+                .configure(config_routes),
+        )
+        .await;
+        for request in [admin_request, custom_request] {
+            let (status, code) = problem(&app, approve(request)).await;
+            assert_eq!((status, code.as_str()), (StatusCode::FORBIDDEN, "forbidden"), "request {}", request);
+        }
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_settings()))
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(cached_access(approver, &["approve_elevation", &permission]))
+                .configure(config_routes),
+        )
+        .await;
+        assert_eq!(test::call_service(&app, approve(custom_request)).await.status(), StatusCode::OK);
+
+        // درخواست نقش سیستمی یا نقش ناموجود ثبت نمی‌شود
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_settings()))
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(cached_access(requester, &[]))
+                .configure(config_routes),
+        )
+        .await;
+        for (role_id, expected) in [(admin, StatusCode::FORBIDDEN), (-1, StatusCode::NOT_FOUND)] {
+            let req = test::TestRequest::post()
+                .uri("/elevation_requests")
+                .insert_header(bearer(requester))
+                .set_json(json!({ "role_id": role_id, "reason": "on call", "duration_minutes": 30 }))
+                .to_request();
+            assert_eq!(problem(&app, req).await.0, expected, "role {}", role_id);
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use actix_web::cookie::{time, Cookie, SameSite};
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::config::{DbPool, Settings};
+use crate::db;
+use crate::errors::ApiError;
+use crate::controllers::user_controller::{issue_login_token, select_login_tenant, TokenResponse};
+use crate::middleware::auth_user::AuthUser;
+use crate::models::identity::UserIdentity;
+use crate::schema::user_identities;
+use crate::services::federation::{consume_login_state, create_login_state, resolve_user, Federation, FederationError};
+use crate::services::oidc::OidcProvider;
+use crate::services::secrets::constant_time_eq;
+use crate::services::sessions::{create_session, SessionOrigin};
+
+// state ورود در کوکی مرورگر هم نگه داشته می‌شود تا callback فقط در همان مرورگر پذیرفته شود
+const LOGIN_STATE_COOKIE: &str = "federated_login_state";
+const LOGIN_STATE_COOKIE_MINUTES: i64 = 10;
+
+#[derive(Deserialize)]
+pub struct CallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+#[derive(Serialize)]
+struct LinkResponse {
+    authorization_url: String,
+}
+
+fn callback_uri(oidc: &OidcProvider, provider: &str) -> String {
+    format!("{}/auth/{}/callback", oidc.issuer(), provider)
+}
+
+fn state_cookie(oidc: &OidcProvider, provider: &str, state: String, max_age: time::Duration) -> Cookie<'static> {
+    Cookie::build(LOGIN_STATE_COOKIE, state)
+        .path(format!("/auth/{}", provider))
+        .http_only(true)
+        .secure(oidc.issuer().starts_with("https://"))
+        .same_site(SameSite::Lax)
+        .max_age(max_age)
+        .finish()
+}
+
+// ثبت state و ساخت آدرس ورود ارائه‌دهنده؛ خروجی (آدرس، state) است
+async fn begin_login(
+    pool: &DbPool,
+    federation: &Federation,
+    oidc: &OidcProvider,
+    provider_name: &str,
+    link_user_id: Option<i32>,
+) -> Result<(String, String), ApiError> {
+    let provider = federation.provider(provider_name)?;
+
+    let name = provider_name.to_string();
+    let pending = db::run(pool, move |conn| {
+        create_login_state(conn, &name, link_user_id)
+    })
+    .await??;
+
+    let url = federation
+        .authorization_url(provider, &callback_uri(oidc, provider_name), &pending)
+        .await?;
+    Ok((url, pending.state))
+}
+
+// تابع شروع ورود با ارائه‌دهنده‌ی بیرونی
+pub async fn start(
+    path: web::Path<String>,
+    pool: web::Data<DbPool>,
+    federation: web::Data<Federation>,
+    oidc: web::Data<OidcProvider>,
+) -> Result<HttpResponse, ApiError> {
+    let provider_name = path.into_inner();
+    let (url, state) = begin_login(&pool, &federation, &oidc, &provider_name, None).await?;
+    Ok(HttpResponse::Found()
+        .cookie(state_cookie(&oidc, &provider_name, state, time::Duration::minutes(LOGIN_STATE_COOKIE_MINUTES)))
+        .insert_header((header::LOCATION, url))
+        .finish())
+}
+
+// تابع بازگشت از ارائه‌دهنده: اعتبارسنجی state و ID token، یافتن یا ساخت کاربر و صدور توکن ورود
+pub async fn callback(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<CallbackQuery>,
+    pool: web::Data<DbPool>,
+    federation: web::Data<Federation>,
+    oidc: web::Data<OidcProvider>,
+    settings: web::Data<Settings>,
+) -> Result<HttpResponse, ApiError> {
+    let provider_name = path.into_inner();
+    let query = query.into_inner();
+    if let Some(error) = query.error {
+        let description = query.error_description.unwrap_or_default();
+        return Err(ApiError::bad_request(format!("Identity provider returned {}: {}", error, description)));
+    }
+    let (Some(code), Some(state)) = (query.code, query.state) else {
+        return Err(ApiError::bad_request("code and state are required"));
+    };
+    let provider = federation.provider(&provider_name)?.clone();
+    let cookie_state = req.cookie(LOGIN_STATE_COOKIE).map(|cookie| cookie.value().to_string());
+    let origin = SessionOrigin::from_request(&req);
+
+    let name = provider_name.clone();
+    let received_state = state.clone();
+    let login = db::run(&pool, move |conn| {
+        consume_login_state(conn, &name, &received_state)
+    })
+    .await??;
+
+    // ورود و اتصال هویت هر دو باید به همان مرورگری برگردند که state را گرفته است؛
+    // وگرنه مهاجم می‌تواند callback اتصال هویت خودش را به مرورگر قربانی بدهد یا برعکس
+    let same_browser = cookie_state.is_some_and(|cookie_state| constant_time_eq(cookie_state.as_bytes(), state.as_bytes()));
+    if !same_browser {
+        return Err(ApiError::bad_request("Login state does not match this browser"));
+    }
+
+    let identity = federation
+        .exchange_code(&provider, &code, &callback_uri(&oidc, &provider_name), &login)
+        .await?;
+
+    let (session, tenant) = db::run(&pool, move |conn| {
+        let user = resolve_user(conn, &provider, &identity, login.link_user_id)?;
+        let tenant = select_login_tenant(conn, user.id, None)?.unwrap_or(None);
+        let session = create_session(conn, user.id, origin)?;
+        Ok::<_, FederationError>((session, tenant))
+    })
+    .await??;
+    let token = issue_login_token(&settings.jwt_secret, &session, tenant).map_err(|err| ApiError::internal(format!("Error generating token: {}", err)))?;
+
+    Ok(HttpResponse::Created()
+        .cookie(state_cookie(&oidc, &provider_name, String::new(), time::Duration::ZERO))
+        .json(TokenResponse { token }))
+}
+
+// تابع شروع اتصال هویت بیرونی به حساب کاربر جاری؛ آدرس ورود ارائه‌دهنده برگردانده می‌شود
+// و کوکی state مثل ورود معمولی تنظیم می‌شود، پس کاربر باید همین مرورگر را به ارائه‌دهنده بفرستد
+pub async fn link_identity(
+    user: AuthUser,
+    path: web::Path<String>,
+    pool: web::Data<DbPool>,
+    federation: web::Data<Federation>,
+    oidc: web::Data<OidcProvider>,
+) -> Result<HttpResponse, ApiError> {
+    if user.api_key_id.is_some() {
+        return Err(ApiError::forbidden("API keys cannot link identities"));
+    }
+    if user.scopes.is_some() {
+        return Err(ApiError::forbidden("Scoped OAuth tokens cannot link identities"));
+    }
+    if user.actor_id.is_some() {
+        return Err(ApiError::forbidden("Identities cannot be linked while impersonating"));
+    }
+
+    let provider_name = path.into_inner();
+    let (authorization_url, state) = begin_login(&pool, &federation, &oidc, &provider_name, Some(user.user_id)).await?;
+    Ok(HttpResponse::Ok()
+        .cookie(state_cookie(&oidc, &provider_name, state, time::Duration::minutes(LOGIN_STATE_COOKIE_MINUTES)))
+        .json(LinkResponse { authorization_url }))
+}
+
+// تابع فهرست هویت‌های بیرونی متصل به کاربر جاری
+pub async fn list_identities(user: AuthUser, pool: web::Data<DbPool>) -> Result<HttpResponse, ApiError> {
+    let owner = user.user_id;
+
+    let identities = db::run(&pool, move |conn| {
+        user_identities::table
+            .filter(user_identities::user_id.eq(owner))
+            .order(user_identities::created_at.asc())
+            .select(UserIdentity::as_select())
+            .load(conn)
+    })
+    .await??;
+
+    Ok(HttpResponse::Ok().json(identities))
+}
+
+// تابع قطع اتصال هویت بیرونی
+pub async fn unlink_identity(user: AuthUser, identity_path: web::Path<i32>, pool: web::Data<DbPool>) -> Result<HttpResponse, ApiError> {
+    if user.api_key_id.is_some() {
+        return Err(ApiError::forbidden("API keys cannot unlink identities"));
+    }
+
+    let owner = user.user_id;
+    let identity_id = identity_path.into_inner();
+
+    let result = db::run(&pool, move |conn| {
+        diesel::delete(
+            user_identities::table
+                .find(identity_id)
+                .filter(user_identities::user_id.eq(owner)),
+        )
+        .execute(conn)
+    })
+    .await;
+
+    match result?? {
+        0 => Err(ApiError::not_found("Identity not found")),
+        _ => Ok(HttpResponse::NoContent().finish()),
+    }
+}
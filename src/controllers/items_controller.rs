@@ -1,83 +1,209 @@
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::{web, HttpResponse};
 use diesel::prelude::*;
 use crate::schema::items::dsl::*;
 use crate::config::DbPool;
+use crate::db;
+use crate::errors::ApiError;
+use crate::middleware::auth_user::{AuthUser, CurrentTenant};
+use crate::middleware::validated_json::ValidatedJson;
 use crate::models::item::{Item, NewItem};
+use crate::services::items::in_tenant;
 
-pub async fn get_items(pool: web::Data<DbPool>) -> impl Responder {
-    let conn = pool.get().expect("Couldn't get db connection from pool");
-    let result = web::block(move || {
-        let mut conn = conn;
-        items.load::<Item>(&mut conn)
+pub async fn get_items(pool: web::Data<DbPool>, tenant: CurrentTenant) -> Result<HttpResponse, ApiError> {
+    let CurrentTenant(tenant) = tenant;
+    let item_list = db::run(&pool, move |conn| {
+        in_tenant(conn, tenant, |conn| items.filter(tenant_id.eq(tenant)).load::<Item>(conn))
     })
-    .await;
+    .await??;
 
-    match result {
-        Ok(Ok(item_list)) => HttpResponse::Ok().json(item_list),
-        Ok(Err(query_err)) => HttpResponse::InternalServerError().body(format!("Query error: {}", query_err)),
-        Err(blocking_err) => HttpResponse::InternalServerError().body(format!("Blocking error: {}", blocking_err)),
-    }
+    Ok(HttpResponse::Ok().json(item_list))
 }
 
 pub async fn create_item(
     pool: web::Data<DbPool>,
-    new_item: web::Json<NewItem>,
-) -> impl Responder {
-    let conn = pool.get().expect("Couldn't get db connection from pool");
+    user: AuthUser,
+    tenant: CurrentTenant,
+    new_item: ValidatedJson<NewItem>,
+) -> Result<HttpResponse, ApiError> {
+    let CurrentTenant(tenant) = tenant;
+    let owner = user.user_id;
     let new_item = new_item.into_inner();
-    let result = web::block(move || {
-        let mut conn = conn;
-        diesel::insert_into(items)
-            .values(new_item)
-            .get_result::<Item>(&mut conn)
+    let inserted_item = db::run(&pool, move |conn| {
+        in_tenant(conn, tenant, |conn| {
+            diesel::insert_into(items)
+                .values((new_item, tenant_id.eq(tenant), owner_id.eq(Some(owner))))
+                .get_result::<Item>(conn)
+        })
     })
-    .await;
+    .await??;
 
-    match result {
-        Ok(Ok(inserted_item)) => HttpResponse::Ok().json(inserted_item),
-        Ok(Err(query_err)) => HttpResponse::InternalServerError().body(format!("Query error: {}", query_err)),
-        Err(blocking_err) => HttpResponse::InternalServerError().body(format!("Blocking error: {}", blocking_err)),
-    }
+    Ok(HttpResponse::Ok().json(inserted_item))
 }
 
 pub async fn update_item(
     pool: web::Data<DbPool>,
+    tenant: CurrentTenant,
     item_id: web::Path<i32>,
-    updated_item: web::Json<NewItem>,
-) -> impl Responder {
-    let conn = pool.get().expect("Couldn't get db connection from pool");
+    updated_item: ValidatedJson<NewItem>,
+) -> Result<HttpResponse, ApiError> {
+    let CurrentTenant(tenant) = tenant;
     let target_id = item_id.into_inner();
     let new_data = updated_item.into_inner();
-    let result = web::block(move || {
-        let mut conn = conn;
-        diesel::update(items.find(target_id))
-            .set(name.eq(new_data.name))
-            .get_result::<Item>(&mut conn)
+    let item = db::run(&pool, move |conn| {
+        in_tenant(conn, tenant, |conn| {
+            diesel::update(items.find(target_id).filter(tenant_id.eq(tenant)))
+                .set(name.eq(new_data.name))
+                .get_result::<Item>(conn)
+        })
     })
-    .await;
+    .await??;
 
-    match result {
-        Ok(Ok(item)) => HttpResponse::Ok().json(item),
-        Ok(Err(query_err)) => HttpResponse::InternalServerError().body(format!("Query error: {}", query_err)),
-        Err(blocking_err) => HttpResponse::InternalServerError().body(format!("Blocking error: {}", blocking_err)),
-    }
+    Ok(HttpResponse::Ok().json(item))
 }
 
 pub async fn delete_item(
     pool: web::Data<DbPool>,
+    tenant: CurrentTenant,
     item_id: web::Path<i32>,
-) -> impl Responder {
-    let conn = pool.get().expect("Couldn't get db connection from pool");
+) -> Result<HttpResponse, ApiError> {
+    let CurrentTenant(tenant) = tenant;
     let target_id = item_id.into_inner();
-    let result = web::block(move || {
-        let mut conn = conn;
-        diesel::delete(items.find(target_id)).execute(&mut conn)
+    let deleted = db::run(&pool, move |conn| {
+        in_tenant(conn, tenant, |conn| {
+            diesel::delete(items.find(target_id).filter(tenant_id.eq(tenant))).execute(conn)
+        })
     })
-    .await;
+    .await??;
+
+    match deleted {
+        0 => Err(ApiError::not_found("Item not found")),
+        _ => Ok(HttpResponse::Ok().body("Item deleted")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::{header, StatusCode};
+    use actix_web::{test, web, App};
+    use diesel::prelude::*;
+    use serde_json::{json, Value};
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use crate::models::user::Claims;
+    use crate::routes::items::config_routes;
+    use crate::schema::users;
+    use crate::services::authorization::AuthorizationService;
+    use crate::test_support::{database_pool, problem, read_only_pool, test_settings, token_for, unavailable_pool};
+
+    fn bearer(tenant: Option<i32>) -> (header::HeaderName, String) {
+        bearer_as(1, tenant)
+    }
+
+    fn bearer_as(user_id: i32, tenant: Option<i32>) -> (header::HeaderName, String) {
+        let token = token_for(Claims { sub: user_id, tenant, ..Default::default() });
+        (header::AUTHORIZATION, format!("Bearer {}", token))
+    }
+
+    #[actix_web::test]
+    async fn item_routes_return_503_when_pool_is_unavailable() {
+        let app = test::init_service(App::new().app_data(web::Data::new(unavailable_pool())).app_data(web::Data::new(test_settings())).configure(config_routes)).await;
+
+        let requests = [
+            test::TestRequest::get().uri("/items"),
+            test::TestRequest::post().uri("/items").set_json(json!({ "name": "item" })),
+            test::TestRequest::put().uri("/items/1").set_json(json!({ "name": "item" })),
+            test::TestRequest::delete().uri("/items/1"),
+        ];
+        for req in requests {
+            let (status, code) = problem(&app, req.insert_header(bearer(Some(1))).to_request()).await;
+            assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+            assert_eq!(code, "service_unavailable");
+        }
+    }
+
+    #[actix_web::test]
+    async fn items_require_an_organization() {
+        let app = test::init_service(App::new().app_data(web::Data::new(unavailable_pool())).app_data(web::Data::new(test_settings())).configure(config_routes)).await;
+        let req = test::TestRequest::get().uri("/items").insert_header(bearer(None)).to_request();
+
+        let (status, code) = problem(&app, req).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(code, "forbidden");
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn create_item_returns_500_when_query_fails() {
+        let pool = read_only_pool();
+        let app = test::init_service(App::new().app_data(web::Data::new(pool)).app_data(web::Data::new(test_settings())).configure(config_routes)).await;
+        let req = test::TestRequest::post()
+            .uri("/items")
+            .insert_header(bearer(Some(1)))
+            .set_json(json!({ "name": "item" }))
+            .to_request();
+
+        let (status, code) = problem(&app, req).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(code, "internal_error");
+    }
+
+    // سیاست `item.owner_id` با مالک ثبت‌شده هنگام ساخت آیتم ارزیابی می‌شود
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn owner_policies_see_the_item_owner() {
+        let pool = database_pool();
+        let suffix = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+        let [owner, other] = ["owner", "other"].map(|name| {
+            diesel::insert_into(users::table)
+                .values((users::username.eq(format!("items-{}-{}", name, suffix)), users::password.eq("!")))
+                .returning(users::id)
+                .get_result::<i32>(&mut pool.get().unwrap())
+                .unwrap()
+        });
+        let policy_file = std::env::temp_dir().join(format!("item-owner-policies-{}.json", suffix));
+        fs::write(
+            &policy_file,
+            r#"{ "policies": [{ "id": "owners-edit-items", "effect": "allow", "methods": ["PUT", "DELETE"], "paths": ["/items/{id}"],
+                "conditions": [{ "attribute": "item.owner_id", "op": "eq_attribute", "value": "user.id" }] }] }"#,
+        )
+        .unwrap();
+        let authz = AuthorizationService::load(&policy_file).unwrap();
+        fs::remove_file(&policy_file).unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool))
+                .app_data(web::Data::new(test_settings()))
+                .app_data(web::Data::new(authz))
+                .configure(config_routes),
+        )
+        .await;
+
+        let req = test::TestRequest::post().uri("/items").insert_header(bearer_as(owner, Some(1))).set_json(json!({ "name": "owned" }));
+        let item: Value = test::call_and_read_body_json(&app, req.to_request()).await;
+        assert_eq!(item["owner_id"], json!(owner));
+
+        let rename = |user_id: i32| {
+            test::TestRequest::put()
+                .uri(&format!("/items/{}", item["id"]))
+                .insert_header(bearer_as(user_id, Some(1)))
+                .set_json(json!({ "name": "renamed" }))
+                .to_request()
+        };
+        assert_eq!(problem(&app, rename(other)).await, (StatusCode::FORBIDDEN, "forbidden".to_string()));
+        assert_eq!(test::call_service(&app, rename(owner)).await.status(), StatusCode::OK);
+        let delete = test::TestRequest::delete().uri(&format!("/items/{}", item["id"])).insert_header(bearer_as(owner, Some(1)));
+        assert_eq!(test::call_service(&app, delete.to_request()).await.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn deleting_a_missing_item_returns_404() {
+        let app = test::init_service(App::new().app_data(web::Data::new(database_pool())).app_data(web::Data::new(test_settings())).configure(config_routes)).await;
+        let req = test::TestRequest::delete().uri("/items/-1").insert_header(bearer(Some(1))).to_request();
 
-    match result {
-        Ok(Ok(_)) => HttpResponse::Ok().body("Item deleted"),
-        Ok(Err(query_err)) => HttpResponse::InternalServerError().body(format!("Query error: {}", query_err)),
-        Err(blocking_err) => HttpResponse::InternalServerError().body(format!("Blocking error: {}", blocking_err)),
+        let (status, code) = problem(&app, req).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(code, "not_found");
     }
 }
@@ -0,0 +1,65 @@
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse};
+use crate::config::{DbPool, Settings};
+use crate::errors::ApiError;
+use crate::services::pool_metrics::PoolMetrics;
+use crate::services::secrets::constant_time_eq;
+
+// تابع خروجی metrics برای Prometheus؛ بدون اتصال به پایگاه داده تا هنگام پر بودن pool هم پاسخ دهد
+// اگر metrics_token تنظیم شده باشد فقط با همان توکن Bearer پاسخ می‌دهد، وگرنه عمداً عمومی است
+pub async fn metrics(
+    req: HttpRequest,
+    settings: web::Data<Settings>,
+    pool: web::Data<DbPool>,
+    metrics: web::Data<PoolMetrics>,
+) -> Result<HttpResponse, ApiError> {
+    if let Some(expected) = &settings.metrics_token {
+        let token = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        if !token.is_some_and(|token| constant_time_eq(token.as_bytes(), expected.as_bytes())) {
+            return Err(ApiError::unauthorized("Invalid metrics token"));
+        }
+    }
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(metrics.render(pool.state(), pool.max_size())))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use actix_web::{test, App};
+    use crate::test_support::{test_settings, unavailable_pool};
+    use super::*;
+
+    async fn status(metrics_token: Option<&str>, authorization: Option<&str>) -> StatusCode {
+        let settings = Settings {
+            metrics_token: metrics_token.map(str::to_string),
+            ..test_settings()
+        };
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(settings))
+                .app_data(web::Data::new(unavailable_pool()))
+                .app_data(web::Data::new(PoolMetrics::default()))
+                .route("/metrics", web::get().to(metrics)),
+        )
+        .await;
+        let mut req = test::TestRequest::get().uri("/metrics");
+        if let Some(authorization) = authorization {
+            req = req.insert_header((header::AUTHORIZATION, authorization));
+        }
+        test::call_service(&app, req.to_request()).await.status()
+    }
+
+    #[actix_web::test]
+    async fn configured_token_is_required() {
+        assert_eq!(status(None, None).await, StatusCode::OK);
+        assert_eq!(status(Some("scrape"), None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(Some("scrape"), Some("Bearer wrong")).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(Some("scrape"), Some("Bearer scrape")).await, StatusCode::OK);
+    }
+}
@@ -1,2 +1,13 @@
+pub mod admin_controller;
+pub mod api_key_controller;
+pub mod audit_controller;
+pub mod authz_controller;
+pub mod elevation_controller;
+pub mod federation_controller;
 pub mod items_controller;
+pub mod metrics_controller;
+pub mod oauth_controller;
+pub mod oidc_controller;
+pub mod organization_controller;
+pub mod session_controller;
 pub mod user_controller;
@@ -0,0 +1,624 @@
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bcrypt::verify;
+use diesel::prelude::*;
+use log::error;
+use serde::{Deserialize, Serialize};
+use url::Url;
+use crate::config::{DbPool, Settings};
+use crate::db;
+use crate::errors::ApiError;
+use crate::middleware::auth_user::AuthUser;
+use crate::controllers::user_controller::select_login_tenant;
+use crate::models::oauth::{GrantType, NewOAuthClient, OAuthClient};
+use crate::models::user::User;
+use crate::schema::{oauth_clients, users};
+use crate::services::oauth::{
+    authenticate_client, client_credentials_token, create_authorization_code, exchange_authorization_code, find_client,
+    introspect, refresh_access_token, resolve_scopes, revoke, AuthorizationGrant, OAuthError,
+};
+use crate::services::oidc::{OidcProvider, OIDC_SCOPES};
+use crate::services::secrets::{hash_secret, random_string};
+
+const CLIENT_ID_LEN: usize = 24;
+const CLIENT_SECRET_LEN: usize = 48;
+
+#[derive(Deserialize)]
+pub struct RegisterClientForm {
+    pub name: String,
+    #[serde(default)]
+    pub redirect_uris: Vec<String>,
+    pub grant_types: Vec<String>,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    // کلاینت عمومی (SPA) secret ندارد
+    pub confidential: bool,
+    pub service_user_id: Option<i32>,
+}
+
+#[derive(Serialize)]
+struct RegisteredClient {
+    client: OAuthClient,
+    // secret فقط همین یک بار برگردانده می‌شود
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_secret: Option<String>,
+}
+
+// پارامترهای درخواست مجوز (RFC 6749 بخش 4.1.1 و RFC 7636)
+#[derive(Deserialize, Clone)]
+pub struct AuthorizeParams {
+    pub response_type: String,
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub scope: Option<String>,
+    pub state: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+    pub nonce: Option<String>,
+}
+
+// فرم صفحه‌ی رضایت: ورود کاربر و تأیید یا رد دسترسی کلاینت
+#[derive(Deserialize)]
+pub struct AuthorizeForm {
+    #[serde(flatten)]
+    pub params: AuthorizeParams,
+    pub username: String,
+    pub password: String,
+    pub organization_id: Option<String>,
+    // `approve` یا `deny`
+    pub decision: String,
+}
+
+#[derive(Deserialize)]
+pub struct TokenForm {
+    pub grant_type: String,
+    pub code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub code_verifier: Option<String>,
+    pub refresh_token: Option<String>,
+    pub scope: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+// بدنه‌ی introspection و revocation (RFC 7662 / RFC 7009)
+#[derive(Deserialize)]
+pub struct TokenHintForm {
+    pub token: String,
+    #[allow(dead_code)] // هر دو نوع توکن بدون توجه به hint جستجو می‌شوند
+    pub token_type_hint: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+#[derive(Serialize)]
+struct OAuthErrorBody {
+    error: &'static str,
+    error_description: String,
+}
+
+// خطای درخواست مجوز: تا وقتی کلاینت و redirect_uri معتبر نشده‌اند نباید redirect کرد
+// خطای داخلی فقط در لاگ ثبت می‌شود و به کاربر 500 بدون جزئیات برمی‌گردد
+enum AuthorizeError {
+    Page(String),
+    Redirect(&'static str, String),
+    Server(String),
+}
+
+// تابع ثبت کلاینت OAuth
+// scopeهای کلاینت از دسترسی‌های خود ثبت‌کننده فراتر نمی‌روند و کاربر سرویس باید خود او یا یک حساب سرویس باشد؛
+// بدون service_user_id، کلاینت client_credentials یک حساب سرویس اختصاصی بدون نقش می‌گیرد
+pub async fn register_client(
+    user: AuthUser,
+    form: web::Json<RegisterClientForm>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, ApiError> {
+    let form = form.into_inner();
+
+    let mut grant_types = Vec::new();
+    for grant in &form.grant_types {
+        match GrantType::parse(grant) {
+            Some(grant) => grant_types.push(grant),
+            None => return Err(ApiError::bad_request(format!("Unsupported grant type '{}'", grant))),
+        }
+    }
+    let client_credentials = grant_types.contains(&GrantType::ClientCredentials);
+    if client_credentials && !form.confidential {
+        return Err(ApiError::bad_request("client_credentials requires a confidential client"));
+    }
+    if grant_types.contains(&GrantType::AuthorizationCode) && form.redirect_uris.is_empty() {
+        return Err(ApiError::bad_request("authorization_code requires at least one redirect_uri"));
+    }
+    if let Some(invalid) = form.redirect_uris.iter().find(|uri| Url::parse(uri).is_err()) {
+        return Err(ApiError::bad_request(format!("Invalid redirect_uri '{}'", invalid)));
+    }
+    if let Some(scope) = form.scopes.iter().find(|scope| !OIDC_SCOPES.contains(&scope.as_str()) && !user.has_permission(scope)) {
+        return Err(ApiError::forbidden(format!("Scope '{}' exceeds your own permissions", scope)));
+    }
+
+    let client_id = random_string(CLIENT_ID_LEN);
+    let client_secret = form.confidential.then(|| random_string(CLIENT_SECRET_LEN));
+    let client_secret_hash = client_secret.as_deref().map(hash_secret);
+    let caller = user.user_id;
+
+    let client = db::run(&pool, move |conn| {
+        conn.transaction(|conn| {
+            let service_user_id = match form.service_user_id {
+                Some(id) if id == caller => Some(id),
+                Some(id) => {
+                    let is_service_account = users::table
+                        .find(id)
+                        .select(users::is_service_account)
+                        .first::<bool>(conn)
+                        .optional()?;
+                    match is_service_account {
+                        Some(true) => Some(id),
+                        Some(false) => return Err(ApiError::forbidden("service_user_id must be a service account or yourself")),
+                        None => return Err(ApiError::bad_request(format!("Unknown service user {}", id))),
+                    }
+                }
+                // رمز عبور `!` هش bcrypt معتبری نیست، پس هیچ رمزی با آن تأیید نمی‌شود
+                None if client_credentials => Some(
+                    diesel::insert_into(users::table)
+                        .values((
+                            users::username.eq(format!("oauth-client:{}", client_id)),
+                            users::password.eq("!"),
+                            users::is_service_account.eq(true),
+                        ))
+                        .returning(users::id)
+                        .get_result::<i32>(conn)?,
+                ),
+                None => None,
+            };
+
+            let new_client = NewOAuthClient {
+                client_id,
+                client_secret_hash,
+                name: form.name,
+                redirect_uris: form.redirect_uris,
+                grant_types: grant_types.iter().map(|grant| grant.as_str().to_string()).collect(),
+                scopes: form.scopes,
+                service_user_id,
+            };
+            diesel::insert_into(oauth_clients::table)
+                .values(&new_client)
+                .returning(OAuthClient::as_returning())
+                .get_result(conn)
+                .map_err(ApiError::from)
+        })
+    })
+    .await??;
+
+    Ok(HttpResponse::Created().json(RegisteredClient { client, client_secret }))
+}
+
+// تابع فهرست کلاینت‌ها
+pub async fn list_clients(pool: web::Data<DbPool>) -> Result<HttpResponse, ApiError> {
+    let clients = db::run(&pool, move |conn| {
+        oauth_clients::table
+            .order(oauth_clients::created_at.asc())
+            .select(OAuthClient::as_select())
+            .load(conn)
+    })
+    .await??;
+
+    Ok(HttpResponse::Ok().json(clients))
+}
+
+fn validate_authorize(conn: &mut PgConnection, params: &AuthorizeParams) -> Result<(OAuthClient, Vec<String>), AuthorizeError> {
+    let client = find_client(conn, &params.client_id)
+        .map_err(|err| AuthorizeError::Server(format!("Cannot load OAuth client {}: {}", params.client_id, err)))?
+        .ok_or_else(|| AuthorizeError::Page("Unknown client".to_string()))?;
+    if !client.redirect_uris.contains(&params.redirect_uri) {
+        return Err(AuthorizeError::Page("Invalid redirect_uri".to_string()));
+    }
+
+    if params.response_type != "code" {
+        return Err(AuthorizeError::Redirect("unsupported_response_type", "Only response_type=code is supported".to_string()));
+    }
+    if !client.allows_grant(GrantType::AuthorizationCode) {
+        return Err(AuthorizeError::Redirect("unauthorized_client", "Client may not use authorization_code".to_string()));
+    }
+    match (&params.code_challenge, params.code_challenge_method.as_deref()) {
+        (Some(_), Some("S256")) => {}
+        (Some(_), _) => return Err(AuthorizeError::Redirect("invalid_request", "code_challenge_method must be S256".to_string())),
+        (None, _) if !client.is_confidential() => {
+            return Err(AuthorizeError::Redirect("invalid_request", "PKCE is required for public clients".to_string()))
+        }
+        (None, _) => {}
+    }
+
+    let scopes = resolve_scopes(&client, params.scope.as_deref())
+        .map_err(|err| AuthorizeError::Redirect(err.code(), err.to_string()))?;
+    Ok((client, scopes))
+}
+
+fn redirect_with(redirect_uri: &str, pairs: &[(&str, &str)], state: Option<&str>) -> HttpResponse {
+    let Ok(mut url) = Url::parse(redirect_uri) else {
+        return ApiError::bad_request("Invalid redirect_uri").error_response();
+    };
+    {
+        let mut query = url.query_pairs_mut();
+        for (name, value) in pairs {
+            query.append_pair(name, value);
+        }
+        if let Some(state) = state {
+            query.append_pair("state", state);
+        }
+    }
+    HttpResponse::Found().insert_header((header::LOCATION, url.to_string())).finish()
+}
+
+fn authorize_error_response(params: &AuthorizeParams, err: AuthorizeError) -> HttpResponse {
+    match err {
+        AuthorizeError::Page(message) => ApiError::bad_request(message).error_response(),
+        AuthorizeError::Server(reason) => ApiError::internal(reason).error_response(),
+        AuthorizeError::Redirect(code, description) => redirect_with(
+            &params.redirect_uri,
+            &[("error", code), ("error_description", &description)],
+            params.state.as_deref(),
+        ),
+    }
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+// صفحه‌ی رضایت؛ پارامترهای درخواست به‌صورت فیلد پنهان دوباره ارسال می‌شوند
+fn consent_page(client: &OAuthClient, scopes: &[String], params: &AuthorizeParams, error: Option<&str>) -> String {
+    let hidden = [
+        ("response_type", Some(params.response_type.as_str())),
+        ("client_id", Some(params.client_id.as_str())),
+        ("redirect_uri", Some(params.redirect_uri.as_str())),
+        ("scope", Some(&scopes.join(" ") as &str)),
+        ("state", params.state.as_deref()),
+        ("code_challenge", params.code_challenge.as_deref()),
+        ("code_challenge_method", params.code_challenge_method.as_deref()),
+        ("nonce", params.nonce.as_deref()),
+    ]
+    .iter()
+    .filter_map(|(name, value)| {
+        value.map(|value| format!("<input type=\"hidden\" name=\"{}\" value=\"{}\">", name, escape_html(value)))
+    })
+    .collect::<Vec<_>>()
+    .join("\n    ");
+
+    let scope_items = scopes
+        .iter()
+        .map(|scope| format!("<li>{}</li>", escape_html(scope)))
+        .collect::<Vec<_>>()
+        .join("");
+    let error = error
+        .map(|error| format!("<p class=\"error\">{}</p>", escape_html(error)))
+        .unwrap_or_default();
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"><title>Authorize {name}</title></head>
+<body>
+  <h1>{name} wants to access your account</h1>
+  <p>Requested permissions:</p>
+  <ul>{scope_items}</ul>
+  {error}
+  <form method="post" action="/oauth/authorize">
+    {hidden}
+    <label>Username <input name="username" autocomplete="username" required></label>
+    <label>Password <input name="password" type="password" autocomplete="current-password" required></label>
+    <label>Organization ID (optional) <input name="organization_id" inputmode="numeric"></label>
+    <button type="submit" name="decision" value="approve">Allow</button>
+    <button type="submit" name="decision" value="deny">Deny</button>
+  </form>
+</body>
+</html>"#,
+        name = escape_html(&client.name),
+    )
+}
+
+fn html(status: actix_web::http::StatusCode, body: String) -> HttpResponse {
+    HttpResponse::build(status).content_type("text/html; charset=utf-8").body(body)
+}
+
+// تابع نمایش صفحه‌ی رضایت
+pub async fn authorize_page(query: web::Query<AuthorizeParams>, pool: web::Data<DbPool>) -> Result<HttpResponse, ApiError> {
+    let params = query.into_inner();
+
+    let validate_params = params.clone();
+    let result = db::run(&pool, move |conn| {
+        validate_authorize(conn, &validate_params)
+    })
+    .await;
+
+    Ok(match result? {
+        Ok((client, scopes)) => html(actix_web::http::StatusCode::OK, consent_page(&client, &scopes, &params, None)),
+        Err(err) => authorize_error_response(&params, err),
+    })
+}
+
+// نتیجه‌ی ارسال فرم رضایت
+enum Consent {
+    Code(String),
+    Denied,
+    InvalidCredentials(OAuthClient, Vec<String>),
+    Rejected(&'static str),
+}
+
+// تابع ثبت رضایت: بررسی رمز عبور کاربر و صدور کد مجوز
+pub async fn authorize_submit(form: web::Form<AuthorizeForm>, pool: web::Data<DbPool>) -> Result<HttpResponse, ApiError> {
+    let form = form.into_inner();
+    let params = form.params.clone();
+
+    let result = db::run(&pool, move |conn| {
+        let (client, scopes) = match validate_authorize(conn, &form.params) {
+            Ok(validated) => validated,
+            Err(err) => return Ok(Err(err)),
+        };
+        if form.decision != "approve" {
+            return Ok(Ok(Consent::Denied));
+        }
+
+        let user = users::table
+            .filter(users::username.eq(&form.username))
+            .first::<User>(conn)
+            .optional()?;
+        let Some(user) = user.filter(|user| !user.is_service_account && verify(&form.password, &user.password).unwrap_or(false)) else {
+            return Ok(Ok(Consent::InvalidCredentials(client, scopes)));
+        };
+
+        let requested_tenant = match form.organization_id.as_deref().map(str::trim).filter(|id| !id.is_empty()) {
+            None => None,
+            Some(id) => match id.parse::<i32>() {
+                Ok(id) => Some(id),
+                Err(_) => return Ok(Ok(Consent::Rejected("Invalid organization_id"))),
+            },
+        };
+        let tenant = match select_login_tenant(conn, user.id, requested_tenant)? {
+            Ok(tenant) => tenant,
+            Err(reason) => return Ok(Ok(Consent::Rejected(reason))),
+        };
+
+        let grant = AuthorizationGrant {
+            user_id: user.id,
+            tenant_id: tenant,
+            scopes,
+            nonce: form.params.nonce.clone(),
+        };
+        create_authorization_code(conn, &client, grant, &form.params.redirect_uri, form.params.code_challenge.clone())
+        .map(|code| Ok(Consent::Code(code)))
+    })
+    .await;
+
+    Ok(match result?? {
+        Ok(Consent::Code(code)) => redirect_with(&params.redirect_uri, &[("code", &code)], params.state.as_deref()),
+        Ok(Consent::Denied) => authorize_error_response(
+            &params,
+            AuthorizeError::Redirect("access_denied", "The user denied the request".to_string()),
+        ),
+        Ok(Consent::InvalidCredentials(client, scopes)) => html(
+            actix_web::http::StatusCode::UNAUTHORIZED,
+            consent_page(&client, &scopes, &params, Some("Invalid credentials")),
+        ),
+        Ok(Consent::Rejected(reason)) => return Err(ApiError::forbidden(reason)),
+        Err(err) => authorize_error_response(&params, err),
+    })
+}
+
+// احراز هویت کلاینت با HTTP Basic یا client_id/client_secret در بدنه
+fn client_credentials(req: &HttpRequest, client_id: Option<String>, client_secret: Option<String>) -> Option<(String, Option<String>)> {
+    let basic = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|encoded| STANDARD.decode(encoded).ok())
+        .and_then(|decoded| String::from_utf8(decoded).ok())
+        .and_then(|decoded| decoded.split_once(':').map(|(id, secret)| (id.to_string(), Some(secret.to_string()))));
+
+    basic.or_else(|| client_id.map(|id| (id, client_secret)))
+}
+
+// خطای پایگاه داده یا امضای توکن فقط در لاگ ثبت می‌شود؛ کلاینت `server_error` بدون جزئیات می‌گیرد
+fn oauth_error_response(err: OAuthError) -> HttpResponse {
+    let body = OAuthErrorBody {
+        error: err.code(),
+        error_description: err.to_string(),
+    };
+    match err {
+        OAuthError::InvalidClient => HttpResponse::Unauthorized()
+            .insert_header((header::WWW_AUTHENTICATE, "Basic realm=\"oauth\""))
+            .json(body),
+        OAuthError::Database(_) | OAuthError::Token(_) => {
+            error!("OAuth server error: {}", body.error_description);
+            HttpResponse::InternalServerError().json(OAuthErrorBody {
+                error: body.error,
+                error_description: "Internal server error".to_string(),
+            })
+        }
+        _ => HttpResponse::BadRequest().json(body),
+    }
+}
+
+// تابع صدور توکن
+pub async fn token(
+    req: HttpRequest,
+    form: web::Form<TokenForm>,
+    pool: web::Data<DbPool>,
+    oidc: web::Data<OidcProvider>,
+    settings: web::Data<Settings>,
+) -> Result<HttpResponse, ApiError> {
+    let form = form.into_inner();
+    let Some((client_id, client_secret)) = client_credentials(&req, form.client_id.clone(), form.client_secret.clone()) else {
+        return Ok(oauth_error_response(OAuthError::InvalidClient));
+    };
+
+    let result = db::run(&pool, move |conn| {
+        let client = authenticate_client(conn, &client_id, client_secret.as_deref())?;
+        match GrantType::parse(&form.grant_type) {
+            Some(GrantType::AuthorizationCode) => {
+                let code = form.code.ok_or_else(|| OAuthError::InvalidRequest("code is required".to_string()))?;
+                exchange_authorization_code(
+                    conn,
+                    &settings.jwt_secret,
+                    &client,
+                    &code,
+                    form.redirect_uri.as_deref(),
+                    form.code_verifier.as_deref(),
+                    &oidc,
+                )
+            }
+            Some(GrantType::RefreshToken) => {
+                let refresh_token = form
+                    .refresh_token
+                    .ok_or_else(|| OAuthError::InvalidRequest("refresh_token is required".to_string()))?;
+                refresh_access_token(conn, &settings.jwt_secret, &client, &refresh_token, form.scope.as_deref(), &oidc)
+            }
+            Some(GrantType::ClientCredentials) => client_credentials_token(conn, &settings.jwt_secret, &client, form.scope.as_deref()),
+            None => Err(OAuthError::UnsupportedGrantType),
+        }
+    })
+    .await;
+
+    Ok(match result? {
+        Ok(tokens) => HttpResponse::Ok()
+            .insert_header((header::CACHE_CONTROL, "no-store"))
+            .json(tokens),
+        Err(err) => oauth_error_response(err),
+    })
+}
+
+// تابع introspection؛ فقط کلاینت‌های محرمانه (سرورهای منبع) اجازه دارند
+pub async fn introspect_token(
+    req: HttpRequest,
+    form: web::Form<TokenHintForm>,
+    pool: web::Data<DbPool>,
+    settings: web::Data<Settings>,
+) -> Result<HttpResponse, ApiError> {
+    let form = form.into_inner();
+    let Some((client_id, client_secret)) = client_credentials(&req, form.client_id, form.client_secret) else {
+        return Ok(oauth_error_response(OAuthError::InvalidClient));
+    };
+
+    let result = db::run(&pool, move |conn| {
+        let client = authenticate_client(conn, &client_id, client_secret.as_deref())?;
+        if !client.is_confidential() {
+            return Err(OAuthError::UnauthorizedClient("Only confidential clients may introspect tokens".to_string()));
+        }
+        introspect(conn, &settings.jwt_secret, &form.token).map_err(OAuthError::from)
+    })
+    .await;
+
+    Ok(match result? {
+        Ok(introspection) => HttpResponse::Ok().json(introspection),
+        Err(err) => oauth_error_response(err),
+    })
+}
+
+// تابع ابطال توکن؛ برای توکن ناشناخته هم 200 برمی‌گرداند
+pub async fn revoke_token(
+    req: HttpRequest,
+    form: web::Form<TokenHintForm>,
+    pool: web::Data<DbPool>,
+    settings: web::Data<Settings>,
+) -> Result<HttpResponse, ApiError> {
+    let form = form.into_inner();
+    let Some((client_id, client_secret)) = client_credentials(&req, form.client_id, form.client_secret) else {
+        return Ok(oauth_error_response(OAuthError::InvalidClient));
+    };
+
+    let result = db::run(&pool, move |conn| {
+        let client = authenticate_client(conn, &client_id, client_secret.as_deref())?;
+        revoke(conn, &settings.jwt_secret, &client, &form.token).map_err(OAuthError::from)
+    })
+    .await;
+
+    Ok(match result? {
+        Ok(()) => HttpResponse::Ok().finish(),
+        Err(err) => oauth_error_response(err),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::body::to_bytes;
+    use actix_web::http::{header, StatusCode};
+    use actix_web::{test, web, App, HttpResponse};
+    use serde_json::json;
+    use super::{authorize_error_response, oauth_error_response, AuthorizeError, AuthorizeParams};
+    use crate::models::user::Claims;
+    use crate::routes::oauth::config_routes;
+    use crate::services::oauth::OAuthError;
+    use crate::test_support::{cached_access, problem, test_settings, token_for, unavailable_pool};
+
+    // مدیر کلاینت‌ها با دسترسی `items.read` نمی‌تواند کلاینتی با scope `*` بسازد
+    #[actix_web::test]
+    async fn client_scopes_are_capped_at_the_callers_permissions() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(unavailable_pool()))
+                .app_data(web::Data::new(test_settings()))
+                .app_data(cached_access(1, &["manage_oauth_clients", "items.read"]))
+                .configure(config_routes),
+        )
+        .await;
+        let token = token_for(Claims { sub: 1, ..Default::default() });
+        let register = |scopes: serde_json::Value| {
+            test::TestRequest::post()
+                .uri("/oauth/clients")
+                .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+                .set_json(json!({ "name": "worker", "grant_types": ["client_credentials"], "scopes": scopes, "confidential": true }))
+                .to_request()
+        };
+
+        let (status, code) = problem(&app, register(json!(["*"]))).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(code, "forbidden");
+
+        let (status, code) = problem(&app, register(json!(["items.*"]))).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(code, "forbidden");
+
+        // scopeهای مجاز از این مرحله می‌گذرند و به پایگاه داده‌ی در دسترس‌نبودنی می‌رسند
+        let (status, _) = problem(&app, register(json!(["items.read", "openid"]))).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    async fn body_text(response: HttpResponse) -> (StatusCode, String) {
+        let status = response.status();
+        let body = to_bytes(response.into_body()).await.expect("Error reading response body");
+        (status, String::from_utf8_lossy(&body).into_owned())
+    }
+
+    // جزئیات خطای پایگاه داده (نام جدول، متن کوئری) نباید به کلاینت یا صفحه‌ی مرورگر برسد
+    #[actix_web::test]
+    async fn database_errors_are_not_exposed() {
+        let leak = "relation \"oauth_clients\" does not exist";
+
+        let err = OAuthError::Database(diesel::result::Error::QueryBuilderError(leak.into()));
+        let (status, body) = body_text(oauth_error_response(err)).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(body.contains("\"server_error\""), "{}", body);
+        assert!(!body.contains("oauth_clients"), "{}", body);
+
+        let params = AuthorizeParams {
+            response_type: "code".to_string(),
+            client_id: "client".to_string(),
+            redirect_uri: "https://app.example.com/callback".to_string(),
+            scope: None,
+            state: None,
+            code_challenge: None,
+            code_challenge_method: None,
+            nonce: None,
+        };
+        let (status, body) = body_text(authorize_error_response(&params, AuthorizeError::Server(leak.to_string()))).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(!body.contains("oauth_clients"), "{}", body);
+    }
+}
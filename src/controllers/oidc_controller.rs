@@ -0,0 +1,47 @@
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder, ResponseError};
+use diesel::prelude::*;
+use crate::config::DbPool;
+use crate::db;
+use crate::errors::ApiError;
+use crate::models::api_key::ApiKey;
+use crate::models::user::{Claims, User};
+use crate::schema::users;
+use crate::services::oidc::{OidcProvider, UserInfo};
+
+// تابع سند discovery (OpenID Connect Discovery 1.0)
+pub async fn openid_configuration(oidc: web::Data<OidcProvider>) -> impl Responder {
+    HttpResponse::Ok().json(oidc.discovery())
+}
+
+// تابع انتشار کلید عمومی امضای ID tokenها
+pub async fn jwks(oidc: web::Data<OidcProvider>) -> impl Responder {
+    HttpResponse::Ok().json(oidc.jwks())
+}
+
+// تابع userinfo؛ توکن دارای scope باید `openid` داشته باشد و claimها بر اساس scope برگردانده می‌شوند
+pub async fn userinfo(req: HttpRequest, pool: web::Data<DbPool>) -> Result<HttpResponse, ApiError> {
+    let Some(claims) = req.extensions().get::<Claims>().cloned() else {
+        return Err(ApiError::unauthorized("Missing token"));
+    };
+    let scopes = match req.extensions().get::<ApiKey>() {
+        Some(api_key) => Some(api_key.scopes.clone()),
+        None => claims.scopes(),
+    };
+    if scopes.as_ref().is_some_and(|scopes| !scopes.iter().any(|scope| scope == "openid")) {
+        let mut response = ApiError::forbidden("Token lacks the openid scope").error_response();
+        response.headers_mut().insert(
+            header::WWW_AUTHENTICATE,
+            HeaderValue::from_static("Bearer error=\"insufficient_scope\", scope=\"openid\""),
+        );
+        return Ok(response);
+    }
+
+    let user = db::run(&pool, move |conn| {
+        users::table.find(claims.sub).first::<User>(conn).optional()
+    })
+    .await??
+    .ok_or_else(|| ApiError::unauthorized("User not found"))?;
+
+    Ok(HttpResponse::Ok().json(UserInfo::for_user(&user, scopes.as_deref())))
+}
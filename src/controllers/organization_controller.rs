@@ -0,0 +1,91 @@
+use actix_web::{web, HttpResponse};
+use diesel::prelude::*;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use serde::Deserialize;
+use crate::config::DbPool;
+use crate::db;
+use crate::errors::ApiError;
+use crate::middleware::auth_user::AuthUser;
+use crate::models::organization::{NewOrganization, Organization, OrganizationMember};
+use crate::schema::{organization_members, organizations};
+
+#[derive(Deserialize)]
+pub struct AddMemberForm {
+    pub user_id: i32,
+}
+
+// تابع ایجاد سازمان؛ سازنده به‌عنوان اولین عضو ثبت می‌شود
+pub async fn create_organization(
+    user: AuthUser,
+    form: web::Json<NewOrganization>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, ApiError> {
+    let new_organization = form.into_inner();
+    let creator = user.user_id;
+
+    let organization = db::run(&pool, move |conn| {
+        conn.transaction::<_, DieselError, _>(|conn| {
+            let organization = diesel::insert_into(organizations::table)
+                .values(&new_organization)
+                .returning(Organization::as_returning())
+                .get_result(conn)?;
+            diesel::insert_into(organization_members::table)
+                .values(&OrganizationMember {
+                    organization_id: organization.id,
+                    user_id: creator,
+                })
+                .execute(conn)?;
+            Ok(organization)
+        })
+    })
+    .await?
+    .map_err(|err| match err {
+        DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => ApiError::conflict("Organization name already exists"),
+        err => err.into(),
+    })?;
+
+    Ok(HttpResponse::Created().json(organization))
+}
+
+// تابع دریافت سازمان‌هایی که کاربر جاری عضو آن‌هاست
+pub async fn list_my_organizations(user: AuthUser, pool: web::Data<DbPool>) -> Result<HttpResponse, ApiError> {
+    let member = user.user_id;
+
+    let list = db::run(&pool, move |conn| {
+        organizations::table
+            .inner_join(organization_members::table)
+            .filter(organization_members::user_id.eq(member))
+            .order(organizations::name.asc())
+            .select(Organization::as_select())
+            .load(conn)
+    })
+    .await??;
+
+    Ok(HttpResponse::Ok().json(list))
+}
+
+// تابع افزودن کاربر به سازمان
+pub async fn add_organization_member(
+    organization_path: web::Path<i32>,
+    form: web::Json<AddMemberForm>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, ApiError> {
+    let membership = OrganizationMember {
+        organization_id: organization_path.into_inner(),
+        user_id: form.user_id,
+    };
+
+    db::run(&pool, move |conn| {
+        diesel::insert_into(organization_members::table)
+            .values(&membership)
+            .on_conflict_do_nothing()
+            .execute(conn)
+    })
+    .await?
+    .map_err(|err| match err {
+        DieselError::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _) => ApiError::not_found("Organization or user not found"),
+        err => err.into(),
+    })?;
+
+    Ok(HttpResponse::Created().body("Member added to organization"))
+}
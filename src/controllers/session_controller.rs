@@ -0,0 +1,84 @@
+use actix_web::{web, HttpResponse};
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use serde::Serialize;
+use crate::config::DbPool;
+use crate::db;
+use crate::errors::ApiError;
+use crate::middleware::auth_user::AuthUser;
+use crate::models::session::Session;
+use crate::schema::sessions::dsl::*;
+
+#[derive(Serialize)]
+struct SessionView {
+    id: i32,
+    user_agent: Option<String>,
+    ip_address: Option<String>,
+    created_at: NaiveDateTime,
+    last_seen_at: NaiveDateTime,
+    expires_at: NaiveDateTime,
+    // نشستی که همین درخواست با توکن آن ارسال شده است
+    current: bool,
+    // نشست جعل هویت توسط پشتیبانی
+    #[serde(skip_serializing_if = "Option::is_none")]
+    impersonated_by: Option<i32>,
+}
+
+// تابع فهرست نشست‌های فعال کاربر جاری
+pub async fn list_sessions(user: AuthUser, pool: web::Data<DbPool>) -> Result<HttpResponse, ApiError> {
+    let owner = user.user_id;
+
+    let active = db::run(&pool, move |conn| {
+        sessions
+            .filter(user_id.eq(owner))
+            .filter(revoked_at.is_null())
+            .filter(expires_at.gt(Utc::now().naive_utc()))
+            .order(last_seen_at.desc())
+            .select(Session::as_select())
+            .load(conn)
+    })
+    .await??;
+
+    Ok(HttpResponse::Ok().json(
+        active
+            .into_iter()
+            .map(|session| SessionView {
+                current: user.session_id == Some(session.id),
+                id: session.id,
+                user_agent: session.user_agent,
+                ip_address: session.ip_address,
+                created_at: session.created_at,
+                last_seen_at: session.last_seen_at,
+                expires_at: session.expires_at,
+                impersonated_by: session.impersonator_id,
+            })
+            .collect::<Vec<_>>(),
+    ))
+}
+
+// تابع ابطال نشست؛ توکن‌های آن نشست از همین لحظه رد می‌شوند (ابطال نشست جاری یعنی خروج)
+pub async fn revoke_session(user: AuthUser, session_path: web::Path<i32>, pool: web::Data<DbPool>) -> Result<HttpResponse, ApiError> {
+    if user.api_key_id.is_some() {
+        return Err(ApiError::forbidden("API keys cannot manage sessions"));
+    }
+    if user.scopes.is_some() {
+        return Err(ApiError::forbidden("Scoped OAuth tokens cannot manage sessions"));
+    }
+
+    let owner = user.user_id;
+    let target_id = session_path.into_inner();
+
+    let result = db::run(&pool, move |conn| {
+        diesel::update(sessions.find(target_id))
+            .filter(user_id.eq(owner))
+            .filter(revoked_at.is_null())
+            .set(revoked_at.eq(Some(Utc::now().naive_utc())))
+            .execute(conn)
+    })
+    .await;
+
+    match result?? {
+        0 => Err(ApiError::not_found("Session not found")),
+        _ => Ok(HttpResponse::NoContent().finish()),
+    }
+}
@@ -0,0 +1,43 @@
+use actix_web::web;
+use diesel::PgConnection;
+use crate::config::DbPool;
+use crate::errors::ApiError;
+
+// اجرای کار پایگاه داده روی thread pool مسدودشونده‌ی actix؛ هیچ handler یا middleware نباید
+// روی executor اتصال بگیرد یا کوئری اجرا کند (گرفتن اتصال خودش تا connection_timeout منتظر می‌ماند)
+// خطای pool به 503 و خطای web::block به 500 تبدیل می‌شود؛ نتیجه‌ی closure دست‌نخورده برمی‌گردد
+pub async fn run<F, R>(pool: &DbPool, f: F) -> Result<R, ApiError>
+where
+    F: FnOnce(&mut PgConnection) -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let pool = pool.clone();
+    web::block(move || {
+        let mut conn = pool.get()?;
+        Ok(f(&mut conn))
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::rt::time::sleep;
+    use std::time::{Duration, Instant};
+    use crate::errors::ApiError;
+    use crate::test_support::unavailable_pool;
+
+    // executor آزمون تک‌thread است؛ اگر گرفتن اتصال آن را مسدود می‌کرد، تایمر تا پایان timeout pool (200ms) عقب می‌افتاد
+    #[actix_web::test]
+    async fn waiting_for_a_connection_does_not_block_the_executor() {
+        let pool = unavailable_pool();
+        let started = Instant::now();
+
+        let (result, ticked_after) = tokio::join!(super::run(&pool, |_| ()), async {
+            sleep(Duration::from_millis(10)).await;
+            started.elapsed()
+        });
+
+        assert!(matches!(result, Err(ApiError::ServiceUnavailable(_))));
+        assert!(ticked_after < Duration::from_millis(150), "timer fired after {:?}", ticked_after);
+    }
+}
@@ -0,0 +1,211 @@
+use actix_web::error::{BlockingError, JsonPayloadError};
+use actix_web::http::{header, StatusCode};
+use actix_web::{HttpResponse, ResponseError};
+use diesel::r2d2::PoolError;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use log::error;
+use serde::Serialize;
+use std::fmt;
+use crate::services::federation::FederationError;
+use crate::validation::ValidationErrors;
+
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+// خطای پاسخ API؛ به‌صورت problem details (RFC 7807) با کد ثابت `code` برگردانده می‌شود
+// جزئیات خطاهای داخلی فقط در log ثبت می‌شوند و به کلاینت نمی‌رسند
+#[derive(Debug)]
+pub enum ApiError {
+    BadRequest(String),
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    Conflict(String),
+    PayloadTooLarge(String),
+    UnsupportedMediaType(String),
+    Unprocessable(String),
+    // خطاهای اعتبارسنجی بدنه‌ی درخواست به تفکیک فیلد
+    Validation(ValidationErrors),
+    BadGateway(String),
+    ServiceUnavailable(String),
+    Internal(String),
+}
+
+#[derive(Serialize)]
+struct ProblemDetails<'a> {
+    #[serde(rename = "type")]
+    problem_type: &'a str,
+    title: &'a str,
+    status: u16,
+    detail: &'a str,
+    code: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    errors: Option<&'a ValidationErrors>,
+}
+
+impl ApiError {
+    pub fn bad_request(detail: impl Into<String>) -> Self {
+        ApiError::BadRequest(detail.into())
+    }
+
+    pub fn unauthorized(detail: impl Into<String>) -> Self {
+        ApiError::Unauthorized(detail.into())
+    }
+
+    pub fn forbidden(detail: impl Into<String>) -> Self {
+        ApiError::Forbidden(detail.into())
+    }
+
+    pub fn not_found(detail: impl Into<String>) -> Self {
+        ApiError::NotFound(detail.into())
+    }
+
+    pub fn conflict(detail: impl Into<String>) -> Self {
+        ApiError::Conflict(detail.into())
+    }
+
+    pub fn internal(reason: impl Into<String>) -> Self {
+        ApiError::Internal(reason.into())
+    }
+
+    // کد ثابت خطا برای کلاینت‌ها؛ متن detail ممکن است تغییر کند
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::PayloadTooLarge(_) => "payload_too_large",
+            ApiError::UnsupportedMediaType(_) => "unsupported_media_type",
+            ApiError::Unprocessable(_) => "unprocessable_entity",
+            ApiError::Validation(_) => "validation_failed",
+            ApiError::BadGateway(_) => "upstream_error",
+            ApiError::ServiceUnavailable(_) => "service_unavailable",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+
+    fn detail(&self) -> &str {
+        match self {
+            ApiError::BadRequest(detail)
+            | ApiError::Unauthorized(detail)
+            | ApiError::Forbidden(detail)
+            | ApiError::NotFound(detail)
+            | ApiError::Conflict(detail)
+            | ApiError::PayloadTooLarge(detail)
+            | ApiError::UnsupportedMediaType(detail)
+            | ApiError::Unprocessable(detail)
+            | ApiError::BadGateway(detail)
+            | ApiError::ServiceUnavailable(detail) => detail,
+            ApiError::Validation(_) => "Request body failed validation",
+            ApiError::Internal(_) => "Internal server error",
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Internal(reason) => write!(f, "{}", reason),
+            _ => write!(f, "{}", self.detail()),
+        }
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ApiError::Unprocessable(_) | ApiError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::BadGateway(_) => StatusCode::BAD_GATEWAY,
+            ApiError::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+        match self {
+            ApiError::Internal(reason) => error!("Internal error: {}", reason),
+            ApiError::BadGateway(reason) => error!("{}", reason),
+            _ => {}
+        }
+
+        HttpResponse::build(status)
+            .insert_header((header::CONTENT_TYPE, PROBLEM_JSON))
+            .json(ProblemDetails {
+                problem_type: "about:blank",
+                title: status.canonical_reason().unwrap_or_default(),
+                status: status.as_u16(),
+                detail: self.detail(),
+                code: self.code(),
+                errors: match self {
+                    ApiError::Validation(errors) => Some(errors),
+                    _ => None,
+                },
+            })
+    }
+}
+
+// بدنه‌ی JSON نامعتبر؛ خطای نوع یا فیلد ناموجود 422 و JSON خراب 400 است
+impl From<JsonPayloadError> for ApiError {
+    fn from(err: JsonPayloadError) -> Self {
+        match err {
+            JsonPayloadError::OverflowKnownLength { limit, .. } | JsonPayloadError::Overflow { limit } => {
+                ApiError::PayloadTooLarge(format!("JSON body is larger than {} bytes", limit))
+            }
+            JsonPayloadError::ContentType => ApiError::UnsupportedMediaType("Content-Type must be application/json".to_string()),
+            JsonPayloadError::Deserialize(err) if err.is_data() => ApiError::Unprocessable(err.to_string()),
+            JsonPayloadError::Deserialize(err) => ApiError::bad_request(format!("Malformed JSON: {}", err)),
+            err => ApiError::bad_request(err.to_string()),
+        }
+    }
+}
+
+impl From<DieselError> for ApiError {
+    fn from(err: DieselError) -> Self {
+        match err {
+            DieselError::NotFound => ApiError::not_found("Resource not found"),
+            DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                ApiError::conflict("A record with the same unique value already exists")
+            }
+            DieselError::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _) => {
+                ApiError::Unprocessable("A referenced record does not exist".to_string())
+            }
+            err => ApiError::internal(format!("Query error: {}", err)),
+        }
+    }
+}
+
+impl From<PoolError> for ApiError {
+    fn from(err: PoolError) -> Self {
+        error!("Cannot get DB connection: {}", err);
+        ApiError::ServiceUnavailable("Database unavailable".to_string())
+    }
+}
+
+impl From<BlockingError> for ApiError {
+    fn from(err: BlockingError) -> Self {
+        ApiError::internal(format!("Blocking error: {}", err))
+    }
+}
+
+impl From<FederationError> for ApiError {
+    fn from(err: FederationError) -> Self {
+        match err {
+            FederationError::UnknownProvider => ApiError::not_found(err.to_string()),
+            FederationError::InvalidState(_) => ApiError::bad_request(err.to_string()),
+            FederationError::InvalidIdToken(_) => ApiError::unauthorized(err.to_string()),
+            FederationError::Conflict(_) => ApiError::conflict(err.to_string()),
+            FederationError::Upstream(_) => ApiError::BadGateway(err.to_string()),
+            FederationError::Database(err) => err.into(),
+            FederationError::Internal(reason) => ApiError::internal(reason),
+        }
+    }
+}
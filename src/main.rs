@@ -2,33 +2,177 @@ extern crate diesel;
 extern crate dotenv;
 
 use actix_web::{web, App, HttpServer};
+use anyhow::Context;
+use clap::Parser;
 use dotenv::dotenv;
-use std::env;
-use crate::config::establish_connection;
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::config::{establish_connection, Cli, Command, DbPool, Settings};
+use crate::middleware::validated_json::json_config;
 use crate::routes::items::config_routes;
+use crate::routes::metrics::config_routes as metrics_routes;
 use crate::routes::user::config_routes as user_routes;
+use crate::routes::admin::config_routes as admin_routes;
+use crate::routes::api_keys::config_routes as api_key_routes;
+use crate::routes::audit::config_routes as audit_routes;
+use crate::routes::elevation::config_routes as elevation_routes;
+use crate::routes::federation::config_routes as federation_routes;
+use crate::routes::oauth::config_routes as oauth_routes;
+use crate::routes::oidc::config_routes as oidc_routes;
+use crate::routes::organization::config_routes as organization_routes;
+use crate::routes::sessions::config_routes as session_routes;
+use crate::services::assignment_sweeper::spawn_assignment_sweeper;
+use crate::services::audit::verify_chain;
+use crate::services::audit_checkpoints::{spawn_checkpoint_writer, verify_checkpoints};
+use crate::services::authorization::{spawn_policy_watcher, AuthorizationService};
+use crate::services::federation::Federation;
+use crate::services::oidc::OidcProvider;
+use crate::services::permission_cache::{spawn_invalidation_listener, PermissionCache};
+use crate::services::pool_metrics::PoolMetrics;
 
 mod config;
+mod db;
+mod errors;
 mod models;
 mod controllers;
 mod routes;
 mod schema;
 mod middleware;
 mod services;
+mod validation;
+#[cfg(test)]
+mod test_support;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv().ok();
-    let host = env::var("HOST").unwrap_or("127.0.0.1:8080".to_string());
-    let pool = establish_connection();
+    env_logger::init();
 
+    // تنظیمات یک بار هنگام راه‌اندازی خوانده و بررسی می‌شوند؛ با تنظیمات نامعتبر سرور بالا نمی‌آید
+    let cli = Cli::parse();
+    let settings = or_exit(Settings::load(&cli));
+    let pool_metrics = Arc::new(PoolMetrics::default());
+    let pool = or_exit(establish_connection(&settings.database_url, &settings.pool, pool_metrics.clone()));
+
+    // `verify-audit`: بررسی زنجیره‌ی هش سابقه‌ی امنیتی و checkpointها به‌جای اجرای سرور
+    if let Some(Command::VerifyAudit) = cli.command {
+        let oidc = settings
+            .oidc
+            .signing_key_file
+            .as_deref()
+            .map(|key_file| or_exit(OidcProvider::load(settings.oidc_issuer(), Some(key_file)).context("Invalid OIDC signing key")));
+        process::exit(verify_audit(&pool, oidc.as_ref(), settings.audit.checkpoint_file.as_deref()));
+    }
+
+    // کش دسترسی‌ها بین همه‌ی workerها مشترک است و با LISTEN/NOTIFY باطل می‌شود
+    let permission_cache = web::Data::new(PermissionCache::new(Duration::from_secs(settings.permission_cache_ttl_secs)));
+    spawn_invalidation_listener(settings.database_url.clone(), permission_cache.clone());
+
+    // انتشار رویداد انقضا/فعال‌شدن اختصاص‌های زمان‌دار نقش
+    spawn_assignment_sweeper(pool.clone(), Duration::from_secs(settings.assignment_sweep_interval_secs));
+
+    // سیاست‌های ABAC از فایل خوانده شده و با تغییر فایل دوباره بارگذاری می‌شوند
+    let authorization = web::Data::new(or_exit(AuthorizationService::load(&settings.policy_file).context("Invalid policy file")));
+    spawn_policy_watcher(authorization.clone());
+
+    // کلید امضای ID tokenها و checkpointهای سابقه‌ی امنیتی
+    let oidc = web::Data::new(or_exit(
+        OidcProvider::load(settings.oidc_issuer(), settings.oidc.signing_key_file.as_deref()).context("Invalid OIDC signing key"),
+    ));
+
+    // checkpoint امضاشده‌ی زنجیره‌ی سابقه‌ی امنیتی به‌صورت دوره‌ای در فایل نوشته می‌شود
+    if let Some(checkpoint_file) = settings.audit.checkpoint_file.clone() {
+        let checkpoint_interval = Duration::from_secs(settings.audit.checkpoint_interval_secs);
+        spawn_checkpoint_writer(pool.clone(), oidc.clone(), checkpoint_file, checkpoint_interval);
+    }
+
+    let federation = web::Data::new(or_exit(
+        Federation::load(settings.federation_providers_file.as_deref()).context("Invalid federation providers file"),
+    ));
+
+    let host = settings.host.clone();
+    let settings = web::Data::new(settings);
+    let pool_metrics = web::Data::from(pool_metrics);
     HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(pool.clone()))
+            .app_data(settings.clone())
+            .app_data(pool_metrics.clone())
+            .app_data(json_config())
+            .app_data(permission_cache.clone())
+            .app_data(authorization.clone())
+            .app_data(oidc.clone())
+            .app_data(federation.clone())
             .configure(config_routes)
             .configure(user_routes)
+            .configure(elevation_routes)
+            .configure(organization_routes)
+            .configure(api_key_routes)
+            .configure(oauth_routes)
+            .configure(oidc_routes)
+            .configure(federation_routes)
+            .configure(session_routes)
+            .configure(admin_routes)
+            .configure(audit_routes)
+            .configure(metrics_routes)
     })
     .bind(host)?
     .run()
     .await
 }
+
+// خطای راه‌اندازی (تنظیمات، پایگاه داده یا فایل‌های پیکربندی): پیام کامل بدون panic و کد خروج ۲
+fn or_exit<T>(result: anyhow::Result<T>) -> T {
+    result.unwrap_or_else(|err| {
+        eprintln!("{:#}", err);
+        process::exit(2);
+    })
+}
+
+// خروجی کد خروج فرمان است: ۰ یعنی زنجیره و همه‌ی checkpointها سالم‌اند
+fn verify_audit(pool: &DbPool, oidc: Option<&OidcProvider>, checkpoint_file: Option<&Path>) -> i32 {
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            eprintln!("Cannot get DB connection: {}", err);
+            return 2;
+        }
+    };
+
+    match verify_chain(&mut conn) {
+        Ok(Ok(count)) => println!("Audit chain intact: {} events", count),
+        Ok(Err(chain_break)) => {
+            println!("Audit chain broken at event {}: {}", chain_break.event_id, chain_break.reason);
+            return 1;
+        }
+        Err(query_err) => {
+            eprintln!("Query error: {}", query_err);
+            return 2;
+        }
+    }
+
+    let Some(checkpoint_file) = checkpoint_file else {
+        println!("audit.checkpoint_file is not set; checkpoints were not verified");
+        return 0;
+    };
+    let Some(oidc) = oidc else {
+        println!("oidc.signing_key_file is not set; checkpoint signatures cannot be verified");
+        return 1;
+    };
+    match verify_checkpoints(&mut conn, oidc, checkpoint_file) {
+        Ok(Ok(count)) => {
+            println!("Audit checkpoints verified: {}", count);
+            0
+        }
+        Ok(Err(mismatch)) => {
+            println!("Audit checkpoint on line {} failed: {}", mismatch.line, mismatch.reason);
+            1
+        }
+        Err(err) => {
+            eprintln!("Cannot verify audit checkpoints: {:#}", err);
+            2
+        }
+    }
+}
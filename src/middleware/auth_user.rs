@@ -0,0 +1,157 @@
+use actix_web::{dev::Payload, web, Error, FromRequest, HttpMessage, HttpRequest};
+use futures_util::future::LocalBoxFuture;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::future::{ready, Ready};
+use crate::config::Settings;
+use crate::errors::ApiError;
+use crate::middleware::jwt::{authenticate, request_credential, resolve_credential_access, DbPool};
+use crate::middleware::permissions::{PermissionSet, UserAccess};
+use crate::models::api_key::ApiKey;
+use crate::models::user::Claims;
+use crate::services::permission_cache::PermissionCache;
+
+// کاربر احراز هویت‌شده‌ی درخواست؛ اگر RbacMiddleware اجرا نشده باشد اعتبارنامه همین‌جا با همان بررسی‌ها تأیید می‌شود
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthUser {
+    pub user_id: i32,
+    // سازمان انتخاب‌شده در توکن
+    pub tenant_id: Option<i32>,
+    // شناسه‌ی کلید API، اگر درخواست با کلید احراز هویت شده باشد
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key_id: Option<i32>,
+    // نشست ورود توکن، اگر توکن ورود کاربر باشد
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<i32>,
+    // کارمندی که هویت این کاربر را جعل کرده است (claim `act`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actor_id: Option<i32>,
+    // scopeهای توکن OAuth؛ None برای توکن ورود و کلید API (محدوده‌ی کلید در permissions اعمال شده است)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<String>>,
+    pub roles: BTreeSet<String>,
+    pub permissions: PermissionSet,
+}
+
+impl AuthUser {
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.permissions.allows(permission)
+    }
+
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.contains(role)
+    }
+}
+
+// اگر RbacMiddleware اعتبارنامه را بررسی نکرده باشد، همان بررسی‌ها (ابطال، نشست، جعل هویت و کلید API) اینجا انجام می‌شود
+fn extract_auth_user(req: &HttpRequest) -> LocalBoxFuture<'static, Result<AuthUser, ApiError>> {
+    let verified = req.extensions().get::<Claims>().cloned();
+    let access = req.extensions().get::<UserAccess>().cloned();
+    let api_key = req.extensions().get::<ApiKey>().cloned();
+    let credential = request_credential(req);
+    let pool = req.app_data::<web::Data<DbPool>>().cloned();
+    let settings = req.app_data::<web::Data<Settings>>().cloned();
+    let cache = req.app_data::<web::Data<PermissionCache>>().cloned();
+    let req = req.clone();
+
+    Box::pin(async move {
+        let pool = || pool.clone().ok_or_else(|| ApiError::internal("Database pool not found"));
+        let (claims, api_key) = match verified {
+            Some(claims) => (claims, api_key),
+            None => {
+                let credential = credential?;
+                let settings = settings.ok_or_else(|| ApiError::internal("Settings not found"))?;
+                authenticate(&pool()?, &settings.jwt_secret, credential, &req).await?
+            }
+        };
+
+        // اگر middleware دسترسی‌ها را بارگذاری نکرده باشد (مثلاً فقط Authenticated)، اینجا بارگذاری می‌شوند
+        let access = match access {
+            Some(access) => access,
+            None => resolve_credential_access(pool()?, cache, &claims, api_key.as_ref()).await?,
+        };
+
+        Ok(AuthUser {
+            user_id: claims.sub,
+            tenant_id: claims.tenant,
+            api_key_id: api_key.map(|api_key| api_key.id),
+            session_id: claims.sid,
+            actor_id: claims.act.as_ref().map(|act| act.sub),
+            scopes: claims.scopes(),
+            roles: access.roles,
+            permissions: access.permissions,
+        })
+    })
+}
+
+impl FromRequest for AuthUser {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let fut = extract_auth_user(req);
+        Box::pin(async move { fut.await.map_err(Error::from) })
+    }
+}
+
+// سازمان (tenant) جاری درخواست؛ مسیرهای دارای داده‌ی سازمانی بدون آن 403 برمی‌گردانند
+// فقط claims تأییدشده توسط RbacMiddleware خوانده می‌شود، پس مسیر باید پشت middleware باشد
+#[derive(Debug, Clone, Copy)]
+pub struct CurrentTenant(pub i32);
+
+impl FromRequest for CurrentTenant {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(match req.extensions().get::<Claims>() {
+            Some(Claims { tenant: Some(tenant), .. }) => Ok(CurrentTenant(*tenant)),
+            Some(_) => Err(ApiError::forbidden("No organization selected").into()),
+            None => Err(ApiError::unauthorized("Missing token").into()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::{header, StatusCode};
+    use actix_web::{test, web, App, HttpResponse};
+    use super::AuthUser;
+    use crate::models::user::{Actor, Claims};
+    use crate::test_support::{cached_access, test_settings, token_for, unavailable_pool};
+
+    async fn can_delete_users(user: AuthUser) -> HttpResponse {
+        HttpResponse::Ok().body(user.has_permission("users.delete").to_string())
+    }
+
+    // مسیرهای بدون RbacMiddleware همان بررسی‌های middleware را روی توکن انجام می‌دهند
+    #[actix_web::test]
+    async fn extractors_without_middleware_validate_tokens_like_the_middleware() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(unavailable_pool()))
+                .app_data(web::Data::new(test_settings()))
+                .app_data(cached_access(1, &["*"]))
+                .route("/required", web::get().to(can_delete_users)),
+        )
+        .await;
+        let call = |claims: Claims| {
+            test::TestRequest::get()
+                .uri("/required")
+                .insert_header((header::AUTHORIZATION, format!("Bearer {}", token_for(claims))))
+                .to_request()
+        };
+        // توکن جعل هویت بدون نشست فقط با دور زدن بررسی‌ها پذیرفته می‌شد
+        let impersonating = Claims { sub: 1, act: Some(Actor { sub: 2 }), ..Default::default() };
+
+        let response = test::call_service(&app, call(impersonating)).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // scopeهای توکن OAuth دسترسی کامل مالک را محدود می‌کنند
+        let body = test::call_and_read_body(&app, call(Claims { sub: 1, ..Default::default() })).await;
+        assert_eq!(body, "true");
+        let scoped = Claims { sub: 1, scope: Some("items.read".to_string()), ..Default::default() };
+        let body = test::call_and_read_body(&app, call(scoped)).await;
+        assert_eq!(body, "false");
+    }
+}
@@ -1,26 +1,43 @@
-use actix_web::{Error, HttpMessage, dev::{ServiceRequest, ServiceResponse}};
+use actix_web::{Error, HttpMessage, HttpRequest, dev::{ServiceRequest, ServiceResponse}};
 use actix_service::{Service, Transform};
 use futures_util::future::LocalBoxFuture;
-use jsonwebtoken::{decode, Validation, DecodingKey};
-use dotenv::dotenv;
-use std::env;
+use jsonwebtoken::{decode, encode, Validation, DecodingKey, EncodingKey, Header};
+use log::info;
 use std::task::{Context, Poll};
 use std::future::{ready, Ready};
-use crate::models::user::Claims;
+use std::rc::Rc;
+use crate::config::Settings;
+use crate::db;
+use crate::errors::ApiError;
+use crate::models::user::{Claims, Permission, PermissionEffect, PermissionGrant, Role, RolePermission, UserRole};
+use crate::middleware::permissions::{permission_matches, PermissionSet, UserAccess};
+use crate::middleware::requirement::{Requirement, RequirementContext};
+use crate::models::api_key::ApiKey;
+use crate::models::audit::{AuditOutcome, NewAuditEvent};
+use crate::services::api_keys::authenticate_api_key;
+use crate::services::audit::record_event_async;
+use crate::services::authorization::{AuthorizationService, RequestFacts};
+use crate::services::items::item_owner;
+use crate::services::oauth::is_access_token_revoked;
+use crate::services::permission_cache::PermissionCache;
+use crate::services::sessions::touch_session;
 use diesel::{prelude::*};
+use diesel::sql_types::{Array, Integer, Nullable, Text, Timestamp};
+use chrono::NaiveDateTime;
 use diesel::r2d2::{ConnectionManager, Pool};
 use actix_web::web;
 
 pub type DbPool = Pool<ConnectionManager<PgConnection>>;
 
 pub struct RbacMiddleware {
-    required_permission: String, // مجوز مورد نیاز
+    requirement: Requirement, // نیاز دسترسی مسیر
 }
 
 impl RbacMiddleware {
-    pub fn new(permission: &str) -> Self {
+    // رشته به‌عنوان نام یا الگوی دسترسی (`items.read`، `items.*`، `*`) تفسیر می‌شود
+    pub fn new<R: Into<Requirement>>(requirement: R) -> Self {
         RbacMiddleware {
-            required_permission: permission.to_string(),
+            requirement: requirement.into(),
         }
     }
 }
@@ -38,15 +55,15 @@ where
 
     fn new_transform(&self, service: S) -> Self::Future {
         ready(Ok(RbacMiddlewareService {
-            service,
-            required_permission: self.required_permission.clone(),
+            service: Rc::new(service),
+            requirement: self.requirement.clone(),
         }))
     }
 }
 
 pub struct RbacMiddlewareService<S> {
-    service: S,
-    required_permission: String,
+    service: Rc<S>,
+    requirement: Requirement,
 }
 
 impl<S, B> Service<ServiceRequest> for RbacMiddlewareService<S>
@@ -63,95 +80,504 @@ where
     }
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
-        let headers = req.headers();
-        let authorization = headers.get("Authorization");
-    
-        if authorization.is_none() {
-            return Box::pin(async move {
-                Err(actix_web::error::ErrorUnauthorized("Missing token"))
+        let credential = match request_credential(req.request()) {
+            Ok(credential) => credential,
+            Err(err) => return Box::pin(async move { Err(err.into()) }),
+        };
+
+        // ✅ دریافت `pool` از `app_data`
+        let pool = match req.app_data::<web::Data<DbPool>>() {
+            Some(pool) => pool.clone(),
+            None => {
+                return Box::pin(async move {
+                    Err(ApiError::internal("Database pool not found").into())
+                });
+            }
+        };
+        let Some(settings) = req.app_data::<web::Data<Settings>>().cloned() else {
+            return Box::pin(async move { Err(ApiError::internal("Settings not found").into()) });
+        };
+
+        // نقش‌ها و دسترسی‌ها فقط وقتی بارگذاری می‌شوند که نیاز مسیر به آن‌ها وابسته باشد
+        let cache = req.app_data::<web::Data<PermissionCache>>().cloned();
+        let authz = req.app_data::<web::Data<AuthorizationService>>().cloned();
+        let requirement = self.requirement.clone();
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move {
+            let (claims, api_key) = authenticate(&pool, &settings.jwt_secret, credential, req.request()).await?;
+            let user_id = claims.sub; // کپی کردن `sub` به یک متغیر جداگانه
+            let tenant = claims.tenant; // نقش‌ها فقط در سازمان انتخاب‌شده (و سراسری) معتبرند
+
+            // برای اعتبارنامه‌ی دارای scope دسترسی همیشه بارگذاری و محدود می‌شود تا extractorها دسترسی کامل مالک را نبینند
+            let needs_roles = authz.as_ref().is_some_and(|authz| authz.needs_roles());
+            let access = if requirement.needs_access() || needs_roles || credential_scopes(&claims, api_key.as_ref()).is_some() {
+                Some(resolve_credential_access(pool.clone(), cache, &claims, api_key.as_ref()).await?)
+            } else {
+                None
+            };
+            let empty_access = UserAccess::default();
+
+            let satisfied = requirement.is_satisfied(&RequirementContext {
+                claims: &claims,
+                access: access.as_ref().unwrap_or(&empty_access),
+                request: &req,
             });
-        }
-    
-        if let Some(auth_value) = authorization {
-            if let Ok(auth_str) = auth_value.to_str() {
-                if auth_str.starts_with("Bearer ") {
-                    let token = &auth_str[7..];
-    
-                    dotenv().ok();
-                    let secret_key = env::var("JWT_SECRET").expect("JWT_SECRET must be set");
-                    let decoding_key = DecodingKey::from_secret(secret_key.as_ref());
-                    let validation = Validation::default();
-    
-                    match decode::<Claims>(token, &decoding_key, &validation) {
-                        Ok(token_data) => {
-                            let user_id = token_data.claims.sub; // کپی کردن `sub` به یک متغیر جداگانه
-
-                            // ✅ دریافت `pool` از `app_data`
-                            let pool = match req.app_data::<web::Data<DbPool>>() {
-                                Some(pool) => pool.clone(),
-                                None => {
-                                    return Box::pin(async move {
-                                        Err(actix_web::error::ErrorInternalServerError("Database pool not found"))
-                                    });
-                                }
-                            };
-    
-                            // اگر مقدار مجوز مورد نیاز `LOGIN` باشد، فقط لاگین بودن بررسی شود
-                            if self.required_permission == "LOGIN" {
-                                req.extensions_mut().insert(token_data.claims.clone()); // استفاده از clone برای جلوگیری از move
-                                let fut = self.service.call(req);
-                                return Box::pin(async move { fut.await });
-                            }
-    
-                            // در غیر این صورت، مجوز را در دیتابیس بررسی کنیم
-                            let permission_check = check_user_permission(&pool, user_id, &self.required_permission);
-    
-                            if permission_check {
-                                req.extensions_mut().insert(token_data.claims.clone()); // دوباره کپی `claims` را در req ذخیره می‌کنیم
-                                let fut = self.service.call(req);
-                                return Box::pin(async move { fut.await });
-                            } else {
-                                return Box::pin(async move { Err(actix_web::error::ErrorForbidden("Forbidden")) });
-                            }
-                        }
-                        Err(_) => {
-                            return Box::pin(async move { Err(actix_web::error::ErrorUnauthorized("Invalid token")) });
-                        }
+
+            if satisfied {
+                // پس از بررسی نقش‌ها، سیاست‌های مبتنی بر ویژگی (ABAC) ارزیابی می‌شوند
+                if let Some(authz) = authz {
+                    let mut facts = RequestFacts::from_request(&req);
+                    if let Some((tenant, item)) = tenant.zip(facts.item_id()).filter(|_| authz.needs_item_owner()) {
+                        facts.item_owner = db::run(&pool, move |conn| item_owner(conn, tenant, item)).await?.map_err(ApiError::from)?;
+                    }
+                    let attributes = facts.attributes(user_id, tenant, access.as_ref());
+                    let decision = authz.evaluate(&attributes);
+                    if !decision.allowed {
+                        return Err(deny(pool, &req, &claims, format!("Forbidden: {}", decision.reason)).await);
                     }
                 }
+
+                req.extensions_mut().insert(claims); // `claims` را در req ذخیره می‌کنیم
+                if let Some(access) = access {
+                    req.extensions_mut().insert(access); // نقش‌ها و دسترسی‌ها برای استفاده‌ی دوباره در همین درخواست
+                }
+                if let Some(api_key) = api_key {
+                    req.extensions_mut().insert(api_key);
+                }
+                service.call(req).await
+            } else if requirement.is_denied(access.as_ref().unwrap_or(&empty_access)) {
+                // رد صریح با قانون deny
+                Err(deny(pool, &req, &claims, "Forbidden: explicitly denied".to_string()).await)
+            } else {
+                Err(deny(pool, &req, &claims, "Forbidden".to_string()).await)
             }
-        }
-    
-        Box::pin(async move {
-            Err(actix_web::error::ErrorUnauthorized("Invalid token format"))
         })
     }
     
     
 }
 
-// تابع بررسی مجوز کاربر
-fn check_user_permission(pool: &DbPool, user_id: i32, required_permission: &str) -> bool {
-    use crate::schema::{users_roles, role_permissions, permissions};
+// ثبت رد دسترسی در سابقه‌ی امنیتی؛ خروجی همان خطای 403 پاسخ است
+async fn deny(pool: web::Data<DbPool>, req: &ServiceRequest, claims: &Claims, reason: String) -> Error {
+    let event = NewAuditEvent::new("access.denied", AuditOutcome::Denied)
+        .actor(claims.sub, claims.act.as_ref().map(|act| act.sub))
+        .peer(req.peer_addr())
+        .target(format!("{} {}", req.method(), req.path()))
+        .detail(reason.clone());
+    record_event_async(pool, event).await;
+    ApiError::forbidden(reason).into()
+}
 
-    let mut conn = pool.get().expect("Cannot get DB connection");
+// اعتبارنامه‌ی درخواست: توکن JWT کاربر یا کلید API کلاینت ماشینی
+pub(crate) enum Credential {
+    Bearer(String),
+    ApiKey(String),
+}
 
+// `Authorization: Bearer ...`، `Authorization: ApiKey ...` یا هدر `X-API-Key`
+pub(crate) fn request_credential(req: &HttpRequest) -> Result<Credential, ApiError> {
+    if let Some(auth_value) = req.headers().get("Authorization") {
+        let auth_str = auth_value
+            .to_str()
+            .map_err(|_| ApiError::unauthorized("Invalid token format"))?;
+        if let Some(token) = auth_str.strip_prefix("Bearer ") {
+            return Ok(Credential::Bearer(token.to_string()));
+        }
+        if let Some(key) = auth_str.strip_prefix("ApiKey ") {
+            return Ok(Credential::ApiKey(key.to_string()));
+        }
+        return Err(ApiError::unauthorized("Invalid token format"));
+    }
+
+    match req.headers().get("X-API-Key").map(|value| value.to_str()) {
+        Some(Ok(key)) => Ok(Credential::ApiKey(key.to_string())),
+        Some(Err(_)) => Err(ApiError::unauthorized("Invalid API key")),
+        None => Err(ApiError::unauthorized("Missing token")),
+    }
+}
+
+// بررسی کامل اعتبارنامه: امضا و انقضای توکن، ابطال jti، نشست ورود و جعل هویت، یا کلید API
+// RbacMiddleware و extractor کاربر در مسیرهای بدون middleware هر دو از همین تابع استفاده می‌کنند
+pub(crate) async fn authenticate(
+    pool: &web::Data<DbPool>,
+    jwt_secret: &str,
+    credential: Credential,
+    req: &HttpRequest,
+) -> Result<(Claims, Option<ApiKey>), ApiError> {
+    match credential {
+        Credential::Bearer(token) => {
+            let claims = decode_token(jwt_secret, &token).map_err(|_| ApiError::unauthorized("Invalid token"))?;
+            // توکن‌های OAuth شناسه (jti) دارند و ممکن است پیش از انقضا ابطال شده باشند
+            if let Some(jti) = claims.jti.clone() {
+                ensure_not_revoked(pool.clone(), jti).await?;
+            }
+            // توکن ورود تا وقتی معتبر است که نشستش ابطال نشده باشد
+            let actor = claims.act.as_ref().map(|act| act.sub);
+            match claims.sid {
+                Some(sid) => ensure_session_active(pool.clone(), sid, claims.sub, actor).await?,
+                None if actor.is_some() => return Err(ApiError::unauthorized("Invalid token")),
+                None => {}
+            }
+            // درخواست با هویت کاربر هدف بررسی می‌شود ولی کارمند واقعی ثبت می‌شود
+            if let Some(actor) = actor {
+                info!("User {} acting as user {}: {} {}", actor, claims.sub, req.method(), req.path());
+            }
+            Ok((claims, None))
+        }
+        Credential::ApiKey(key) => {
+            let api_key = resolve_api_key(pool.clone(), key).await?;
+            Ok((api_key.claims(), Some(api_key)))
+        }
+    }
+}
+
+// scopeهای کلید API یا توکن OAuth؛ None برای توکن ورود مستقیم
+fn credential_scopes(claims: &Claims, api_key: Option<&ApiKey>) -> Option<Vec<String>> {
+    match api_key {
+        Some(api_key) => Some(api_key.scopes.clone()),
+        None => claims.scopes(),
+    }
+}
+
+// دسترسی اعتبارنامه: دسترسی کاربر در سازمان توکن، محدود به scopeهای کلید یا توکن OAuth
+pub(crate) async fn resolve_credential_access(
+    pool: web::Data<DbPool>,
+    cache: Option<web::Data<PermissionCache>>,
+    claims: &Claims,
+    api_key: Option<&ApiKey>,
+) -> Result<UserAccess, ApiError> {
+    let access = resolve_user_access(pool, cache, claims.sub, claims.tenant).await?;
+    Ok(match credential_scopes(claims, api_key) {
+        Some(scopes) => access.restrict_to_scopes(scopes),
+        None => access,
+    })
+}
+
+// بررسی کلید API خارج از executor
+async fn resolve_api_key(pool: web::Data<DbPool>, key: String) -> Result<ApiKey, ApiError> {
+    db::run(&pool, move |conn| authenticate_api_key(conn, &key))
+        .await??
+        .ok_or_else(|| ApiError::unauthorized("Invalid API key"))
+}
+
+async fn ensure_not_revoked(pool: web::Data<DbPool>, jti: String) -> Result<(), ApiError> {
+    match db::run(&pool, move |conn| is_access_token_revoked(conn, &jti)).await?? {
+        false => Ok(()),
+        true => Err(ApiError::unauthorized("Token revoked")),
+    }
+}
+
+async fn ensure_session_active(pool: web::Data<DbPool>, sid: i32, user_id: i32, actor: Option<i32>) -> Result<(), ApiError> {
+    match db::run(&pool, move |conn| touch_session(conn, sid, user_id, actor)).await?? {
+        true => Ok(()),
+        false => Err(ApiError::unauthorized("Session revoked")),
+    }
+}
+
+// امضای claims با jwt_secret تنظیمات
+pub fn encode_token(secret_key: &str, claims: &Claims) -> Result<String, jsonwebtoken::errors::Error> {
+    encode(&Header::default(), claims, &EncodingKey::from_secret(secret_key.as_ref()))
+}
+
+// اعتبارسنجی توکن JWT و استخراج claims
+pub fn decode_token(secret_key: &str, token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
+    let decoding_key = DecodingKey::from_secret(secret_key.as_ref());
+    let validation = Validation::default();
+
+    decode::<Claims>(token, &decoding_key, &validation).map(|token_data| token_data.claims)
+}
+
+// دریافت نقش‌ها و دسترسی‌های کاربر در یک سازمان از کش؛ در صورت نبود، کوئری خارج از executor با db::run اجرا می‌شود
+pub async fn resolve_user_access(
+    pool: web::Data<DbPool>,
+    cache: Option<web::Data<PermissionCache>>,
+    user_id: i32,
+    tenant: Option<i32>,
+) -> Result<UserAccess, ApiError> {
+    if let Some(access) = cache.as_ref().and_then(|cache| cache.get(user_id, tenant)) {
+        return Ok(access);
+    }
+
+    let generation = cache.as_ref().map(|cache| cache.generation());
+    let access = db::run(&pool, move |conn| load_user_access(conn, user_id, tenant)).await??;
+    if let (Some(cache), Some(generation)) = (cache, generation) {
+        cache.insert(user_id, tenant, generation, access.clone());
+    }
+    Ok(access)
+}
+
+// نقش‌های فعال یک کاربر (در بازه‌ی اعتبار) به همراه نقش‌های به‌ارث‌رسیده از طریق role_parents
+// فقط اختصاص‌های سراسری و اختصاص‌های سازمان $2 در نظر گرفته می‌شوند؛ بدون سازمان ($2 = NULL) فقط سراسری‌ها
+// path برای جلوگیری از حلقه‌ی بی‌پایان در صورت وجود چرخه نگه داشته می‌شود
+const USER_ROLE_TREE: &str = "
+    WITH RECURSIVE user_role_tree (user_id, assigned_role_id, valid_from, valid_until, tenant_id, role_id, path) AS (
+        SELECT ur.user_id, ur.role_id, ur.valid_from, ur.valid_until, ur.tenant_id, ur.role_id, ARRAY[ur.role_id]
+        FROM users_roles ur
+        WHERE ur.user_id = $1
+          AND (ur.tenant_id IS NULL OR ur.tenant_id = $2)
+          AND (ur.valid_from IS NULL OR ur.valid_from <= NOW())
+          AND (ur.valid_until IS NULL OR ur.valid_until > NOW())
+      UNION ALL
+        SELECT t.user_id, t.assigned_role_id, t.valid_from, t.valid_until, t.tenant_id, rp.parent_role_id, t.path || rp.parent_role_id
+        FROM user_role_tree t
+        INNER JOIN role_parents rp ON rp.role_id = t.role_id
+        WHERE NOT rp.parent_role_id = ANY(t.path)
+    )";
+
+#[derive(QueryableByName)]
+struct RoleRow {
+    #[diesel(sql_type = Integer)]
+    id: i32,
+    #[diesel(sql_type = Text)]
+    name: String,
+}
+
+#[derive(QueryableByName)]
+struct PermissionName {
+    #[diesel(sql_type = Text)]
+    name: String,
+    #[diesel(sql_type = Text)]
+    permission_type: String,
+}
+
+#[derive(QueryableByName)]
+struct GrantRow {
+    #[diesel(sql_type = Integer)]
+    user_id: i32,
+    #[diesel(sql_type = Integer)]
+    assigned_role_id: i32,
+    #[diesel(sql_type = Nullable<Timestamp>)]
+    valid_from: Option<NaiveDateTime>,
+    #[diesel(sql_type = Nullable<Timestamp>)]
+    valid_until: Option<NaiveDateTime>,
+    #[diesel(sql_type = Nullable<Integer>)]
+    tenant_id: Option<i32>,
+    #[diesel(sql_type = Array<Integer>)]
+    path: Vec<i32>,
+    #[diesel(sql_type = Integer)]
+    role_id: i32,
+    #[diesel(sql_type = Text)]
+    role_name: String,
+    #[diesel(sql_type = Text)]
+    role_type: String,
+    #[diesel(sql_type = Integer)]
+    permission_id: i32,
+    #[diesel(sql_type = Text)]
+    permission_name: String,
+    #[diesel(sql_type = Text)]
+    permission_type: String,
+}
+
+// تابع بررسی مجوز کاربر در یک سازمان
+pub fn check_user_permission(conn: &mut PgConnection, user_id: i32, tenant: Option<i32>, required_permission: &str) -> QueryResult<bool> {
+    Ok(load_user_permission_set(conn, user_id, tenant)?.allows(required_permission))
+}
+
+// دریافت نقش‌ها و دسترسی‌های کاربر
+pub fn load_user_access(conn: &mut PgConnection, user_id: i32, tenant: Option<i32>) -> QueryResult<UserAccess> {
+    let roles = diesel::sql_query(format!(
+        "{USER_ROLE_TREE}
+        SELECT DISTINCT r.id, r.name
+        FROM user_role_tree t
+        INNER JOIN roles r ON r.id = t.role_id"
+    ))
+    .bind::<Integer, _>(user_id)
+    .bind::<Nullable<Integer>, _>(tenant)
+    .load::<RoleRow>(conn)?;
+
+    Ok(UserAccess {
+        role_ids: roles.iter().map(|row| row.id).collect(),
+        roles: roles.into_iter().map(|row| row.name).collect(),
+        permissions: load_user_permission_set(conn, user_id, tenant)?,
+    })
+}
+
+// دریافت مجموعه‌ی دسترسی‌های کاربر (مستقیم و به‌ارث‌رسیده) با اثر allow/deny؛ تطبیق الگوها در Rust انجام می‌شود
+pub fn load_user_permission_set(conn: &mut PgConnection, user_id: i32, tenant: Option<i32>) -> QueryResult<PermissionSet> {
+    let query = diesel::sql_query(format!(
+        "{USER_ROLE_TREE}
+        SELECT DISTINCT p.name, p.permission_type
+        FROM user_role_tree t
+        INNER JOIN role_permissions rp ON rp.role_id = t.role_id
+        INNER JOIN permissions p ON p.id = rp.permission_id"
+    ))
+    .bind::<Integer, _>(user_id)
+    .bind::<Nullable<Integer>, _>(tenant);
 
-    let query = diesel::dsl::select(diesel::dsl::exists(
-        users_roles::table
-            .inner_join(role_permissions::table.on(users_roles::role_id.eq(role_permissions::role_id)))
-            .inner_join(permissions::table.on(role_permissions::permission_id.eq(permissions::id)))
-            .filter(users_roles::user_id.eq(user_id))
-            .filter(permissions::name.eq(required_permission))
-    ));
-    
     // چاپ کوئری SQL
     // let sql = debug_query::<Pg, _>(&query).to_string();
     // println!("Generated SQL: {}", sql);
-    
-    let exists = query
-        .get_result::<bool>(&mut conn)
-        .expect("Error checking permission");
 
-    exists
+    let rows = query.load::<PermissionName>(conn)?;
+
+    // نوع ناشناخته را deny در نظر می‌گیریم تا داده‌ی نامعتبر دسترسی اعطا نکند
+    Ok(PermissionSet::new(rows.into_iter().map(|row| {
+        let effect = PermissionEffect::parse(&row.permission_type).unwrap_or(PermissionEffect::Deny);
+        (row.name, effect)
+    })))
+}
+
+// دسترسی‌های یک نقش به همراه دسترسی‌های نقش‌های والد آن
+pub fn load_role_permission_set(conn: &mut PgConnection, role_id: i32) -> QueryResult<PermissionSet> {
+    let rows = diesel::sql_query(
+        "WITH RECURSIVE role_tree (role_id, path) AS (
+            SELECT CAST($1 AS INTEGER), ARRAY[CAST($1 AS INTEGER)]
+          UNION ALL
+            SELECT rp.parent_role_id, t.path || rp.parent_role_id
+            FROM role_tree t
+            INNER JOIN role_parents rp ON rp.role_id = t.role_id
+            WHERE NOT rp.parent_role_id = ANY(t.path)
+        )
+        SELECT DISTINCT p.name, p.permission_type
+        FROM role_tree t
+        INNER JOIN role_permissions rp ON rp.role_id = t.role_id
+        INNER JOIN permissions p ON p.id = rp.permission_id",
+    )
+    .bind::<Integer, _>(role_id)
+    .load::<PermissionName>(conn)?;
+
+    Ok(PermissionSet::new(rows.into_iter().map(|row| {
+        let effect = PermissionEffect::parse(&row.permission_type).unwrap_or(PermissionEffect::Deny);
+        (row.name, effect)
+    })))
+}
+
+// دریافت همه‌ی مسیرهای اعطای دسترسی یک کاربر (در صورت نیاز فقط برای یک مجوز)
+pub fn load_user_grants(
+    conn: &mut PgConnection,
+    user_id: i32,
+    tenant: Option<i32>,
+    permission_name: Option<&str>,
+) -> QueryResult<Vec<PermissionGrant>> {
+    let rows = diesel::sql_query(format!(
+        "{USER_ROLE_TREE}
+        SELECT t.user_id, t.assigned_role_id, t.valid_from, t.valid_until, t.tenant_id, t.path,
+               r.id AS role_id, r.name AS role_name, r.role_type,
+               p.id AS permission_id, p.name AS permission_name, p.permission_type
+        FROM user_role_tree t
+        INNER JOIN roles r ON r.id = t.role_id
+        INNER JOIN role_permissions rp ON rp.role_id = t.role_id
+        INNER JOIN permissions p ON p.id = rp.permission_id
+        ORDER BY p.name, r.name, array_length(t.path, 1)"
+    ))
+    .bind::<Integer, _>(user_id)
+    .bind::<Nullable<Integer>, _>(tenant)
+    .load::<GrantRow>(conn)?;
+
+    Ok(rows
+        .into_iter()
+        .filter(|row| permission_name.is_none_or(|name| permission_matches(&row.permission_name, name)))
+        .map(|row| PermissionGrant {
+            user_role: UserRole {
+                user_id: row.user_id,
+                role_id: row.assigned_role_id,
+                valid_from: row.valid_from,
+                valid_until: row.valid_until,
+                tenant_id: row.tenant_id,
+            },
+            role_path: row.path,
+            role: Role {
+                id: row.role_id,
+                name: row.role_name,
+                role_type: row.role_type,
+            },
+            role_permission: RolePermission {
+                role_id: row.role_id,
+                permission_id: row.permission_id,
+            },
+            permission: Permission {
+                id: row.permission_id,
+                name: row.permission_name,
+                permission_type: row.permission_type,
+            },
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::{header, StatusCode};
+    use actix_web::{test, web, App, HttpResponse};
+    use super::RbacMiddleware;
+    use crate::middleware::requirement::Requirement;
+    use crate::models::user::Claims;
+    use crate::test_support::{problem, test_settings, token_for, unavailable_pool};
+
+    fn bearer(claims: Claims) -> (header::HeaderName, String) {
+        (header::AUTHORIZATION, format!("Bearer {}", token_for(claims)))
+    }
+
+    async fn ok() -> HttpResponse {
+        HttpResponse::Ok().finish()
+    }
+
+    macro_rules! protected_app {
+        ($pool:expr) => {
+            test::init_service(
+                App::new()
+                    .app_data(web::Data::new($pool))
+                    .app_data(web::Data::new(test_settings()))
+                    .route("/authenticated", web::get().to(ok).wrap(RbacMiddleware::new(Requirement::Authenticated)))
+                    .route("/protected", web::get().to(ok).wrap(RbacMiddleware::new("view_role"))),
+            )
+        };
+    }
+
+    #[actix_web::test]
+    async fn loading_permissions_returns_503_when_pool_is_unavailable() {
+        let app = protected_app!(unavailable_pool()).await;
+        let req = test::TestRequest::get()
+            .uri("/protected")
+            .insert_header(bearer(Claims { sub: 1, ..Default::default() }))
+            .to_request();
+
+        let (status, code) = problem(&app, req).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(code, "service_unavailable");
+    }
+
+    #[actix_web::test]
+    async fn session_and_revocation_checks_return_503_when_pool_is_unavailable() {
+        let app = protected_app!(unavailable_pool()).await;
+        let claims = [
+            Claims { sub: 1, sid: Some(1), ..Default::default() },
+            Claims { sub: 1, jti: Some("token".to_string()), ..Default::default() },
+        ];
+
+        for claims in claims {
+            let req = test::TestRequest::get().uri("/authenticated").insert_header(bearer(claims)).to_request();
+            let (status, code) = problem(&app, req).await;
+            assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+            assert_eq!(code, "service_unavailable");
+        }
+    }
+
+    #[actix_web::test]
+    async fn api_key_lookup_returns_503_when_pool_is_unavailable() {
+        let app = protected_app!(unavailable_pool()).await;
+        let req = test::TestRequest::get().uri("/authenticated").insert_header(("X-API-Key", "key")).to_request();
+
+        let (status, code) = problem(&app, req).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(code, "service_unavailable");
+    }
+
+    #[actix_web::test]
+    async fn missing_pool_returns_500() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_settings()))
+                .route("/authenticated", web::get().to(ok).wrap(RbacMiddleware::new(Requirement::Authenticated))),
+        )
+        .await;
+        let req = test::TestRequest::get()
+            .uri("/authenticated")
+            .insert_header(bearer(Claims { sub: 1, ..Default::default() }))
+            .to_request();
+
+        let (status, code) = problem(&app, req).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(code, "internal_error");
+    }
 }
@@ -1 +1,5 @@
-pub mod jwt;
\ No newline at end of file
+pub mod auth_user;
+pub mod jwt;
+pub mod permissions;
+pub mod requirement;
+pub mod validated_json;
@@ -0,0 +1,174 @@
+use crate::models::user::PermissionEffect;
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+// مجموعه‌ی دسترسی‌های یک کاربر؛ ممکن است شامل الگوهایی مثل `items.*` یا `*` باشد
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PermissionSet {
+    allowed: BTreeSet<String>,
+    denied: BTreeSet<String>,
+    // محدوده‌ی کلید API؛ None یعنی بدون محدودیت (توکن کاربر)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scopes: Option<BTreeSet<String>>,
+}
+
+impl PermissionSet {
+    pub fn new<I: IntoIterator<Item = (String, PermissionEffect)>>(grants: I) -> Self {
+        let mut set = PermissionSet::default();
+        for (name, effect) in grants {
+            match effect {
+                PermissionEffect::Allow => set.allowed.insert(name),
+                PermissionEffect::Deny => set.denied.insert(name),
+            };
+        }
+        set
+    }
+
+    // deny-overrides: هر دسترسی deny که نیاز را پوشش دهد، بر همه‌ی allowها غلبه می‌کند
+    pub fn allows(&self, required: &str) -> bool {
+        !self.denies(required)
+            && self.allowed.iter().any(|granted| permission_matches(granted, required))
+            && self.within_scopes(required)
+    }
+
+    pub fn denies(&self, required: &str) -> bool {
+        self.denied.iter().any(|denied| permission_matches(denied, required))
+    }
+
+    // آیا همه‌ی دسترسی‌های allow مجموعه‌ی دیگر (مثلاً دسترسی‌های یک نقش) در این مجموعه مجاز است؟
+    // denyهای مجموعه‌ی دیگر فقط محدود می‌کنند و نیازی به پوشش ندارند
+    pub fn covers(&self, other: &PermissionSet) -> bool {
+        other.allowed.iter().all(|name| self.allows(name))
+    }
+
+    fn within_scopes(&self, required: &str) -> bool {
+        self.scopes
+            .as_ref()
+            .is_none_or(|scopes| scopes.iter().any(|scope| permission_matches(scope, required)))
+    }
+}
+
+// نقش‌ها (مستقیم و به‌ارث‌رسیده) و دسترسی‌های یک کاربر
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UserAccess {
+    pub role_ids: BTreeSet<i32>,
+    pub roles: BTreeSet<String>,
+    pub permissions: PermissionSet,
+}
+
+impl UserAccess {
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.contains(role)
+    }
+
+    // دسترسی یک کلید API: اشتراک دسترسی‌های مالک با scopeهای کلید
+    // نقش‌ها حذف می‌شوند تا نیازهای مبتنی بر نقش از محدوده‌ی کلید فراتر نروند
+    pub fn restrict_to_scopes<I: IntoIterator<Item = String>>(self, scopes: I) -> Self {
+        UserAccess {
+            role_ids: BTreeSet::new(),
+            roles: BTreeSet::new(),
+            permissions: PermissionSet {
+                scopes: Some(scopes.into_iter().collect()),
+                ..self.permissions
+            },
+        }
+    }
+}
+
+// تطبیق نام نقطه‌دار با الگو:
+// `*` در میانه دقیقاً یک بخش را می‌پذیرد و در انتها یک یا چند بخش باقی‌مانده را
+// جهت تطبیق یک‌طرفه است: الگوی اعطاشده (allow، deny یا scope) باید نیاز را پوشش دهد؛
+// نیاز `items.*` فقط با دسترسی `items.*` یا گسترده‌تر برآورده می‌شود، نه با `items.read`
+pub fn permission_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split('.').collect();
+    let name: Vec<&str> = name.split('.').collect();
+
+    for (i, segment) in pattern.iter().enumerate() {
+        if *segment == "*" && i == pattern.len() - 1 {
+            return name.len() > i;
+        }
+        match name.get(i) {
+            Some(part) if *segment == "*" || segment == part => continue,
+            _ => return false,
+        }
+    }
+
+    pattern.len() == name.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{permission_matches, PermissionSet, UserAccess};
+    use crate::models::user::PermissionEffect::{self, Allow, Deny};
+
+    fn set(grants: &[(&str, PermissionEffect)]) -> PermissionSet {
+        PermissionSet::new(grants.iter().map(|(name, effect)| (name.to_string(), *effect)))
+    }
+
+    #[test]
+    fn wildcards_match_segments() {
+        assert!(permission_matches("items.read", "items.read"));
+        assert!(permission_matches("items.*", "items.read"));
+        assert!(permission_matches("items.*", "items.archive.read"));
+        assert!(permission_matches("*.read", "items.read"));
+        assert!(!permission_matches("*.read", "items.archive.read"));
+        assert!(permission_matches("*", "items.read"));
+        assert!(!permission_matches("items.*", "items"));
+        assert!(!permission_matches("items.read", "items.read.all"));
+        assert!(!permission_matches("items.read", "items.write"));
+    }
+
+    #[test]
+    fn matching_is_one_directional() {
+        assert!(!permission_matches("items.read", "items.*"));
+        assert!(!permission_matches("items.read", "*"));
+        assert!(permission_matches("items.*", "items.*"));
+    }
+
+    #[test]
+    fn narrow_grants_do_not_satisfy_wildcard_requirements() {
+        let permissions = set(&[("items.read", Allow)]);
+        assert!(permissions.allows("items.read"));
+        assert!(!permissions.allows("items.*"));
+        assert!(!permissions.allows("*"));
+    }
+
+    #[test]
+    fn deny_overrides_allow() {
+        let permissions = set(&[("items.*", Allow), ("items.delete", Deny)]);
+        assert!(permissions.allows("items.read"));
+        assert!(!permissions.allows("items.delete"));
+        assert!(permissions.denies("items.delete"));
+        assert!(!permissions.denies("items.read"));
+
+        let denied_everywhere = set(&[("*", Allow), ("*.delete", Deny)]);
+        assert!(!denied_everywhere.allows("users.delete"));
+        assert!(denied_everywhere.allows("users.read"));
+    }
+
+    #[test]
+    fn narrow_deny_does_not_deny_wildcard_requirement() {
+        let permissions = set(&[("items.*", Allow), ("items.delete", Deny)]);
+        assert!(!permissions.denies("items.*"));
+        assert!(permissions.allows("items.*"));
+    }
+
+    #[test]
+    fn scopes_restrict_without_widening() {
+        let access = UserAccess { permissions: set(&[("items.*", Allow)]), ..Default::default() };
+        let scoped = access.restrict_to_scopes(["items.read".to_string(), "users.*".to_string()]);
+        assert!(scoped.permissions.allows("items.read"));
+        assert!(!scoped.permissions.allows("items.write"));
+        assert!(!scoped.permissions.allows("items.*"));
+        assert!(!scoped.permissions.allows("users.read"));
+    }
+
+    #[test]
+    fn covering_requires_every_allowed_permission() {
+        let approver = set(&[("items.*", Allow), ("approve_elevation", Allow)]);
+        assert!(approver.covers(&set(&[("items.read", Allow), ("items.delete", Deny)])));
+        assert!(!approver.covers(&set(&[("items.read", Allow), ("users.read", Allow)])));
+        assert!(!approver.covers(&set(&[("*", Allow)])));
+        assert!(!set(&[("items.*", Allow), ("items.delete", Deny)]).covers(&set(&[("items.delete", Allow)])));
+    }
+}
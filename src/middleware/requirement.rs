@@ -0,0 +1,222 @@
+use actix_web::dev::ServiceRequest;
+use std::rc::Rc;
+use crate::middleware::permissions::UserAccess;
+use crate::models::user::Claims;
+
+// اطلاعاتی که هنگام ارزیابی نیاز یک مسیر در دسترس است
+pub struct RequirementContext<'a> {
+    pub claims: &'a Claims,
+    pub access: &'a UserAccess,
+    pub request: &'a ServiceRequest,
+}
+
+// نیاز دسترسی یک مسیر؛ قابل ترکیب با AnyOf / AllOf
+#[derive(Clone)]
+pub enum Requirement {
+    // فقط داشتن توکن معتبر
+    Authenticated,
+    // نام دقیق یا الگو، مثل `items.read` یا `items.*`
+    Permission(String),
+    // نام نقش (مستقیم یا به‌ارث‌رسیده)، مثل `admin`
+    Role(String),
+    // پارامتر مسیر با این نام برابر شناسه‌ی کاربر جاری (claims.sub) باشد
+    PathIsSelf(String),
+    // کاربر نقشی داشته باشد که شناسه‌اش در پارامتر مسیر با این نام آمده است
+    PathRoleHeld(String),
+    // پارامتر مسیر با این نام برابر سازمان انتخاب‌شده در توکن (claims.tenant) باشد
+    PathIsTenant(String),
+    AnyOf(Vec<Requirement>),
+    AllOf(Vec<Requirement>),
+    // شرط دلخواه روی توکن، دسترسی‌ها و درخواست
+    Custom(Rc<dyn Fn(&RequirementContext) -> bool>),
+}
+
+impl Requirement {
+    pub fn permission(name: &str) -> Self {
+        Requirement::Permission(name.to_string())
+    }
+
+    pub fn role(name: &str) -> Self {
+        Requirement::Role(name.to_string())
+    }
+
+    pub fn path_is_self(param: &str) -> Self {
+        Requirement::PathIsSelf(param.to_string())
+    }
+
+    pub fn path_role_held(param: &str) -> Self {
+        Requirement::PathRoleHeld(param.to_string())
+    }
+
+    pub fn path_is_tenant(param: &str) -> Self {
+        Requirement::PathIsTenant(param.to_string())
+    }
+
+    // «کاربر خودش است یا دسترسی permission را دارد»
+    pub fn self_or(param: &str, permission: &str) -> Self {
+        Requirement::any_of([Requirement::path_is_self(param), Requirement::permission(permission)])
+    }
+
+    pub fn any_of<I: IntoIterator<Item = Requirement>>(requirements: I) -> Self {
+        Requirement::AnyOf(requirements.into_iter().collect())
+    }
+
+    pub fn all_of<I: IntoIterator<Item = Requirement>>(requirements: I) -> Self {
+        Requirement::AllOf(requirements.into_iter().collect())
+    }
+
+    pub fn custom<F: Fn(&RequirementContext) -> bool + 'static>(predicate: F) -> Self {
+        Requirement::Custom(Rc::new(predicate))
+    }
+
+    // آیا برای ارزیابی باید نقش‌ها و دسترسی‌های کاربر بارگذاری شوند؟
+    pub fn needs_access(&self) -> bool {
+        match self {
+            Requirement::Authenticated | Requirement::PathIsSelf(_) | Requirement::PathIsTenant(_) => false,
+            Requirement::Permission(_)
+            | Requirement::Role(_)
+            | Requirement::PathRoleHeld(_)
+            | Requirement::Custom(_) => true,
+            Requirement::AnyOf(requirements) | Requirement::AllOf(requirements) => {
+                requirements.iter().any(Requirement::needs_access)
+            }
+        }
+    }
+
+    pub fn is_satisfied(&self, ctx: &RequirementContext) -> bool {
+        match self {
+            Requirement::Authenticated => true,
+            Requirement::Permission(name) => ctx.access.permissions.allows(name),
+            Requirement::Role(name) => ctx.access.has_role(name),
+            Requirement::PathIsSelf(param) => path_id(ctx, param) == Some(ctx.claims.sub),
+            Requirement::PathIsTenant(param) => ctx.claims.tenant.is_some() && path_id(ctx, param) == ctx.claims.tenant,
+            Requirement::PathRoleHeld(param) => {
+                path_id(ctx, param).is_some_and(|role_id| ctx.access.role_ids.contains(&role_id))
+            }
+            Requirement::AnyOf(requirements) => requirements.iter().any(|r| r.is_satisfied(ctx)),
+            Requirement::AllOf(requirements) => requirements.iter().all(|r| r.is_satisfied(ctx)),
+            Requirement::Custom(predicate) => predicate(ctx),
+        }
+    }
+
+    // آیا یکی از دسترسی‌های این نیاز صراحتاً با قانون deny رد شده است؟
+    pub fn is_denied(&self, access: &UserAccess) -> bool {
+        match self {
+            Requirement::Permission(name) => access.permissions.denies(name),
+            Requirement::AnyOf(requirements) | Requirement::AllOf(requirements) => {
+                requirements.iter().any(|r| r.is_denied(access))
+            }
+            _ => false,
+        }
+    }
+}
+
+fn path_id(ctx: &RequirementContext, param: &str) -> Option<i32> {
+    ctx.request.match_info().get(param)?.parse().ok()
+}
+
+impl From<&str> for Requirement {
+    fn from(permission: &str) -> Self {
+        Requirement::permission(permission)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+    use super::{Requirement, RequirementContext};
+    use crate::middleware::permissions::{PermissionSet, UserAccess};
+    use crate::models::user::{Actor, Claims, PermissionEffect};
+
+    fn access(grants: &[(&str, PermissionEffect)], role_ids: &[i32]) -> UserAccess {
+        UserAccess {
+            role_ids: role_ids.iter().copied().collect(),
+            permissions: PermissionSet::new(grants.iter().map(|(name, effect)| (name.to_string(), *effect))),
+            ..Default::default()
+        }
+    }
+
+    fn satisfied(requirement: &Requirement, claims: &Claims, access: &UserAccess, params: &[(&'static str, &'static str)]) -> bool {
+        let request = params
+            .iter()
+            .fold(TestRequest::default(), |request, (name, value)| request.param(*name, *value))
+            .to_srv_request();
+        requirement.is_satisfied(&RequirementContext { claims, access, request: &request })
+    }
+
+    #[test]
+    fn combinators_follow_any_and_all_semantics() {
+        let claims = Claims { sub: 7, tenant: Some(3), ..Default::default() };
+        let member = access(&[("manage_organization_members", PermissionEffect::Allow)], &[]);
+        let tenant_admin = Requirement::any_of([
+            Requirement::permission("manage_organizations"),
+            Requirement::all_of([
+                Requirement::path_is_tenant("organization_id"),
+                Requirement::permission("manage_organization_members"),
+            ]),
+        ]);
+
+        assert!(satisfied(&tenant_admin, &claims, &member, &[("organization_id", "3")]));
+        assert!(!satisfied(&tenant_admin, &claims, &member, &[("organization_id", "4")]));
+        assert!(!satisfied(&tenant_admin, &claims, &UserAccess::default(), &[("organization_id", "3")]));
+        assert!(satisfied(&Requirement::all_of([]), &claims, &member, &[]));
+        assert!(!satisfied(&Requirement::any_of([]), &claims, &member, &[]));
+    }
+
+    #[test]
+    fn path_requirements_compare_with_the_token() {
+        let claims = Claims { sub: 7, ..Default::default() };
+        let viewer = access(&[("view_role", PermissionEffect::Allow)], &[12]);
+        let self_or = Requirement::self_or("user_id", "view_role");
+
+        assert!(satisfied(&self_or, &claims, &UserAccess::default(), &[("user_id", "7")]));
+        assert!(!satisfied(&self_or, &claims, &UserAccess::default(), &[("user_id", "8")]));
+        assert!(satisfied(&self_or, &claims, &viewer, &[("user_id", "8")]));
+        assert!(!satisfied(&self_or, &claims, &UserAccess::default(), &[("user_id", "me")]));
+
+        assert!(satisfied(&Requirement::path_role_held("role_id"), &claims, &viewer, &[("role_id", "12")]));
+        assert!(!satisfied(&Requirement::path_role_held("role_id"), &claims, &viewer, &[("role_id", "13")]));
+        // بدون سازمان در توکن هیچ پارامتر سازمانی برابر نیست
+        assert!(!satisfied(&Requirement::path_is_tenant("organization_id"), &claims, &viewer, &[("organization_id", "3")]));
+    }
+
+    #[test]
+    fn role_and_custom_requirements_see_the_request() {
+        let claims = Claims { sub: 7, ..Default::default() };
+        let impersonating = Claims { act: Some(Actor { sub: 8 }), ..claims.clone() };
+        let admin = UserAccess { roles: ["admin".to_string()].into(), ..Default::default() };
+
+        assert!(satisfied(&Requirement::role("admin"), &claims, &admin, &[]));
+        assert!(!satisfied(&Requirement::role("admin"), &claims, &UserAccess::default(), &[]));
+        assert!(!satisfied(&Requirement::role("adm"), &claims, &admin, &[]));
+
+        let not_impersonating = Requirement::custom(|ctx| ctx.claims.act.is_none());
+        assert!(satisfied(&not_impersonating, &claims, &admin, &[]));
+        assert!(!satisfied(&not_impersonating, &impersonating, &admin, &[]));
+
+        let id_ends_in_two = Requirement::custom(|ctx| ctx.request.match_info().get("id").is_some_and(|id| id.ends_with('2')));
+        let guarded = Requirement::all_of([Requirement::role("admin"), id_ends_in_two]);
+        assert!(satisfied(&guarded, &claims, &admin, &[("id", "12")]));
+        assert!(!satisfied(&guarded, &claims, &admin, &[("id", "13")]));
+        assert!(!satisfied(&guarded, &claims, &UserAccess::default(), &[("id", "12")]));
+    }
+
+    #[test]
+    fn access_is_loaded_only_when_needed() {
+        assert!(!Requirement::Authenticated.needs_access());
+        assert!(!Requirement::any_of([Requirement::path_is_self("user_id"), Requirement::path_is_tenant("id")]).needs_access());
+        assert!(Requirement::self_or("user_id", "view_role").needs_access());
+        assert!(Requirement::all_of([Requirement::Authenticated, Requirement::path_role_held("role_id")]).needs_access());
+        assert!(Requirement::role("admin").needs_access());
+        assert!(Requirement::custom(|_| true).needs_access());
+    }
+
+    #[test]
+    fn explicit_denies_are_found_inside_combinators() {
+        let denied = access(&[("*", PermissionEffect::Allow), ("view_role", PermissionEffect::Deny)], &[]);
+        assert!(Requirement::self_or("user_id", "view_role").is_denied(&denied));
+        assert!(Requirement::all_of([Requirement::permission("items.read"), Requirement::permission("view_role")]).is_denied(&denied));
+        assert!(!Requirement::permission("items.read").is_denied(&denied));
+        assert!(!Requirement::path_is_self("user_id").is_denied(&denied));
+    }
+}
@@ -0,0 +1,111 @@
+use actix_web::{dev::Payload, web, Error, FromRequest, HttpRequest};
+use futures_util::future::LocalBoxFuture;
+use serde::de::DeserializeOwned;
+use std::ops::Deref;
+use crate::errors::ApiError;
+use crate::validation::Validate;
+
+// سقف اندازه‌ی بدنه‌ی JSON؛ بدنه‌های بزرگ‌تر پیش از deserialize با 413 رد می‌شوند
+pub const JSON_BODY_LIMIT: usize = 64 * 1024;
+
+// بدنه‌ی JSON که پس از deserialize با `Validate` بررسی شده؛ خطاها 422 با فهرست فیلدها هستند
+pub struct ValidatedJson<T>(pub T);
+
+impl<T> ValidatedJson<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for ValidatedJson<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: DeserializeOwned + Validate + 'static> FromRequest for ValidatedJson<T> {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let json = web::Json::<T>::from_request(req, payload);
+        Box::pin(async move {
+            let value = json.await?.into_inner();
+            value.validate().map_err(ApiError::Validation)?;
+            Ok(ValidatedJson(value))
+        })
+    }
+}
+
+// تنظیم بدنه‌های JSON کل برنامه: سقف اندازه و پاسخ problem+json به‌جای متن پیش‌فرض actix
+pub fn json_config() -> web::JsonConfig {
+    web::JsonConfig::default()
+        .limit(JSON_BODY_LIMIT)
+        .error_handler(|err, _req| ApiError::from(err).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::{header, StatusCode};
+    use actix_web::{test, web, App, HttpResponse};
+    use serde_json::{json, Value};
+    use super::{json_config, ValidatedJson, JSON_BODY_LIMIT};
+    use crate::models::user::NewRole;
+
+    async fn create(role: ValidatedJson<NewRole>) -> HttpResponse {
+        HttpResponse::Created().body(role.into_inner().name)
+    }
+
+    async fn call(req: test::TestRequest) -> (StatusCode, Value) {
+        let app = test::init_service(App::new().app_data(json_config()).route("/roles", web::post().to(create))).await;
+        let response = test::call_service(&app, req.uri("/roles").to_request()).await;
+        let status = response.status();
+        (status, test::read_body_json(response).await)
+    }
+
+    #[actix_web::test]
+    async fn invalid_fields_are_listed() {
+        let (status, body) = call(test::TestRequest::post().set_json(json!({ "name": "", "role_type": "owner" }))).await;
+
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "validation_failed");
+        assert_eq!(body["errors"]["name"][0], "must be between 1 and 255 characters");
+        assert_eq!(body["errors"]["role_type"][0], "must be one of: system, custom");
+    }
+
+    #[actix_web::test]
+    async fn valid_body_reaches_handler() {
+        let app = test::init_service(App::new().app_data(json_config()).route("/roles", web::post().to(create))).await;
+        let req = test::TestRequest::post()
+            .uri("/roles")
+            .set_json(json!({ "name": "editor", "role_type": "custom" }))
+            .to_request();
+
+        assert_eq!(test::call_and_read_body(&app, req).await, "editor");
+    }
+
+    #[actix_web::test]
+    async fn malformed_bodies_return_problem_json() {
+        let (status, body) = call(
+            test::TestRequest::post().insert_header((header::CONTENT_TYPE, "application/json")).set_payload("{\"name\":"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "bad_request");
+
+        let (status, body) = call(test::TestRequest::post().set_json(json!({ "name": 1 }))).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "unprocessable_entity");
+
+        let (status, body) = call(test::TestRequest::post().insert_header((header::CONTENT_TYPE, "text/plain")).set_payload("{}")).await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(body["code"], "unsupported_media_type");
+
+        let name = "a".repeat(JSON_BODY_LIMIT);
+        let (status, body) = call(test::TestRequest::post().set_json(json!({ "name": name, "role_type": "custom" }))).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(body["code"], "payload_too_large");
+    }
+}
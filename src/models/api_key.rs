@@ -0,0 +1,47 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::Serialize;
+use crate::models::user::Claims;
+use crate::schema::api_keys;
+
+// کلید API یک کاربر؛ کلید کامل فقط هنگام ساخت نمایش داده می‌شود
+#[derive(Queryable, Selectable, Identifiable, Serialize, Clone, Debug)]
+#[diesel(table_name = api_keys)]
+pub struct ApiKey {
+    pub id: i32,
+    pub user_id: i32,
+    pub tenant_id: Option<i32>,
+    pub name: String,
+    pub prefix: String,
+    #[serde(skip_serializing)]
+    pub secret_hash: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl ApiKey {
+    // claims معادل برای ارزیابی نیازهای مسیر؛ exp صفر یعنی کلید بدون تاریخ انقضا
+    pub fn claims(&self) -> Claims {
+        Claims {
+            sub: self.user_id,
+            exp: self.expires_at.map_or(0, |at| at.and_utc().timestamp() as usize),
+            tenant: self.tenant_id,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = api_keys)]
+pub struct NewApiKey {
+    pub user_id: i32,
+    pub tenant_id: Option<i32>,
+    pub name: String,
+    pub prefix: String,
+    pub secret_hash: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<NaiveDateTime>,
+}
@@ -0,0 +1,98 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::Serialize;
+use std::net::SocketAddr;
+use crate::middleware::auth_user::AuthUser;
+use crate::schema::audit_events;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuditOutcome {
+    Success,
+    // درخواست نامعتبر یا اعتبارنامه‌ی نادرست
+    Failure,
+    // رد به دلیل نبود دسترسی
+    Denied,
+}
+
+impl AuditOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditOutcome::Success => "success",
+            AuditOutcome::Failure => "failure",
+            AuditOutcome::Denied => "denied",
+        }
+    }
+}
+
+#[derive(Queryable, Selectable, Identifiable, Serialize)]
+#[diesel(table_name = audit_events)]
+pub struct AuditEvent {
+    pub id: i32,
+    pub event_type: String,
+    pub outcome: String,
+    pub actor_id: Option<i32>,
+    // کارمندی که هنگام رویداد هویت actor را جعل کرده بود
+    pub impersonator_id: Option<i32>,
+    pub ip_address: Option<String>,
+    pub target: Option<String>,
+    pub detail: Option<String>,
+    pub created_at: NaiveDateTime,
+    // هش رویداد قبلی و هش همین رویداد؛ در پایگاه داده هنگام درج محاسبه می‌شوند
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = audit_events)]
+pub struct NewAuditEvent {
+    pub event_type: &'static str,
+    pub outcome: &'static str,
+    pub actor_id: Option<i32>,
+    pub impersonator_id: Option<i32>,
+    pub ip_address: Option<String>,
+    pub target: Option<String>,
+    pub detail: Option<String>,
+}
+
+impl NewAuditEvent {
+    pub fn new(event_type: &'static str, outcome: AuditOutcome) -> Self {
+        NewAuditEvent {
+            event_type,
+            outcome: outcome.as_str(),
+            actor_id: None,
+            impersonator_id: None,
+            ip_address: None,
+            target: None,
+            detail: None,
+        }
+    }
+
+    pub fn actor(mut self, actor_id: i32, impersonator_id: Option<i32>) -> Self {
+        self.actor_id = Some(actor_id);
+        self.impersonator_id = impersonator_id;
+        self
+    }
+
+    // کاربر احراز هویت‌شده‌ی درخواست، اگر توکن معتبر داشته باشد
+    pub fn by(self, user: Option<&AuthUser>) -> Self {
+        match user {
+            Some(user) => self.actor(user.user_id, user.actor_id),
+            None => self,
+        }
+    }
+
+    pub fn peer(mut self, addr: Option<SocketAddr>) -> Self {
+        self.ip_address = addr.map(|addr| addr.ip().to_string());
+        self
+    }
+
+    pub fn target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+}
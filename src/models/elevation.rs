@@ -0,0 +1,57 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::Serialize;
+use crate::schema::role_elevation_requests;
+
+// وضعیت درخواست ارتقای موقت نقش
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElevationStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+impl ElevationStatus {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "pending" => Some(ElevationStatus::Pending),
+            "approved" => Some(ElevationStatus::Approved),
+            "rejected" => Some(ElevationStatus::Rejected),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ElevationStatus::Pending => "pending",
+            ElevationStatus::Approved => "approved",
+            ElevationStatus::Rejected => "rejected",
+        }
+    }
+}
+
+#[derive(Queryable, Selectable, Identifiable, Serialize)]
+#[diesel(table_name = role_elevation_requests)]
+pub struct RoleElevationRequest {
+    pub id: i32,
+    pub user_id: i32,
+    pub role_id: i32,
+    pub reason: String,
+    pub duration_minutes: i32,
+    pub status: String,
+    pub approver_id: Option<i32>,
+    pub created_at: NaiveDateTime,
+    pub decided_at: Option<NaiveDateTime>,
+    pub tenant_id: Option<i32>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = role_elevation_requests)]
+pub struct NewRoleElevationRequest {
+    pub user_id: i32,
+    pub role_id: i32,
+    pub reason: String,
+    pub duration_minutes: i32,
+    // سازمانی که نقش موقت در آن اعطا می‌شود
+    pub tenant_id: Option<i32>,
+}
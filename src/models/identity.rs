@@ -0,0 +1,49 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::Serialize;
+use crate::schema::{federated_login_states, user_identities};
+
+#[derive(Queryable, Selectable, Identifiable, Serialize)]
+#[diesel(table_name = user_identities)]
+pub struct UserIdentity {
+    pub id: i32,
+    pub user_id: i32,
+    pub provider: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub last_login_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = user_identities)]
+pub struct NewUserIdentity {
+    pub user_id: i32,
+    pub provider: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub last_login_at: Option<NaiveDateTime>,
+}
+
+#[derive(Queryable, Selectable, Identifiable)]
+#[diesel(table_name = federated_login_states)]
+pub struct FederatedLoginState {
+    pub id: i32,
+    pub provider: String,
+    pub nonce: String,
+    pub code_verifier: String,
+    pub link_user_id: Option<i32>,
+    pub expires_at: NaiveDateTime,
+    pub consumed_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = federated_login_states)]
+pub struct NewFederatedLoginState {
+    pub state_hash: String,
+    pub provider: String,
+    pub nonce: String,
+    pub code_verifier: String,
+    pub link_user_id: Option<i32>,
+    pub expires_at: NaiveDateTime,
+}
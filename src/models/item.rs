@@ -2,16 +2,29 @@ use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use diesel::{Queryable, Insertable};
 use crate::schema::items;
+use crate::validation::{Rule, Validate, ValidationErrors, Validator};
 
 #[derive(Debug, Serialize, Deserialize, Queryable)]
 pub struct Item {
     pub id: i32,
     pub name: String,
     pub created_at: Option<NaiveDateTime>,
+    pub tenant_id: i32,
+    // کاربر سازنده؛ None برای آیتم‌های پیش از ثبت مالک
+    pub owner_id: Option<i32>,
 }
 
+// tenant_id و owner_id از توکن درخواست‌دهنده تعیین می‌شوند، نه از بدنه‌ی درخواست
 #[derive(Debug, Serialize, Deserialize, Insertable)]
 #[diesel(table_name = items)]
 pub struct NewItem {
     pub name: String,
 }
+
+impl Validate for NewItem {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        Validator::new()
+            .field("name", &self.name, &[Rule::Length { min: 1, max: 255 }])
+            .finish()
+    }
+}
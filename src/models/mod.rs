@@ -1,2 +1,9 @@
+pub mod api_key;
+pub mod audit;
+pub mod elevation;
+pub mod identity;
 pub mod item;
+pub mod oauth;
+pub mod organization;
+pub mod session;
 pub mod user;
\ No newline at end of file
@@ -0,0 +1,129 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::Serialize;
+use crate::schema::{oauth_authorization_codes, oauth_clients, oauth_refresh_tokens, oauth_revoked_access_tokens};
+
+// نوع‌های grant پشتیبانی‌شده در `/oauth/token`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrantType {
+    AuthorizationCode,
+    ClientCredentials,
+    RefreshToken,
+}
+
+impl GrantType {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "authorization_code" => Some(GrantType::AuthorizationCode),
+            "client_credentials" => Some(GrantType::ClientCredentials),
+            "refresh_token" => Some(GrantType::RefreshToken),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GrantType::AuthorizationCode => "authorization_code",
+            GrantType::ClientCredentials => "client_credentials",
+            GrantType::RefreshToken => "refresh_token",
+        }
+    }
+}
+
+#[derive(Queryable, Selectable, Identifiable, Serialize, Clone)]
+#[diesel(table_name = oauth_clients)]
+pub struct OAuthClient {
+    pub id: i32,
+    pub client_id: String,
+    #[serde(skip_serializing)]
+    pub client_secret_hash: Option<String>,
+    pub name: String,
+    pub redirect_uris: Vec<String>,
+    pub grant_types: Vec<String>,
+    pub scopes: Vec<String>,
+    pub service_user_id: Option<i32>,
+    pub created_at: NaiveDateTime,
+}
+
+impl OAuthClient {
+    // کلاینت محرمانه (دارای secret) در برابر کلاینت عمومی
+    pub fn is_confidential(&self) -> bool {
+        self.client_secret_hash.is_some()
+    }
+
+    pub fn allows_grant(&self, grant: GrantType) -> bool {
+        self.grant_types.iter().any(|allowed| allowed == grant.as_str())
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = oauth_clients)]
+pub struct NewOAuthClient {
+    pub client_id: String,
+    pub client_secret_hash: Option<String>,
+    pub name: String,
+    pub redirect_uris: Vec<String>,
+    pub grant_types: Vec<String>,
+    pub scopes: Vec<String>,
+    pub service_user_id: Option<i32>,
+}
+
+#[derive(Queryable, Selectable, Identifiable)]
+#[diesel(table_name = oauth_authorization_codes)]
+pub struct AuthorizationCode {
+    pub id: i32,
+    pub oauth_client_id: i32,
+    pub user_id: i32,
+    pub tenant_id: Option<i32>,
+    pub redirect_uri: String,
+    pub scopes: Vec<String>,
+    pub code_challenge: Option<String>,
+    pub expires_at: NaiveDateTime,
+    pub consumed_at: Option<NaiveDateTime>,
+    pub nonce: Option<String>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = oauth_authorization_codes)]
+pub struct NewAuthorizationCode {
+    pub code_hash: String,
+    pub oauth_client_id: i32,
+    pub user_id: i32,
+    pub tenant_id: Option<i32>,
+    pub redirect_uri: String,
+    pub scopes: Vec<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+    pub expires_at: NaiveDateTime,
+    pub nonce: Option<String>,
+}
+
+#[derive(Queryable, Selectable, Identifiable)]
+#[diesel(table_name = oauth_refresh_tokens)]
+pub struct RefreshToken {
+    pub id: i32,
+    pub oauth_client_id: i32,
+    pub user_id: i32,
+    pub tenant_id: Option<i32>,
+    pub scopes: Vec<String>,
+    pub expires_at: NaiveDateTime,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = oauth_refresh_tokens)]
+pub struct NewRefreshToken {
+    pub token_hash: String,
+    pub oauth_client_id: i32,
+    pub user_id: i32,
+    pub tenant_id: Option<i32>,
+    pub scopes: Vec<String>,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = oauth_revoked_access_tokens)]
+pub struct RevokedAccessToken {
+    pub jti: String,
+    pub expires_at: NaiveDateTime,
+}
@@ -0,0 +1,26 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::schema::{organization_members, organizations};
+
+// سازمان (tenant): نقش‌های اختصاص‌یافته و داده‌ها به آن محدود می‌شوند
+#[derive(Queryable, Selectable, Identifiable, Serialize, Clone)]
+#[diesel(table_name = organizations)]
+pub struct Organization {
+    pub id: i32,
+    pub name: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Deserialize)]
+#[diesel(table_name = organizations)]
+pub struct NewOrganization {
+    pub name: String,
+}
+
+#[derive(Queryable, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = organization_members)]
+pub struct OrganizationMember {
+    pub organization_id: i32,
+    pub user_id: i32,
+}
@@ -1,6 +1,8 @@
 use diesel::prelude::*;
-use crate::schema::{roles, permissions, role_permissions, users_roles, users};
+use crate::schema::{roles, permissions, role_parents, role_permissions, users_roles, users};
 use serde::{Serialize, Deserialize};
+use chrono::NaiveDateTime;
+use crate::validation::{Rule, Validate, ValidationErrors, Validator, IDENTIFIER, PERMISSION_NAME};
 
 
 #[derive(Queryable, Insertable, Identifiable, Debug)] // حذف Associations
@@ -8,13 +10,46 @@ use serde::{Serialize, Deserialize};
 pub struct User {
     pub id: i32,
     pub username: String,
-    pub password: String
+    pub password: String,
+    pub email: Option<String>,
+    // حساب سرویس کلاینت OAuth؛ با رمز عبور وارد نمی‌شود
+    pub is_service_account: bool,
 }
 
-#[derive(Serialize, Deserialize, Clone)] // اضافه کردن Clone برای امکان کپی کردن
+#[derive(Serialize, Deserialize, Clone, Default)] // اضافه کردن Clone برای امکان کپی کردن
 pub struct Claims {
     pub sub: i32,  // شناسه کاربر (id) به جای نام کاربری
     pub exp: usize,   // زمان انقضای توکن
+    // سازمان (tenant) انتخاب‌شده هنگام ورود؛ توکن‌های قدیمی این فیلد را ندارند
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<i32>,
+    // فیلدهای توکن‌های صادرشده از طریق OAuth: شناسه‌ی توکن (برای ابطال)، کلاینت و scopeها (با فاصله جدا شده)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    // نشست ورود؛ فقط توکن‌های ورود با رمز عبور یا ارائه‌دهنده‌ی بیرونی آن را دارند
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<i32>,
+    // کاربری که با جعل هویت به نام `sub` عمل می‌کند (claim `act` در RFC 8693)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Actor>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Actor {
+    pub sub: i32,
+}
+
+impl Claims {
+    // scopeهای توکن OAuth؛ None برای توکن‌های ورود مستقیم که محدودیتی ندارند
+    pub fn scopes(&self) -> Option<Vec<String>> {
+        self.scope
+            .as_ref()
+            .map(|scope| scope.split_whitespace().map(str::to_string).collect())
+    }
 }
 
 #[derive(Insertable)]
@@ -22,9 +57,10 @@ pub struct Claims {
 pub struct NewUser {
     pub username: String,
     pub password: String,
+    pub email: Option<String>,
 }
 
-#[derive(Queryable, Insertable, Identifiable)]
+#[derive(Queryable, Insertable, Identifiable, Serialize, Clone)]
 #[diesel(table_name = roles)]
 pub struct Role {
     pub id: i32,
@@ -32,6 +68,36 @@ pub struct Role {
     pub role_type: String
 }
 
+// نوع نقش: نقش‌های سیستمی توسط migration ایجاد می‌شوند و قابل حذف نیستند
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoleType {
+    System,
+    Custom,
+}
+
+impl RoleType {
+    pub const ALL: [RoleType; 2] = [RoleType::System, RoleType::Custom];
+    // مقادیر مجاز role_type برای اعتبارسنجی، به همان ترتیب ALL
+    pub const NAMES: &'static [&'static str] = &[Self::ALL[0].as_str(), Self::ALL[1].as_str()];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            RoleType::System => "system",
+            RoleType::Custom => "custom",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|role_type| role_type.as_str() == value)
+    }
+}
+
+impl Role {
+    pub fn is_system(&self) -> bool {
+        RoleType::parse(&self.role_type) == Some(RoleType::System)
+    }
+}
+
 #[derive(Insertable, Serialize, Deserialize)]
 #[diesel(table_name = roles)]
 pub struct NewRole {
@@ -39,7 +105,19 @@ pub struct NewRole {
     pub role_type: String
 }
 
-#[derive(Queryable, Insertable, Identifiable)]
+impl Validate for NewRole {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        Validator::new()
+            .field("name", &self.name, &[
+                Rule::Length { min: 1, max: 255 },
+                Rule::Pattern(&IDENTIFIER, "letters, digits, '_', '.', ':' or '-'"),
+            ])
+            .field("role_type", &self.role_type, &[Rule::OneOf(RoleType::NAMES)])
+            .finish()
+    }
+}
+
+#[derive(Queryable, Insertable, Identifiable, Serialize, Clone)]
 #[diesel(table_name = permissions)]
 pub struct Permission {
     pub id: i32,
@@ -47,6 +125,30 @@ pub struct Permission {
     pub permission_type: String
 }
 
+// اثر دسترسی (permission_type): deny بر allow غلبه می‌کند
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermissionEffect {
+    Allow,
+    Deny,
+}
+
+impl PermissionEffect {
+    pub const ALL: [PermissionEffect; 2] = [PermissionEffect::Allow, PermissionEffect::Deny];
+    // مقادیر مجاز permission_type برای اعتبارسنجی، به همان ترتیب ALL
+    pub const NAMES: &'static [&'static str] = &[Self::ALL[0].as_str(), Self::ALL[1].as_str()];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            PermissionEffect::Allow => "allow",
+            PermissionEffect::Deny => "deny",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|effect| effect.as_str() == value)
+    }
+}
+
 #[derive(Queryable, Insertable, Serialize, Deserialize)]
 #[diesel(table_name = permissions)]
 pub struct NewPermission {
@@ -54,6 +156,18 @@ pub struct NewPermission {
     pub permission_type: String
 }
 
+impl Validate for NewPermission {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        Validator::new()
+            .field("name", &self.name, &[
+                Rule::Length { min: 1, max: 255 },
+                Rule::Pattern(&PERMISSION_NAME, "letters, digits, '_', '.', ':', '-' or the '*' wildcard"),
+            ])
+            .field("permission_type", &self.permission_type, &[Rule::OneOf(PermissionEffect::NAMES)])
+            .finish()
+    }
+}
+
 #[derive(Queryable, Insertable, Associations, Serialize, Deserialize)]
 #[diesel(table_name = role_permissions)]
 #[diesel(belongs_to(Permission))]
@@ -63,11 +177,35 @@ pub struct RolePermission {
     pub permission_id: i32,
 }
 
-#[derive(Queryable, Insertable, Associations, Serialize, Deserialize)]
+#[derive(Queryable, Selectable, Insertable, Associations, Serialize, Deserialize)]
 #[diesel(table_name = users_roles)]
 #[diesel(belongs_to(User))]
 #[diesel(belongs_to(Role))]
 pub struct UserRole {
     pub user_id: i32,
-    pub role_id: i32
+    pub role_id: i32,
+    // بازه‌ی اعتبار اختصاص (UTC)؛ None یعنی بدون محدودیت
+    pub valid_from: Option<NaiveDateTime>,
+    pub valid_until: Option<NaiveDateTime>,
+    // سازمانی که اختصاص در آن معتبر است؛ None یعنی اختصاص سراسری
+    pub tenant_id: Option<i32>,
+}
+
+// نقش role_id دسترسی‌های نقش parent_role_id را به ارث می‌برد
+#[derive(Queryable, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = role_parents)]
+pub struct RoleParent {
+    pub role_id: i32,
+    pub parent_role_id: i32,
+}
+
+// یک مسیر اعطای دسترسی: users_roles → (role_parents) → role_permissions → permissions
+#[derive(Serialize)]
+pub struct PermissionGrant {
+    pub user_role: UserRole,
+    // مسیر وراثت از نقش اختصاص‌یافته تا نقش دارنده‌ی دسترسی
+    pub role_path: Vec<i32>,
+    pub role: Role,
+    pub role_permission: RolePermission,
+    pub permission: Permission,
 }
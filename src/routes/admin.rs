@@ -0,0 +1,23 @@
+use actix_web::web;
+use crate::controllers::admin_controller::*;
+use crate::middleware::jwt::RbacMiddleware;
+use crate::middleware::requirement::Requirement;
+
+pub fn config_routes(cfg: &mut web::ServiceConfig) {
+    // پایان جعل هویت با توکن جعل هویت فراخوانی می‌شود که دسترسی‌های کاربر هدف را دارد
+    // و باید پیش از `/{user_id}` ثبت شود
+    cfg.service(
+        web::resource("/admin/impersonate/end")
+            .wrap(RbacMiddleware::new(Requirement::Authenticated))
+            .route(web::post().to(end_impersonation)),
+    );
+    // توکن جعل هویت (claim `act`) نمی‌تواند جعل هویت دیگری را شروع کند
+    cfg.service(
+        web::resource("/admin/impersonate/{user_id}")
+            .wrap(RbacMiddleware::new(Requirement::all_of([
+                Requirement::permission(IMPERSONATE_PERMISSION),
+                Requirement::custom(|ctx| ctx.claims.act.is_none()),
+            ])))
+            .route(web::post().to(impersonate)),
+    );
+}
@@ -0,0 +1,15 @@
+use actix_web::web;
+use crate::controllers::api_key_controller::*;
+use crate::middleware::jwt::RbacMiddleware;
+use crate::middleware::requirement::Requirement;
+
+pub fn config_routes(cfg: &mut web::ServiceConfig) {
+    // هر کاربر فقط کلیدهای خودش را مدیریت می‌کند
+    cfg.service(
+        web::scope("/api_keys")
+            .wrap(RbacMiddleware::new(Requirement::Authenticated))
+            .route("", web::post().to(create_api_key))
+            .route("", web::get().to(list_api_keys))
+            .route("/{id}", web::delete().to(revoke_api_key)),
+    );
+}
@@ -0,0 +1,11 @@
+use actix_web::web;
+use crate::controllers::audit_controller::*;
+use crate::middleware::jwt::RbacMiddleware;
+
+pub fn config_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/audit")
+            .wrap(RbacMiddleware::new("view_audit"))
+            .route(web::get().to(list_audit_events)),
+    );
+}
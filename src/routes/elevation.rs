@@ -0,0 +1,24 @@
+use actix_web::web;
+use crate::controllers::elevation_controller::*;
+use crate::middleware::{jwt::RbacMiddleware, requirement::Requirement};
+
+pub fn config_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/elevation_requests")
+            .service(
+                web::resource("")
+                    .route(web::post().to(request_elevation).wrap(RbacMiddleware::new(Requirement::Authenticated)))
+                    .route(web::get().to(list_elevation_requests).wrap(RbacMiddleware::new("approve_elevation"))),
+            )
+            .service(
+                web::resource("/{id}/approve")
+                    .wrap(RbacMiddleware::new("approve_elevation"))
+                    .route(web::post().to(approve_elevation)),
+            )
+            .service(
+                web::resource("/{id}/reject")
+                    .wrap(RbacMiddleware::new("approve_elevation"))
+                    .route(web::post().to(reject_elevation)),
+            ),
+    );
+}
@@ -0,0 +1,22 @@
+use actix_web::web;
+use crate::controllers::federation_controller::*;
+use crate::middleware::jwt::RbacMiddleware;
+use crate::middleware::requirement::Requirement;
+
+pub fn config_routes(cfg: &mut web::ServiceConfig) {
+    // ورود با ارائه‌دهندگان بیرونی OIDC
+    cfg.service(
+        web::scope("/auth/{provider}")
+            .route("/start", web::get().to(start))
+            .route("/callback", web::get().to(callback)),
+    );
+
+    // هویت‌های بیرونی متصل به کاربر جاری
+    cfg.service(
+        web::scope("/me/identities")
+            .wrap(RbacMiddleware::new(Requirement::Authenticated))
+            .route("", web::get().to(list_identities))
+            .route("/{provider}/link", web::post().to(link_identity))
+            .route("/{identity_id}", web::delete().to(unlink_identity)),
+    );
+}
@@ -1,12 +1,24 @@
 use actix_web::web;
 use crate::controllers::items_controller::*;
+use crate::middleware::jwt::RbacMiddleware;
+use crate::middleware::requirement::Requirement;
 
 pub fn config_routes(cfg: &mut web::ServiceConfig) {
+    // آیتم‌ها داده‌ی سازمانی‌اند و بدون توکن دارای سازمان قابل دسترسی نیستند
+    // middleware روی هر resource است تا پارامتر `id` (و ویژگی `item.owner_id`) در ارزیابی سیاست‌ها در دسترس باشد
     cfg.service(
         web::scope("/items")
-            .route("", web::get().to(get_items))
-            .route("", web::post().to(create_item))
-            .route("/{id}", web::put().to(update_item))
-            .route("/{id}", web::delete().to(delete_item)),
+            .service(
+                web::resource("")
+                    .wrap(RbacMiddleware::new(Requirement::Authenticated))
+                    .route(web::get().to(get_items))
+                    .route(web::post().to(create_item)),
+            )
+            .service(
+                web::resource("/{id}")
+                    .wrap(RbacMiddleware::new(Requirement::Authenticated))
+                    .route(web::put().to(update_item))
+                    .route(web::delete().to(delete_item)),
+            ),
     );
 }
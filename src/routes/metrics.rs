@@ -0,0 +1,7 @@
+use actix_web::web;
+use crate::controllers::metrics_controller::*;
+
+pub fn config_routes(cfg: &mut web::ServiceConfig) {
+    // بدون RbacMiddleware تا به pool وابسته نباشد؛ دسترسی با metrics_token در خود handler بررسی می‌شود
+    cfg.route("/metrics", web::get().to(metrics));
+}
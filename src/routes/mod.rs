@@ -1,2 +1,12 @@
+pub mod admin;
+pub mod api_keys;
+pub mod audit;
+pub mod elevation;
+pub mod federation;
 pub mod items;
+pub mod metrics;
+pub mod oauth;
+pub mod oidc;
+pub mod organization;
+pub mod sessions;
 pub mod user;
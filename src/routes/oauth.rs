@@ -0,0 +1,25 @@
+use actix_web::web;
+use crate::controllers::oauth_controller::*;
+use crate::middleware::jwt::RbacMiddleware;
+
+pub fn config_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/oauth")
+            // مدیریت کلاینت‌ها
+            .service(
+                web::resource("/clients")
+                    .wrap(RbacMiddleware::new("manage_oauth_clients"))
+                    .route(web::post().to(register_client))
+                    .route(web::get().to(list_clients)),
+            )
+            // endpointهای استاندارد؛ احراز هویت کاربر یا کلاینت داخل خود handlerها انجام می‌شود
+            .service(
+                web::resource("/authorize")
+                    .route(web::get().to(authorize_page))
+                    .route(web::post().to(authorize_submit)),
+            )
+            .route("/token", web::post().to(token))
+            .route("/introspect", web::post().to(introspect_token))
+            .route("/revoke", web::post().to(revoke_token)),
+    );
+}
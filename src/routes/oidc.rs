@@ -0,0 +1,15 @@
+use actix_web::web;
+use crate::controllers::oidc_controller::*;
+use crate::middleware::jwt::RbacMiddleware;
+use crate::middleware::requirement::Requirement;
+
+pub fn config_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/.well-known/openid-configuration", web::get().to(openid_configuration))
+        .route("/.well-known/jwks.json", web::get().to(jwks))
+        .service(
+            web::resource("/userinfo")
+                .wrap(RbacMiddleware::new(Requirement::Authenticated))
+                .route(web::get().to(userinfo))
+                .route(web::post().to(userinfo)),
+        );
+}
@@ -0,0 +1,27 @@
+use actix_web::web;
+use crate::controllers::organization_controller::*;
+use crate::middleware::jwt::RbacMiddleware;
+use crate::middleware::requirement::Requirement;
+
+pub fn config_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/organizations")
+            .service(
+                web::resource("")
+                    .route(web::post().to(create_organization).wrap(RbacMiddleware::new("manage_organizations")))
+                    .route(web::get().to(list_my_organizations).wrap(RbacMiddleware::new(Requirement::Authenticated))),
+            )
+            // مدیر سراسری سازمان‌ها، یا مدیر اعضا در سازمانی که با آن وارد شده است
+            .service(
+                web::resource("/{organization_id}/members")
+                    .wrap(RbacMiddleware::new(Requirement::any_of([
+                        Requirement::permission("manage_organizations"),
+                        Requirement::all_of([
+                            Requirement::path_is_tenant("organization_id"),
+                            Requirement::permission("manage_organization_members"),
+                        ]),
+                    ])))
+                    .route(web::post().to(add_organization_member)),
+            ),
+    );
+}
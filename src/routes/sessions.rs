@@ -0,0 +1,14 @@
+use actix_web::web;
+use crate::controllers::session_controller::*;
+use crate::middleware::jwt::RbacMiddleware;
+use crate::middleware::requirement::Requirement;
+
+pub fn config_routes(cfg: &mut web::ServiceConfig) {
+    // نشست‌ها و دستگاه‌های ورود کاربر جاری
+    cfg.service(
+        web::scope("/me/sessions")
+            .wrap(RbacMiddleware::new(Requirement::Authenticated))
+            .route("", web::get().to(list_sessions))
+            .route("/{session_id}", web::delete().to(revoke_session)),
+    );
+}
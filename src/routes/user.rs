@@ -1,23 +1,49 @@
 use actix_web::{web};
-use crate::{controllers::user_controller::*, middleware::jwt::RbacMiddleware};
+use crate::{controllers::admin_controller::ADMIN_ROLE, controllers::authz_controller::evaluate_policy, controllers::user_controller::*, middleware::jwt::RbacMiddleware, middleware::requirement::Requirement};
 
 pub fn config_routes(cfg: &mut web::ServiceConfig) {
     // مسیرهای ثبت‌نام و لاگین
     cfg.service(web::resource("/register").route(web::post().to(register)));
     cfg.service(web::resource("/login").route(web::post().to(login)));
+    cfg.service(web::resource("/me").wrap(RbacMiddleware::new(Requirement::Authenticated)).route(web::get().to(me)));
 
     // مسیرهای مدیریت نقش‌ها و دسترسی‌ها
-    cfg.service(web::resource("/roles").route(web::post().to(add_role)));
-    cfg.service(web::resource("/permissions").route(web::post().to(add_permission)));
+    cfg.service(web::resource("/roles").wrap(RbacMiddleware::new("manage_roles")).route(web::post().to(add_role)));
+    cfg.service(web::resource("/permissions").wrap(RbacMiddleware::new("manage_roles")).route(web::post().to(add_permission)));
 
     // مسیرهای مدیریت روابط دسترسی‌ها به نقش‌ها
-    cfg.service(web::resource("/role_permissions").route(web::post().to(add_role_permission)));
+    cfg.service(web::resource("/role_permissions").wrap(RbacMiddleware::new("manage_roles")).route(web::post().to(add_role_permission)));
+
+    // مسیر مدیریت سلسله‌مراتب نقش‌ها
+    cfg.service(web::resource("/role_parents").wrap(RbacMiddleware::new("manage_roles")).route(web::post().to(add_role_parent)));
 
     // مسیرهای مدیریت روابط نقش‌ها به کاربران
-    cfg.service(web::resource("/assign_role_to_user").route(web::post().to(assign_role_to_user)));
+    cfg.service(web::resource("/assign_role_to_user").wrap(RbacMiddleware::new("manage_roles")).route(web::post().to(assign_role_to_user)));
+
+    // حذف نقش با شناسه‌ی نقش
+    cfg.service(web::resource("/roles/by-id/{role_id}").wrap(RbacMiddleware::new("delete_role")).route(web::delete().to(delete_role)));
 
     // مسیرهای دریافت اطلاعات نقش‌ها و دسترسی‌ها
-    cfg.service(web::resource("/roles/{user_id}").wrap(RbacMiddleware::new("view_role")).route(web::get().to(get_roles_for_user)));
-    cfg.service(web::resource("/permissions/{role_id}").route(web::get().to(get_permissions_for_role)));
-    cfg.service(web::resource("/test").route(web::get().to(test)));
+    // کاربر همیشه می‌تواند نقش‌های خودش را ببیند
+    cfg.service(
+        web::resource("/roles/{user_id}")
+            .wrap(RbacMiddleware::new(Requirement::self_or("user_id", "view_role")))
+            .route(web::get().to(get_roles_for_user)),
+    );
+    // دسترسی‌های نقشی که کاربر خودش دارد برایش قابل مشاهده است
+    cfg.service(
+        web::resource("/permissions/{role_id}")
+            .wrap(RbacMiddleware::new(Requirement::any_of([
+                Requirement::path_role_held("role_id"),
+                Requirement::permission("view_role"),
+            ])))
+            .route(web::get().to(get_permissions_for_role)),
+    );
+
+    // مسیرهای بررسی و توضیح دسترسی‌های مؤثر
+    cfg.service(web::resource("/users/{user_id}/effective-permissions").wrap(RbacMiddleware::new(Requirement::self_or("user_id", "view_role"))).route(web::get().to(get_effective_permissions)));
+    cfg.service(web::resource("/authz/explain").wrap(RbacMiddleware::new("view_role")).route(web::get().to(explain_permission)));
+    cfg.service(web::resource("/authz/policies/evaluate").wrap(RbacMiddleware::new("view_role")).route(web::post().to(evaluate_policy)));
+    // فراخوانی API سامفا با توکن سرور؛ فقط برای مدیران
+    cfg.service(web::resource("/test").wrap(RbacMiddleware::new(Requirement::role(ADMIN_ROLE))).route(web::get().to(test)));
 }
@@ -1,10 +1,148 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    api_keys (id) {
+        id -> Int4,
+        user_id -> Int4,
+        tenant_id -> Nullable<Int4>,
+        #[max_length = 255]
+        name -> Varchar,
+        #[max_length = 16]
+        prefix -> Varchar,
+        #[max_length = 64]
+        secret_hash -> Varchar,
+        scopes -> Array<Text>,
+        expires_at -> Nullable<Timestamp>,
+        last_used_at -> Nullable<Timestamp>,
+        revoked_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    audit_events (id) {
+        id -> Int4,
+        #[max_length = 64]
+        event_type -> Varchar,
+        #[max_length = 16]
+        outcome -> Varchar,
+        actor_id -> Nullable<Int4>,
+        impersonator_id -> Nullable<Int4>,
+        #[max_length = 45]
+        ip_address -> Nullable<Varchar>,
+        target -> Nullable<Text>,
+        detail -> Nullable<Text>,
+        created_at -> Timestamp,
+        #[max_length = 64]
+        prev_hash -> Varchar,
+        #[max_length = 64]
+        hash -> Varchar,
+    }
+}
+
+diesel::table! {
+    federated_login_states (id) {
+        id -> Int4,
+        #[max_length = 64]
+        state_hash -> Varchar,
+        #[max_length = 64]
+        provider -> Varchar,
+        #[max_length = 64]
+        nonce -> Varchar,
+        #[max_length = 128]
+        code_verifier -> Varchar,
+        link_user_id -> Nullable<Int4>,
+        expires_at -> Timestamp,
+        consumed_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     items (id) {
         id -> Int4,
         name -> Varchar,
         created_at -> Nullable<Timestamp>,
+        tenant_id -> Int4,
+        owner_id -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    oauth_authorization_codes (id) {
+        id -> Int4,
+        #[max_length = 64]
+        code_hash -> Varchar,
+        oauth_client_id -> Int4,
+        user_id -> Int4,
+        tenant_id -> Nullable<Int4>,
+        redirect_uri -> Text,
+        scopes -> Array<Text>,
+        #[max_length = 128]
+        code_challenge -> Nullable<Varchar>,
+        #[max_length = 10]
+        code_challenge_method -> Nullable<Varchar>,
+        expires_at -> Timestamp,
+        consumed_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        #[max_length = 255]
+        nonce -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    oauth_clients (id) {
+        id -> Int4,
+        #[max_length = 64]
+        client_id -> Varchar,
+        #[max_length = 64]
+        client_secret_hash -> Nullable<Varchar>,
+        #[max_length = 255]
+        name -> Varchar,
+        redirect_uris -> Array<Text>,
+        grant_types -> Array<Text>,
+        scopes -> Array<Text>,
+        service_user_id -> Nullable<Int4>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    oauth_refresh_tokens (id) {
+        id -> Int4,
+        #[max_length = 64]
+        token_hash -> Varchar,
+        oauth_client_id -> Int4,
+        user_id -> Int4,
+        tenant_id -> Nullable<Int4>,
+        scopes -> Array<Text>,
+        expires_at -> Timestamp,
+        revoked_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    oauth_revoked_access_tokens (jti) {
+        #[max_length = 64]
+        jti -> Varchar,
+        expires_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    organization_members (organization_id, user_id) {
+        organization_id -> Int4,
+        user_id -> Int4,
+    }
+}
+
+diesel::table! {
+    organizations (id) {
+        id -> Int4,
+        #[max_length = 255]
+        name -> Varchar,
+        created_at -> Timestamp,
     }
 }
 
@@ -18,6 +156,29 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    role_elevation_requests (id) {
+        id -> Int4,
+        user_id -> Int4,
+        role_id -> Int4,
+        reason -> Text,
+        duration_minutes -> Int4,
+        #[max_length = 20]
+        status -> Varchar,
+        approver_id -> Nullable<Int4>,
+        created_at -> Timestamp,
+        decided_at -> Nullable<Timestamp>,
+        tenant_id -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    role_parents (role_id, parent_role_id) {
+        role_id -> Int4,
+        parent_role_id -> Int4,
+    }
+}
+
 diesel::table! {
     role_permissions (role_id, permission_id) {
         role_id -> Int4,
@@ -35,6 +196,37 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    sessions (id) {
+        id -> Int4,
+        user_id -> Int4,
+        user_agent -> Nullable<Text>,
+        #[max_length = 45]
+        ip_address -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        last_seen_at -> Timestamp,
+        expires_at -> Timestamp,
+        revoked_at -> Nullable<Timestamp>,
+        impersonator_id -> Nullable<Int4>,
+        impersonation_reason -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    user_identities (id) {
+        id -> Int4,
+        user_id -> Int4,
+        #[max_length = 64]
+        provider -> Varchar,
+        #[max_length = 255]
+        subject -> Varchar,
+        #[max_length = 255]
+        email -> Nullable<Varchar>,
+        last_login_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     users (id) {
         id -> Int4,
@@ -42,26 +234,66 @@ diesel::table! {
         username -> Varchar,
         #[max_length = 255]
         password -> Varchar,
+        #[max_length = 255]
+        email -> Nullable<Varchar>,
+        is_service_account -> Bool,
     }
 }
 
 diesel::table! {
-    users_roles (user_id, role_id) {
+    users_roles (id) {
         user_id -> Int4,
         role_id -> Int4,
+        valid_from -> Nullable<Timestamp>,
+        valid_until -> Nullable<Timestamp>,
+        expiry_processed_at -> Nullable<Timestamp>,
+        id -> Int4,
+        tenant_id -> Nullable<Int4>,
     }
 }
 
+diesel::joinable!(api_keys -> organizations (tenant_id));
+diesel::joinable!(api_keys -> users (user_id));
+diesel::joinable!(federated_login_states -> users (link_user_id));
+diesel::joinable!(items -> organizations (tenant_id));
+diesel::joinable!(items -> users (owner_id));
+diesel::joinable!(oauth_authorization_codes -> oauth_clients (oauth_client_id));
+diesel::joinable!(oauth_authorization_codes -> organizations (tenant_id));
+diesel::joinable!(oauth_authorization_codes -> users (user_id));
+diesel::joinable!(oauth_clients -> users (service_user_id));
+diesel::joinable!(oauth_refresh_tokens -> oauth_clients (oauth_client_id));
+diesel::joinable!(oauth_refresh_tokens -> organizations (tenant_id));
+diesel::joinable!(oauth_refresh_tokens -> users (user_id));
+diesel::joinable!(organization_members -> organizations (organization_id));
+diesel::joinable!(organization_members -> users (user_id));
+diesel::joinable!(role_elevation_requests -> organizations (tenant_id));
+diesel::joinable!(role_elevation_requests -> roles (role_id));
 diesel::joinable!(role_permissions -> permissions (permission_id));
 diesel::joinable!(role_permissions -> roles (role_id));
+diesel::joinable!(sessions -> users (user_id));
+diesel::joinable!(user_identities -> users (user_id));
+diesel::joinable!(users_roles -> organizations (tenant_id));
 diesel::joinable!(users_roles -> roles (role_id));
 diesel::joinable!(users_roles -> users (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    api_keys,
+    audit_events,
+    federated_login_states,
     items,
+    oauth_authorization_codes,
+    oauth_clients,
+    oauth_refresh_tokens,
+    oauth_revoked_access_tokens,
+    organization_members,
+    organizations,
     permissions,
+    role_elevation_requests,
+    role_parents,
     role_permissions,
     roles,
+    sessions,
+    user_identities,
     users,
     users_roles,
 );
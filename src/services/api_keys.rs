@@ -0,0 +1,63 @@
+use chrono::Utc;
+use diesel::prelude::*;
+use crate::models::api_key::ApiKey;
+use crate::schema::api_keys::dsl::*;
+use crate::services::secrets::{constant_time_eq, hash_secret, random_string};
+
+// قالب کلید: `ck_<prefix>_<secret>`؛ prefix برای جستجو و secret فقط به‌صورت هش ذخیره می‌شود
+const KEY_MARKER: &str = "ck";
+const PREFIX_LEN: usize = 12;
+const SECRET_LEN: usize = 40;
+
+// فاصله‌ی حداقل بین دو به‌روزرسانی last_used_at برای یک کلید (ثانیه)
+const LAST_USED_RESOLUTION_SECS: i64 = 60;
+
+pub struct GeneratedKey {
+    pub prefix: String,
+    pub secret_hash: String,
+    pub key: String,
+}
+
+pub fn generate_key() -> GeneratedKey {
+    let key_prefix = random_string(PREFIX_LEN);
+    let secret = random_string(SECRET_LEN);
+    GeneratedKey {
+        secret_hash: hash_secret(&secret),
+        key: format!("{}_{}_{}", KEY_MARKER, key_prefix, secret),
+        prefix: key_prefix,
+    }
+}
+
+fn split_key(key: &str) -> Option<(&str, &str)> {
+    let rest = key.strip_prefix(KEY_MARKER)?.strip_prefix('_')?;
+    let (key_prefix, secret) = rest.split_once('_')?;
+    (key_prefix.len() == PREFIX_LEN && secret.len() == SECRET_LEN).then_some((key_prefix, secret))
+}
+
+// یافتن کلید فعال (ابطال‌نشده و منقضی‌نشده) و ثبت زمان استفاده؛ کلید نامعتبر None برمی‌گرداند
+pub fn authenticate_api_key(conn: &mut PgConnection, presented: &str) -> QueryResult<Option<ApiKey>> {
+    let Some((key_prefix, secret)) = split_key(presented) else {
+        return Ok(None);
+    };
+
+    let now = Utc::now().naive_utc();
+    let key = api_keys
+        .filter(prefix.eq(key_prefix))
+        .filter(revoked_at.is_null())
+        .filter(expires_at.is_null().or(expires_at.gt(now)))
+        .select(ApiKey::as_select())
+        .first(conn)
+        .optional()?;
+
+    let Some(key) = key.filter(|key| constant_time_eq(key.secret_hash.as_bytes(), hash_secret(secret).as_bytes())) else {
+        return Ok(None);
+    };
+
+    let stale_before = now - chrono::Duration::seconds(LAST_USED_RESOLUTION_SECS);
+    diesel::update(api_keys.find(key.id))
+        .filter(last_used_at.is_null().or(last_used_at.lt(stale_before)))
+        .set(last_used_at.eq(Some(now)))
+        .execute(conn)?;
+
+    Ok(Some(key))
+}
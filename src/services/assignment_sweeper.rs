@@ -0,0 +1,101 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{Integer, Nullable, Text, Timestamp};
+use log::{error, info};
+use serde::Serialize;
+use std::thread;
+use std::time::Duration;
+use crate::config::DbPool;
+use crate::services::permission_cache::PERMISSION_CHANGES_CHANNEL;
+
+// کانالی که رویدادهای فعال‌شدن و انقضای اختصاص نقش روی آن منتشر می‌شوند
+pub const ROLE_ASSIGNMENT_EVENTS_CHANNEL: &str = "role_assignment_events";
+
+#[derive(QueryableByName)]
+struct AssignmentRow {
+    #[diesel(sql_type = Integer)]
+    user_id: i32,
+    #[diesel(sql_type = Integer)]
+    role_id: i32,
+    #[diesel(sql_type = Nullable<Integer>)]
+    tenant_id: Option<i32>,
+    #[diesel(sql_type = Timestamp)]
+    at: NaiveDateTime,
+}
+
+#[derive(Serialize)]
+struct AssignmentEvent<'a> {
+    event: &'a str,
+    user_id: i32,
+    role_id: i32,
+    tenant_id: Option<i32>,
+    at: NaiveDateTime,
+}
+
+// بررسی دوره‌ای اختصاص‌های زمان‌دار: انتشار رویداد انقضا/فعال‌شدن و ابطال کش دسترسی کاربر
+pub fn spawn_assignment_sweeper(pool: DbPool, interval: Duration) {
+    thread::spawn(move || {
+        let mut last_sweep = Utc::now().naive_utc();
+        loop {
+            thread::sleep(interval);
+            let now = Utc::now().naive_utc();
+            match sweep(&pool, last_sweep, now) {
+                Ok(()) => last_sweep = now,
+                Err(err) => error!("Role assignment sweep failed: {:#}", err),
+            }
+        }
+    });
+}
+
+fn sweep(pool: &DbPool, last_sweep: NaiveDateTime, now: NaiveDateTime) -> anyhow::Result<()> {
+    let mut conn = pool.get()?;
+
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        // تغییر expiry_processed_at خودش تریگر ابطال کش همان کاربر را اجرا می‌کند
+        let expired = diesel::sql_query(
+            "UPDATE users_roles SET expiry_processed_at = $1
+            WHERE valid_until <= $1 AND expiry_processed_at IS NULL
+            RETURNING user_id, role_id, tenant_id, valid_until AS at",
+        )
+        .bind::<Timestamp, _>(now)
+        .load::<AssignmentRow>(conn)?;
+
+        // اختصاص‌هایی که از آخرین بررسی تا اکنون فعال شده‌اند تغییری در جدول ایجاد نمی‌کنند، پس اعلان صریح لازم است
+        let activated = diesel::sql_query(
+            "SELECT user_id, role_id, tenant_id, valid_from AS at FROM users_roles
+            WHERE valid_from > $1 AND valid_from <= $2",
+        )
+        .bind::<Timestamp, _>(last_sweep)
+        .bind::<Timestamp, _>(now)
+        .load::<AssignmentRow>(conn)?;
+
+        for row in &activated {
+            notify(conn, PERMISSION_CHANGES_CHANNEL, &row.user_id.to_string())?;
+        }
+
+        for (event, rows) in [("expired", &expired), ("activated", &activated)] {
+            for row in rows {
+                info!("Role assignment {}: user {} role {}", event, row.user_id, row.role_id);
+                let payload = serde_json::json!(AssignmentEvent {
+                    event,
+                    user_id: row.user_id,
+                    role_id: row.role_id,
+                    tenant_id: row.tenant_id,
+                    at: row.at,
+                });
+                notify(conn, ROLE_ASSIGNMENT_EVENTS_CHANNEL, &payload.to_string())?;
+            }
+        }
+
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
+fn notify(conn: &mut PgConnection, channel: &str, payload: &str) -> QueryResult<usize> {
+    diesel::sql_query("SELECT pg_notify($1, $2)")
+        .bind::<Text, _>(channel)
+        .bind::<Text, _>(payload)
+        .execute(conn)
+}
@@ -0,0 +1,156 @@
+use actix_web::web;
+use diesel::prelude::*;
+use log::error;
+use sha2::{Digest, Sha256};
+use crate::config::DbPool;
+use crate::db;
+use crate::models::audit::{AuditEvent, NewAuditEvent};
+use crate::schema::audit_events;
+
+// prev_hash اولین رویداد زنجیره
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+const VERIFY_BATCH_SIZE: i64 = 1000;
+
+// اولین شکست زنجیره‌ی هش
+#[derive(Debug)]
+pub struct ChainBreak {
+    pub event_id: i32,
+    pub reason: &'static str,
+}
+
+// ثبت رویداد امنیتی؛ خطای ثبت فقط گزارش می‌شود تا پاسخ درخواست اصلی تغییر نکند
+// (prev_hash و hash را تریگر audit_events_chain پر می‌کند)
+pub fn record_event(conn: &mut PgConnection, event: NewAuditEvent) {
+    if let Err(err) = diesel::insert_into(audit_events::table).values(&event).execute(conn) {
+        error!("Failed to record audit event {} ({}): {}", event.event_type, event.outcome, err);
+    }
+}
+
+// ثبت رویداد خارج از executor، برای middleware و کنترلرهایی که اتصال ندارند
+pub async fn record_event_async(pool: web::Data<DbPool>, event: NewAuditEvent) {
+    let (event_type, outcome) = (event.event_type, event.outcome);
+    if let Err(err) = db::run(&pool, move |conn| record_event(conn, event)).await {
+        error!("Failed to record audit event {} ({}): {}", event_type, outcome, err);
+    }
+}
+
+// هش رویداد با همان قالب تابع audit_event_hash در پایگاه داده: هر فیلد `طول:مقدار` و NULL به‌صورت `-`
+pub fn event_hash(event: &AuditEvent) -> String {
+    let id = event.id.to_string();
+    let actor_id = event.actor_id.map(|id| id.to_string());
+    let impersonator_id = event.impersonator_id.map(|id| id.to_string());
+    let created_at = event.created_at.format("%Y-%m-%dT%H:%M:%S%.6f").to_string();
+
+    let mut hasher = Sha256::new();
+    for field in [
+        Some(event.prev_hash.as_str()),
+        Some(id.as_str()),
+        Some(event.event_type.as_str()),
+        Some(event.outcome.as_str()),
+        actor_id.as_deref(),
+        impersonator_id.as_deref(),
+        event.ip_address.as_deref(),
+        event.target.as_deref(),
+        event.detail.as_deref(),
+        Some(created_at.as_str()),
+    ] {
+        match field {
+            Some(value) => {
+                hasher.update(format!("{}:", value.len()));
+                hasher.update(value);
+            }
+            None => hasher.update("-"),
+        }
+    }
+    hex::encode(hasher.finalize())
+}
+
+// پیمایش زنجیره به ترتیب شناسه؛ خروجی تعداد رویدادهای سالم یا اولین شکست است
+pub fn verify_chain(conn: &mut PgConnection) -> QueryResult<Result<usize, ChainBreak>> {
+    let mut prev_hash = GENESIS_HASH.to_string();
+    let mut last_id = 0;
+    let mut verified = 0;
+
+    loop {
+        let batch = audit_events::table
+            .filter(audit_events::id.gt(last_id))
+            .order(audit_events::id.asc())
+            .limit(VERIFY_BATCH_SIZE)
+            .select(AuditEvent::as_select())
+            .load(conn)?;
+        if batch.is_empty() {
+            return Ok(Ok(verified));
+        }
+
+        for event in batch {
+            if event.prev_hash != prev_hash {
+                return Ok(Err(ChainBreak { event_id: event.id, reason: "previous hash does not match the preceding event" }));
+            }
+            if event_hash(&event) != event.hash {
+                return Ok(Err(ChainBreak { event_id: event.id, reason: "event contents do not match its hash" }));
+            }
+            prev_hash = event.hash;
+            last_id = event.id;
+            verified += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use diesel::prelude::*;
+    use super::{event_hash, record_event, GENESIS_HASH};
+    use crate::models::audit::{AuditEvent, AuditOutcome, NewAuditEvent};
+    use crate::schema::audit_events;
+    use crate::test_support::database_pool;
+
+    // مقدار مورد انتظار جدا از این کد با sha256 روی همان قالب حساب شده؛ طول فیلدها بایتی است (octet_length)، نه تعداد کاراکتر
+    #[test]
+    fn event_hash_uses_length_prefixed_fields() {
+        let event = AuditEvent {
+            id: 7,
+            event_type: "role.create".to_string(),
+            outcome: "success".to_string(),
+            actor_id: Some(3),
+            impersonator_id: None,
+            ip_address: Some("127.0.0.1".to_string()),
+            target: None,
+            detail: Some("نقش جدید".to_string()),
+            created_at: NaiveDate::from_ymd_opt(2026, 10, 19).unwrap().and_hms_micro_opt(12, 30, 45, 123456).unwrap(),
+            prev_hash: GENESIS_HASH.to_string(),
+            hash: String::new(),
+        };
+
+        assert_eq!(event_hash(&event), "0b19dc20b75e6aad718a2033a6e34034f70b57bad01c9f9f5c421fc4493438e5");
+    }
+
+    // هش محاسبه‌شده در تریگر (audit_event_hash) باید با event_hash یکی باشد، هم با فیلدهای NULL و هم با متن چندبایتی
+    #[test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    fn event_hash_matches_the_database_function() {
+        let mut conn = database_pool().get().unwrap();
+        conn.test_transaction::<_, diesel::result::Error, _>(|conn| {
+            record_event(conn, NewAuditEvent::new("hash.test", AuditOutcome::Denied));
+            record_event(
+                conn,
+                NewAuditEvent::new("hash.test", AuditOutcome::Success)
+                    .actor(1, Some(2))
+                    .target("user:1")
+                    .detail("ورود با کلید «آزمون»"),
+            );
+
+            let events = audit_events::table
+                .filter(audit_events::event_type.eq("hash.test"))
+                .order(audit_events::id.asc())
+                .select(AuditEvent::as_select())
+                .load(conn)?;
+            assert_eq!(events.len(), 2);
+            for event in &events {
+                assert_eq!(event_hash(event), event.hash, "event {}", event.id);
+            }
+            assert_eq!(events[1].prev_hash, events[0].hash);
+            Ok(())
+        });
+    }
+}
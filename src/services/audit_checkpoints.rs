@@ -0,0 +1,105 @@
+use actix_web::web;
+use chrono::Utc;
+use diesel::prelude::*;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+use crate::config::DbPool;
+use crate::schema::audit_events;
+use crate::services::oidc::OidcProvider;
+
+// checkpoint امضاشده‌ی انتهای زنجیره؛ با داشتن آن حذف یا بازنویسی رویدادهای قدیمی‌تر قابل تشخیص است
+#[derive(Serialize, Deserialize)]
+pub struct AuditCheckpoint {
+    pub iss: String,
+    pub iat: i64,
+    pub last_event_id: i32,
+    pub hash: String,
+}
+
+// یک checkpoint نامعتبر در فایل (شماره‌ی خط از ۱)
+#[derive(Debug)]
+pub struct CheckpointMismatch {
+    pub line: usize,
+    pub reason: String,
+}
+
+// هر بار که رویداد تازه‌ای ثبت شده باشد، یک checkpoint به‌صورت JWS در یک خط به انتهای فایل افزوده می‌شود
+pub fn spawn_checkpoint_writer(pool: DbPool, oidc: web::Data<OidcProvider>, file: PathBuf, interval: Duration) {
+    thread::spawn(move || {
+        let mut last_checkpoint = None;
+        loop {
+            thread::sleep(interval);
+            match write_checkpoint(&pool, &oidc, &file, last_checkpoint) {
+                Ok(Some(event_id)) => last_checkpoint = Some(event_id),
+                Ok(None) => {}
+                Err(err) => error!("Audit checkpoint failed: {:#}", err),
+            }
+        }
+    });
+}
+
+fn write_checkpoint(pool: &DbPool, oidc: &OidcProvider, file: &Path, last_checkpoint: Option<i32>) -> anyhow::Result<Option<i32>> {
+    let mut conn = pool.get()?;
+    let head = audit_events::table
+        .order(audit_events::id.desc())
+        .select((audit_events::id, audit_events::hash))
+        .first::<(i32, String)>(&mut conn)
+        .optional()?;
+
+    let Some((last_event_id, hash)) = head else {
+        return Ok(None);
+    };
+    if last_checkpoint == Some(last_event_id) {
+        return Ok(None);
+    }
+
+    let token = oidc.sign(&AuditCheckpoint {
+        iss: oidc.issuer().to_string(),
+        iat: Utc::now().timestamp(),
+        last_event_id,
+        hash,
+    })?;
+    let mut out = OpenOptions::new().create(true).append(true).open(file)?;
+    writeln!(out, "{}", token)?;
+    info!("Audit checkpoint written at event {}", last_event_id);
+    Ok(Some(last_event_id))
+}
+
+// بررسی امضای همه‌ی checkpointهای فایل و تطابق هش آن‌ها با رویدادهای فعلی؛ خروجی تعداد checkpointهای سالم است
+pub fn verify_checkpoints(
+    conn: &mut PgConnection,
+    oidc: &OidcProvider,
+    file: &Path,
+) -> anyhow::Result<Result<usize, CheckpointMismatch>> {
+    let contents = fs::read_to_string(file)?;
+    let mut verified = 0;
+
+    for (index, token) in contents.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let line = index + 1;
+        let checkpoint = match oidc.verify::<AuditCheckpoint>(token.trim()) {
+            Ok(checkpoint) => checkpoint,
+            Err(err) => return Ok(Err(CheckpointMismatch { line, reason: format!("invalid signature: {}", err) })),
+        };
+
+        let stored = audit_events::table
+            .find(checkpoint.last_event_id)
+            .select(audit_events::hash)
+            .first::<String>(conn)
+            .optional()?;
+        let reason = match stored {
+            Some(hash) if hash == checkpoint.hash => {
+                verified += 1;
+                continue;
+            }
+            Some(_) => format!("event {} does not match the checkpointed hash", checkpoint.last_event_id),
+            None => format!("event {} is missing", checkpoint.last_event_id),
+        };
+        return Ok(Err(CheckpointMismatch { line, reason }));
+    }
+    Ok(Ok(verified))
+}
@@ -0,0 +1,543 @@
+use actix_web::{dev::ServiceRequest, web};
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Datelike, Local, Timelike};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use crate::middleware::permissions::UserAccess;
+
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+const ITEM_ROUTE: &str = "/items/{id}";
+const ITEM_OWNER: &str = "item.owner_id";
+
+// ویژگی‌های درخواست که شرط‌های سیاست روی آن‌ها ارزیابی می‌شوند، مثل `time.hour` یا `path.user_id`
+pub type Attributes = BTreeMap<String, Value>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Effect {
+    Allow,
+    Deny,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operator {
+    Eq,
+    Ne,
+    In,
+    NotIn,
+    // بازه‌ی بسته [a, b]
+    Between,
+    NotBetween,
+    InCidr,
+    NotInCidr,
+    // مقدار شرط نام یک ویژگی دیگر است، مثل `user.id`
+    EqAttribute,
+    // ویژگی آرایه‌ای (مثل `user.roles`) شامل مقدار باشد
+    Contains,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Condition {
+    pub attribute: String,
+    pub op: Operator,
+    pub value: Value,
+}
+
+// یک قانون سیاست؛ methods و paths خالی یعنی روی همه‌ی درخواست‌ها اعمال شود
+#[derive(Debug, Clone, Deserialize)]
+pub struct Policy {
+    pub id: String,
+    pub effect: Effect,
+    #[serde(default)]
+    pub methods: Vec<String>,
+    // الگوی مسیر (`/roles/{user_id}`) یا مسیر واقعی؛ `*` در انتها یعنی پیشوند
+    #[serde(default)]
+    pub paths: Vec<String>,
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PolicySet {
+    #[serde(default)]
+    pub policies: Vec<Policy>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Decision {
+    pub allowed: bool,
+    pub policy: Option<String>,
+    pub reason: String,
+}
+
+impl PolicySet {
+    pub fn parse(source: &str) -> Result<Self> {
+        let set: PolicySet = serde_json::from_str(source).context("Invalid policy JSON")?;
+        for policy in &set.policies {
+            for condition in &policy.conditions {
+                condition
+                    .validate()
+                    .with_context(|| format!("Invalid condition on `{}` in policy `{}`", condition.attribute, policy.id))?;
+            }
+        }
+        Ok(set)
+    }
+
+    // deny-overrides: هر deny منطبق رد می‌کند؛ اگر برای درخواست allow تعریف شده باشد، حداقل یکی باید برقرار باشد
+    pub fn evaluate(&self, attributes: &Attributes) -> Decision {
+        let applicable: Vec<&Policy> = self.policies.iter().filter(|p| p.targets(attributes)).collect();
+
+        if let Some(policy) = applicable
+            .iter()
+            .find(|p| p.effect == Effect::Deny && p.conditions_hold(attributes))
+        {
+            return Decision {
+                allowed: false,
+                policy: Some(policy.id.clone()),
+                reason: format!("denied by policy `{}`", policy.id),
+            };
+        }
+
+        let allows: Vec<&&Policy> = applicable.iter().filter(|p| p.effect == Effect::Allow).collect();
+        if allows.is_empty() {
+            return Decision {
+                allowed: true,
+                policy: None,
+                reason: "no policy applies".to_string(),
+            };
+        }
+
+        match allows.iter().find(|p| p.conditions_hold(attributes)) {
+            Some(policy) => Decision {
+                allowed: true,
+                policy: Some(policy.id.clone()),
+                reason: format!("allowed by policy `{}`", policy.id),
+            },
+            None => Decision {
+                allowed: false,
+                policy: None,
+                reason: "no allow policy conditions matched".to_string(),
+            },
+        }
+    }
+
+    fn references(&self, attribute: &str) -> bool {
+        self.policies.iter().any(|policy| {
+            policy.conditions.iter().any(|c| {
+                c.attribute == attribute || (matches!(c.op, Operator::EqAttribute) && c.value == json!(attribute))
+            })
+        })
+    }
+}
+
+impl Policy {
+    fn targets(&self, attributes: &Attributes) -> bool {
+        let method = attributes.get("request.method").and_then(Value::as_str).unwrap_or("");
+        let route = attributes.get("request.route").and_then(Value::as_str).unwrap_or("");
+        let path = attributes.get("request.path").and_then(Value::as_str).unwrap_or("");
+
+        let method_matches = self.methods.is_empty() || self.methods.iter().any(|m| m.eq_ignore_ascii_case(method));
+        let path_matches = self.paths.is_empty()
+            || self
+                .paths
+                .iter()
+                .any(|pattern| path_pattern_matches(pattern, route) || path_pattern_matches(pattern, path));
+
+        method_matches && path_matches
+    }
+
+    fn conditions_hold(&self, attributes: &Attributes) -> bool {
+        self.conditions.iter().all(|c| c.holds(attributes))
+    }
+}
+
+impl Condition {
+    fn validate(&self) -> Result<()> {
+        match self.op {
+            Operator::In | Operator::NotIn => {
+                self.value.as_array().ok_or_else(|| anyhow!("value must be an array"))?;
+            }
+            Operator::Between | Operator::NotBetween => {
+                range_bounds(&self.value).ok_or_else(|| anyhow!("value must be [min, max]"))?;
+            }
+            Operator::InCidr | Operator::NotInCidr => {
+                for cidr in cidr_list(&self.value) {
+                    parse_cidr(cidr.as_str().unwrap_or_default())?;
+                }
+            }
+            Operator::EqAttribute => {
+                self.value.as_str().ok_or_else(|| anyhow!("value must be an attribute name"))?;
+            }
+            Operator::Eq | Operator::Ne | Operator::Contains => {}
+        }
+        Ok(())
+    }
+
+    // ویژگی ناموجود هیچ شرطی را برقرار نمی‌کند (حتی ne / not_in)
+    fn holds(&self, attributes: &Attributes) -> bool {
+        let Some(actual) = attributes.get(&self.attribute) else {
+            return false;
+        };
+
+        match self.op {
+            Operator::Eq => actual == &self.value,
+            Operator::Ne => actual != &self.value,
+            Operator::In => self.value.as_array().is_some_and(|values| values.contains(actual)),
+            Operator::NotIn => self.value.as_array().is_some_and(|values| !values.contains(actual)),
+            Operator::Between => in_range(actual, &self.value),
+            Operator::NotBetween => actual.is_number() && !in_range(actual, &self.value),
+            Operator::InCidr => ip_in_cidrs(actual, &self.value),
+            Operator::NotInCidr => actual.is_string() && !ip_in_cidrs(actual, &self.value),
+            Operator::EqAttribute => self
+                .value
+                .as_str()
+                .and_then(|other| attributes.get(other))
+                .is_some_and(|other| other == actual),
+            Operator::Contains => actual.as_array().is_some_and(|values| values.contains(&self.value)),
+        }
+    }
+}
+
+fn path_pattern_matches(pattern: &str, path: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => path.starts_with(prefix),
+        None => pattern == path,
+    }
+}
+
+fn range_bounds(value: &Value) -> Option<(f64, f64)> {
+    match value.as_array()?.as_slice() {
+        [min, max] => Some((min.as_f64()?, max.as_f64()?)),
+        _ => None,
+    }
+}
+
+fn in_range(actual: &Value, range: &Value) -> bool {
+    match (actual.as_f64(), range_bounds(range)) {
+        (Some(n), Some((min, max))) => min <= n && n <= max,
+        _ => false,
+    }
+}
+
+fn cidr_list(value: &Value) -> Vec<Value> {
+    match value {
+        Value::Array(values) => values.clone(),
+        other => vec![other.clone()],
+    }
+}
+
+fn parse_cidr(cidr: &str) -> Result<(IpAddr, u32)> {
+    let (addr, prefix) = cidr.split_once('/').unwrap_or((cidr, ""));
+    let addr: IpAddr = addr.parse().with_context(|| format!("invalid IP address in `{}`", cidr))?;
+    let max_prefix = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix = if prefix.is_empty() {
+        max_prefix
+    } else {
+        prefix.parse().with_context(|| format!("invalid prefix length in `{}`", cidr))?
+    };
+    if prefix > max_prefix {
+        bail!("prefix length too large in `{}`", cidr);
+    }
+    Ok((addr, prefix))
+}
+
+fn cidr_contains(network: IpAddr, prefix: u32, ip: IpAddr) -> bool {
+    match (network, ip) {
+        (IpAddr::V4(network), IpAddr::V4(ip)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            u32::from(network) & mask == u32::from(ip) & mask
+        }
+        (IpAddr::V6(network), IpAddr::V6(ip)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+            u128::from(network) & mask == u128::from(ip) & mask
+        }
+        _ => false,
+    }
+}
+
+fn ip_in_cidrs(actual: &Value, cidrs: &Value) -> bool {
+    let Some(ip) = actual.as_str().and_then(|ip| ip.parse::<IpAddr>().ok()) else {
+        return false;
+    };
+    cidr_list(cidrs).iter().any(|cidr| {
+        parse_cidr(cidr.as_str().unwrap_or_default()).is_ok_and(|(network, prefix)| cidr_contains(network, prefix, ip))
+    })
+}
+
+// اطلاعات درخواست که ویژگی‌های سیاست از آن ساخته می‌شوند
+pub struct RequestFacts {
+    pub method: String,
+    pub route: String,
+    pub path: String,
+    pub path_params: BTreeMap<String, String>,
+    pub ip: Option<IpAddr>,
+    pub now: DateTime<Local>,
+    // مالک آیتم مسیر `/items/{id}`؛ فقط وقتی سیاستی به `item.owner_id` ارجاع دهد بارگذاری می‌شود
+    pub item_owner: Option<i32>,
+}
+
+impl RequestFacts {
+    // IP از اتصال مستقیم گرفته می‌شود نه از هدرهای X-Forwarded-For
+    pub fn from_request(req: &ServiceRequest) -> Self {
+        RequestFacts {
+            method: req.method().to_string(),
+            route: req.match_pattern().unwrap_or_default(),
+            path: req.path().to_string(),
+            path_params: req
+                .match_info()
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            ip: req.peer_addr().map(|addr| addr.ip()),
+            now: Local::now(),
+            item_owner: None,
+        }
+    }
+
+    // شناسه‌ی آیتم مسیر `/items/{id}`
+    pub fn item_id(&self) -> Option<i32> {
+        if self.route != ITEM_ROUTE {
+            return None;
+        }
+        self.path_params.get("id")?.parse().ok()
+    }
+
+    pub fn attributes(&self, user_id: i32, tenant: Option<i32>, access: Option<&UserAccess>) -> Attributes {
+        let mut attributes = Attributes::new();
+        attributes.insert("request.method".to_string(), json!(self.method));
+        attributes.insert("request.route".to_string(), json!(self.route));
+        attributes.insert("request.path".to_string(), json!(self.path));
+        if let Some(ip) = self.ip {
+            attributes.insert("request.ip".to_string(), json!(ip.to_string()));
+        }
+        attributes.insert("time.hour".to_string(), json!(self.now.hour()));
+        attributes.insert("time.weekday".to_string(), json!(self.now.weekday().to_string().to_lowercase()));
+        attributes.insert("user.id".to_string(), json!(user_id));
+        if let Some(tenant) = tenant {
+            attributes.insert("user.tenant_id".to_string(), json!(tenant));
+        }
+        if let Some(access) = access {
+            attributes.insert("user.roles".to_string(), json!(access.roles));
+        }
+        if let Some(owner) = self.item_owner {
+            attributes.insert(ITEM_OWNER.to_string(), json!(owner));
+        }
+        for (name, value) in &self.path_params {
+            // پارامترهای عددی به‌صورت عدد ذخیره می‌شوند تا با `user.id` قابل مقایسه باشند
+            let value = value.parse::<i64>().map(|n| json!(n)).unwrap_or_else(|_| json!(value));
+            attributes.insert(format!("path.{}", name), value);
+        }
+        attributes
+    }
+}
+
+// سرویس مجوزدهی مبتنی بر ویژگی؛ RbacMiddleware پس از بررسی نقش‌ها با آن مشورت می‌کند
+pub struct AuthorizationService {
+    path: PathBuf,
+    policies: RwLock<Arc<PolicySet>>,
+}
+
+impl AuthorizationService {
+    // فایل ناموجود یعنی هیچ سیاستی وجود ندارد؛ فایل نامعتبر خطا است
+    pub fn load(path: &Path) -> Result<Self> {
+        let policies = read_policy_file(path)?;
+        info!("Loaded {} authorization policies from {}", policies.policies.len(), path.display());
+        Ok(AuthorizationService {
+            path: path.to_path_buf(),
+            policies: RwLock::new(Arc::new(policies)),
+        })
+    }
+
+    pub fn reload(&self) -> Result<()> {
+        let policies = read_policy_file(&self.path)?;
+        info!("Reloaded {} authorization policies from {}", policies.policies.len(), self.path.display());
+        *self.policies.write().unwrap() = Arc::new(policies);
+        Ok(())
+    }
+
+    pub fn policies(&self) -> Arc<PolicySet> {
+        self.policies.read().unwrap().clone()
+    }
+
+    pub fn evaluate(&self, attributes: &Attributes) -> Decision {
+        self.policies().evaluate(attributes)
+    }
+
+    // آیا برای ارزیابی باید نقش‌های کاربر بارگذاری شوند؟
+    pub fn needs_roles(&self) -> bool {
+        self.policies().references("user.roles")
+    }
+
+    // آیا برای ارزیابی باید مالک آیتم از پایگاه داده خوانده شود؟
+    pub fn needs_item_owner(&self) -> bool {
+        self.policies().references(ITEM_OWNER)
+    }
+}
+
+fn read_policy_file(path: &Path) -> Result<PolicySet> {
+    if !path.exists() {
+        return Ok(PolicySet::default());
+    }
+    let source = fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    PolicySet::parse(&source).with_context(|| format!("Cannot parse {}", path.display()))
+}
+
+fn modified_at(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+// بارگذاری دوباره با تغییر زمان ویرایش فایل؛ در صورت خطا سیاست‌های قبلی حفظ می‌شوند
+pub fn spawn_policy_watcher(service: web::Data<AuthorizationService>) {
+    thread::spawn(move || {
+        let mut last_modified = modified_at(&service.path);
+        loop {
+            thread::sleep(WATCH_INTERVAL);
+            let modified = modified_at(&service.path);
+            if modified == last_modified {
+                continue;
+            }
+            last_modified = modified;
+            if let Err(err) = service.reload() {
+                error!("Keeping previous authorization policies: {:#}", err);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Local;
+    use serde_json::{json, Value};
+    use super::{parse_cidr, Attributes, PolicySet, RequestFacts};
+
+    fn attributes(pairs: &[(&str, Value)]) -> Attributes {
+        pairs.iter().map(|(name, value)| (name.to_string(), value.clone())).collect()
+    }
+
+    fn policies() -> PolicySet {
+        PolicySet::parse(
+            r#"{ "policies": [
+                { "id": "no-night-deletes", "effect": "deny", "methods": ["DELETE"], "paths": ["/roles/*"],
+                  "conditions": [{ "attribute": "time.hour", "op": "not_between", "value": [8, 17] }] },
+                { "id": "internal-authz", "effect": "allow", "paths": ["/authz/*"],
+                  "conditions": [{ "attribute": "request.ip", "op": "in_cidr", "value": ["10.0.0.0/8", "::1"] }] },
+                { "id": "own-roles", "effect": "allow", "methods": ["GET"], "paths": ["/roles/{user_id}"],
+                  "conditions": [{ "attribute": "path.user_id", "op": "eq_attribute", "value": "user.id" }] },
+                { "id": "admins-see-all-roles", "effect": "allow", "methods": ["GET"], "paths": ["/roles/{user_id}"],
+                  "conditions": [{ "attribute": "user.roles", "op": "contains", "value": "admin" }] },
+                { "id": "owners-edit-items", "effect": "allow", "methods": ["PUT", "DELETE"], "paths": ["/items/{id}"],
+                  "conditions": [{ "attribute": "item.owner_id", "op": "eq_attribute", "value": "user.id" }] }
+            ] }"#,
+        )
+        .expect("Invalid test policies")
+    }
+
+    #[test]
+    fn requests_without_applicable_policies_are_allowed() {
+        let decision = policies().evaluate(&attributes(&[("request.method", json!("GET")), ("request.path", json!("/items"))]));
+        assert!(decision.allowed);
+        assert_eq!(decision.policy, None);
+    }
+
+    #[test]
+    fn matching_deny_wins() {
+        let request = |hour: i64| {
+            attributes(&[
+                ("request.method", json!("DELETE")),
+                ("request.route", json!("/roles/{user_id}")),
+                ("request.path", json!("/roles/5")),
+                ("time.hour", json!(hour)),
+            ])
+        };
+        let decision = policies().evaluate(&request(22));
+        assert!(!decision.allowed);
+        assert_eq!(decision.policy.as_deref(), Some("no-night-deletes"));
+        assert!(policies().evaluate(&request(10)).allowed);
+    }
+
+    #[test]
+    fn applicable_allows_must_hold_for_one_policy() {
+        let request = |user_id: i64, roles: Value| {
+            attributes(&[
+                ("request.method", json!("GET")),
+                ("request.route", json!("/roles/{user_id}")),
+                ("path.user_id", json!(5)),
+                ("user.id", json!(user_id)),
+                ("user.roles", roles),
+            ])
+        };
+        assert_eq!(policies().evaluate(&request(5, json!([]))).policy.as_deref(), Some("own-roles"));
+        assert_eq!(policies().evaluate(&request(6, json!(["admin"]))).policy.as_deref(), Some("admins-see-all-roles"));
+        assert!(!policies().evaluate(&request(6, json!(["editor"]))).allowed);
+    }
+
+    // مالک آیتم فقط برای مسیر `/items/{id}` و فقط وقتی بارگذاری شده باشد ویژگی می‌شود
+    #[test]
+    fn item_owners_are_compared_with_the_user() {
+        let facts = |route: &str, item_owner: Option<i32>| RequestFacts {
+            method: "PUT".to_string(),
+            route: route.to_string(),
+            path: "/items/9".to_string(),
+            path_params: [("id".to_string(), "9".to_string())].into(),
+            ip: None,
+            now: Local::now(),
+            item_owner,
+        };
+        assert_eq!(facts("/items/{id}", None).item_id(), Some(9));
+        assert_eq!(facts("/roles/{id}", None).item_id(), None);
+        assert!(policies().references("item.owner_id"));
+
+        let owned = facts("/items/{id}", Some(5));
+        assert_eq!(owned.attributes(5, Some(1), None).get("item.owner_id"), Some(&json!(5)));
+        assert_eq!(policies().evaluate(&owned.attributes(5, Some(1), None)).policy.as_deref(), Some("owners-edit-items"));
+        assert!(!policies().evaluate(&owned.attributes(6, Some(1), None)).allowed);
+        assert!(!policies().evaluate(&facts("/items/{id}", None).attributes(5, Some(1), None)).allowed);
+    }
+
+    // ویژگی ناموجود شرط را برقرار نمی‌کند، پس allow منطبق‌نشده رد می‌کند
+    #[test]
+    fn missing_attributes_do_not_satisfy_conditions() {
+        let request = |ip: Option<&str>| {
+            let mut facts = attributes(&[("request.method", json!("GET")), ("request.path", json!("/authz/explain"))]);
+            if let Some(ip) = ip {
+                facts.insert("request.ip".to_string(), json!(ip));
+            }
+            facts
+        };
+        assert!(policies().evaluate(&request(Some("10.20.30.40"))).allowed);
+        assert!(policies().evaluate(&request(Some("::1"))).allowed);
+        assert!(!policies().evaluate(&request(Some("192.168.1.1"))).allowed);
+        assert!(!policies().evaluate(&request(Some("not-an-ip"))).allowed);
+        assert!(!policies().evaluate(&request(None)).allowed);
+    }
+
+    #[test]
+    fn cidrs_are_parsed_with_default_and_bounded_prefixes() {
+        assert_eq!(parse_cidr("10.0.0.0/8").unwrap(), ("10.0.0.0".parse().unwrap(), 8));
+        assert_eq!(parse_cidr("127.0.0.1").unwrap(), ("127.0.0.1".parse().unwrap(), 32));
+        assert_eq!(parse_cidr("::1").unwrap(), ("::1".parse().unwrap(), 128));
+        assert_eq!(parse_cidr("0.0.0.0/0").unwrap().1, 0);
+        assert!(parse_cidr("10.0.0.0/33").is_err());
+        assert!(parse_cidr("fe80::/129").is_err());
+        assert!(parse_cidr("10.0.0/8").is_err());
+        assert!(parse_cidr("10.0.0.0/x").is_err());
+    }
+
+    #[test]
+    fn invalid_conditions_are_rejected_when_parsing() {
+        let parse = |condition: &str| PolicySet::parse(&format!(r#"{{ "policies": [{{ "id": "p", "effect": "deny", "conditions": [{}] }}] }}"#, condition));
+        assert!(parse(r#"{ "attribute": "request.ip", "op": "in_cidr", "value": "10.0.0.0/40" }"#).is_err());
+        assert!(parse(r#"{ "attribute": "time.hour", "op": "between", "value": [8] }"#).is_err());
+        assert!(parse(r#"{ "attribute": "time.weekday", "op": "in", "value": "mon" }"#).is_err());
+        assert!(parse(r#"{ "attribute": "time.hour", "op": "between", "value": [8, 17] }"#).is_ok());
+    }
+}
@@ -0,0 +1,515 @@
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use bcrypt::{hash, DEFAULT_COST};
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use jsonwebtoken::jwk::{Jwk, JwkSet};
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use log::{info, warn};
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use url::Url;
+use crate::models::identity::{FederatedLoginState, NewFederatedLoginState, NewUserIdentity, UserIdentity};
+use crate::models::user::{NewUser, User, UserRole};
+use crate::schema::{federated_login_states, roles, user_identities, users, users_roles};
+use crate::services::secrets::{hash_secret, random_string};
+
+const LOGIN_STATE_TTL_SECS: i64 = 10 * 60;
+const HTTP_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+const STATE_LEN: usize = 40;
+const NONCE_LEN: usize = 32;
+const CODE_VERIFIER_LEN: usize = 64;
+// رمز تصادفی کاربرانی که با ارائه‌دهنده‌ی بیرونی ساخته می‌شوند؛ ورود با رمز برایشان عملاً ممکن نیست
+const UNUSABLE_PASSWORD_LEN: usize = 48;
+const USERNAME_MAX_LEN: usize = 240;
+
+// الگوریتم‌های نامتقارن قابل قبول برای ID token ارائه‌دهنده؛ HS* هرگز پذیرفته نمی‌شود
+const ALLOWED_ALGORITHMS: [Algorithm; 8] = [
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::ES384,
+];
+
+// یک ارائه‌دهنده‌ی بیرونی OIDC؛ endpointها از سند discovery صادرکننده خوانده می‌شوند
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProviderConfig {
+    pub name: String,
+    pub issuer: String,
+    pub client_id: String,
+    #[serde(default)]
+    pub client_secret: Option<String>,
+    #[serde(default = "default_scopes")]
+    pub scopes: Vec<String>,
+    // نام نقش‌هایی که به کاربر تازه‌ساخته‌شده (سراسری) داده می‌شوند
+    #[serde(default)]
+    pub default_roles: Vec<String>,
+}
+
+fn default_scopes() -> Vec<String> {
+    ["openid", "profile", "email"].map(str::to_string).to_vec()
+}
+
+#[derive(Deserialize)]
+struct ProvidersFile {
+    #[serde(default)]
+    providers: Vec<ProviderConfig>,
+}
+
+#[derive(Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Deserialize)]
+struct UpstreamTokenResponse {
+    id_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct UpstreamClaims {
+    sub: String,
+    nonce: Option<String>,
+    email: Option<String>,
+    // بعضی ارائه‌دهنده‌ها مقدار را به‌صورت رشته‌ی "true" می‌فرستند
+    email_verified: Option<Value>,
+    preferred_username: Option<String>,
+}
+
+// هویت تأییدشده‌ی کاربر نزد ارائه‌دهنده‌ی بیرونی
+pub struct ExternalIdentity {
+    pub subject: String,
+    // فقط ایمیل تأییدشده توسط ارائه‌دهنده نگه داشته می‌شود
+    pub email: Option<String>,
+    pub preferred_username: Option<String>,
+}
+
+// مقادیری که برای شروع ورود به ارائه‌دهنده فرستاده می‌شوند
+pub struct PendingLogin {
+    pub state: String,
+    pub nonce: String,
+    pub code_challenge: String,
+}
+
+#[derive(Debug)]
+pub enum FederationError {
+    UnknownProvider,
+    InvalidState(&'static str),
+    Upstream(String),
+    InvalidIdToken(String),
+    Conflict(&'static str),
+    Database(diesel::result::Error),
+    Internal(String),
+}
+
+impl fmt::Display for FederationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FederationError::UnknownProvider => write!(f, "Unknown identity provider"),
+            FederationError::InvalidState(reason) | FederationError::Conflict(reason) => write!(f, "{}", reason),
+            FederationError::Upstream(reason) => write!(f, "Identity provider error: {}", reason),
+            FederationError::InvalidIdToken(reason) => write!(f, "Invalid ID token: {}", reason),
+            FederationError::Database(err) => write!(f, "Query error: {}", err),
+            FederationError::Internal(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl From<diesel::result::Error> for FederationError {
+    fn from(err: diesel::result::Error) -> Self {
+        FederationError::Database(err)
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for FederationError {
+    fn from(err: jsonwebtoken::errors::Error) -> Self {
+        FederationError::InvalidIdToken(err.to_string())
+    }
+}
+
+// ورود با ارائه‌دهندگان بیرونی OIDC؛ سند discovery و کلیدهای امضا در حافظه نگه داشته می‌شوند
+pub struct Federation {
+    providers: BTreeMap<String, ProviderConfig>,
+    http: Client,
+    metadata: RwLock<HashMap<String, Arc<ProviderMetadata>>>,
+    keys: RwLock<HashMap<String, Arc<JwkSet>>>,
+}
+
+impl Federation {
+    // بدون فایل پیکربندی هیچ ارائه‌دهنده‌ای فعال نیست
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let configured = match path {
+            Some(path) => {
+                let source = fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+                serde_json::from_str::<ProvidersFile>(&source)
+                    .context("Invalid federation providers JSON")?
+                    .providers
+            }
+            None => Vec::new(),
+        };
+
+        let mut providers = BTreeMap::new();
+        for provider in configured {
+            if !provider.scopes.iter().any(|scope| scope == "openid") {
+                bail!("Provider '{}' must request the openid scope", provider.name);
+            }
+            if let Some(duplicate) = providers.insert(provider.name.clone(), provider) {
+                bail!("Duplicate provider '{}'", duplicate.name);
+            }
+        }
+        info!("Loaded {} federated login providers", providers.len());
+
+        Ok(Federation {
+            providers,
+            http: Client::builder().timeout(HTTP_TIMEOUT).build()?,
+            metadata: RwLock::default(),
+            keys: RwLock::default(),
+        })
+    }
+
+    pub fn provider(&self, name: &str) -> Result<&ProviderConfig, FederationError> {
+        self.providers.get(name).ok_or(FederationError::UnknownProvider)
+    }
+
+    async fn fetch_json<T: DeserializeOwned>(&self, url: &str) -> Result<T, FederationError> {
+        let response = self
+            .http
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|err| FederationError::Upstream(format!("{}: {}", url, err)))?;
+        response
+            .json()
+            .await
+            .map_err(|err| FederationError::Upstream(format!("{}: {}", url, err)))
+    }
+
+    async fn metadata(&self, provider: &ProviderConfig) -> Result<Arc<ProviderMetadata>, FederationError> {
+        if let Some(metadata) = self.metadata.read().unwrap().get(&provider.name) {
+            return Ok(metadata.clone());
+        }
+
+        let url = format!("{}/.well-known/openid-configuration", provider.issuer.trim_end_matches('/'));
+        let metadata: ProviderMetadata = self.fetch_json(&url).await?;
+        if metadata.issuer.trim_end_matches('/') != provider.issuer.trim_end_matches('/') {
+            return Err(FederationError::Upstream(format!("Discovery issuer '{}' does not match", metadata.issuer)));
+        }
+
+        let metadata = Arc::new(metadata);
+        self.metadata.write().unwrap().insert(provider.name.clone(), metadata.clone());
+        Ok(metadata)
+    }
+
+    // کلید با kid ناشناخته یعنی ارائه‌دهنده کلیدش را چرخانده است، پس JWKS دوباره خوانده می‌شود
+    async fn signing_key(
+        &self,
+        provider: &ProviderConfig,
+        metadata: &ProviderMetadata,
+        kid: Option<&str>,
+    ) -> Result<Jwk, FederationError> {
+        let find = |keys: &JwkSet| match kid {
+            Some(kid) => keys.find(kid).cloned(),
+            None if keys.keys.len() == 1 => keys.keys.first().cloned(),
+            None => None,
+        };
+
+        let cached = self.keys.read().unwrap().get(&provider.name).cloned();
+        if let Some(key) = cached.as_deref().and_then(find) {
+            return Ok(key);
+        }
+
+        let keys: Arc<JwkSet> = Arc::new(self.fetch_json(&metadata.jwks_uri).await?);
+        self.keys.write().unwrap().insert(provider.name.clone(), keys.clone());
+        find(&keys).ok_or_else(|| FederationError::InvalidIdToken("Unknown signing key".to_string()))
+    }
+
+    pub async fn authorization_url(
+        &self,
+        provider: &ProviderConfig,
+        redirect_uri: &str,
+        pending: &PendingLogin,
+    ) -> Result<String, FederationError> {
+        let metadata = self.metadata(provider).await?;
+        let mut url = Url::parse(&metadata.authorization_endpoint)
+            .map_err(|err| FederationError::Upstream(format!("Invalid authorization_endpoint: {}", err)))?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &provider.client_id)
+            .append_pair("redirect_uri", redirect_uri)
+            .append_pair("scope", &provider.scopes.join(" "))
+            .append_pair("state", &pending.state)
+            .append_pair("nonce", &pending.nonce)
+            .append_pair("code_challenge", &pending.code_challenge)
+            .append_pair("code_challenge_method", "S256");
+        Ok(url.to_string())
+    }
+
+    // تبادل کد با ارائه‌دهنده و اعتبارسنجی امضا، صادرکننده، مخاطب، انقضا و nonce در ID token
+    pub async fn exchange_code(
+        &self,
+        provider: &ProviderConfig,
+        code: &str,
+        redirect_uri: &str,
+        login: &FederatedLoginState,
+    ) -> Result<ExternalIdentity, FederationError> {
+        let metadata = self.metadata(provider).await?;
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", redirect_uri),
+            ("client_id", &provider.client_id),
+            ("code_verifier", &login.code_verifier),
+        ];
+        if let Some(secret) = &provider.client_secret {
+            form.push(("client_secret", secret));
+        }
+
+        let response = self
+            .http
+            .post(&metadata.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .map_err(|err| FederationError::Upstream(err.to_string()))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(FederationError::Upstream(format!("Token request failed ({}): {}", status, body)));
+        }
+        let tokens: UpstreamTokenResponse = response
+            .json()
+            .await
+            .map_err(|err| FederationError::Upstream(err.to_string()))?;
+        let id_token = tokens
+            .id_token
+            .ok_or_else(|| FederationError::Upstream("Token response has no id_token".to_string()))?;
+
+        let header = decode_header(&id_token)?;
+        if !ALLOWED_ALGORITHMS.contains(&header.alg) {
+            return Err(FederationError::InvalidIdToken(format!("Unsupported algorithm {:?}", header.alg)));
+        }
+        let jwk = self.signing_key(provider, &metadata, header.kid.as_deref()).await?;
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&metadata.issuer]);
+        validation.set_audience(&[&provider.client_id]);
+        let claims = decode::<UpstreamClaims>(&id_token, &DecodingKey::from_jwk(&jwk)?, &validation)?.claims;
+
+        if claims.nonce.as_deref() != Some(login.nonce.as_str()) {
+            return Err(FederationError::InvalidIdToken("nonce does not match".to_string()));
+        }
+
+        let email_verified = matches!(&claims.email_verified, Some(Value::Bool(true)))
+            || matches!(&claims.email_verified, Some(Value::String(value)) if value == "true");
+        Ok(ExternalIdentity {
+            subject: claims.sub,
+            email: claims.email.filter(|_| email_verified),
+            preferred_username: claims.preferred_username,
+        })
+    }
+}
+
+// ثبت state ورود؛ link_user_id برای اتصال هویت به حساب کاربر واردشده است
+pub fn create_login_state(
+    conn: &mut PgConnection,
+    provider: &str,
+    link_user_id: Option<i32>,
+) -> QueryResult<PendingLogin> {
+    let state = random_string(STATE_LEN);
+    let nonce = random_string(NONCE_LEN);
+    let code_verifier = random_string(CODE_VERIFIER_LEN);
+    let code_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()));
+
+    diesel::insert_into(federated_login_states::table)
+        .values(&NewFederatedLoginState {
+            state_hash: hash_secret(&state),
+            provider: provider.to_string(),
+            nonce: nonce.clone(),
+            code_verifier,
+            link_user_id,
+            expires_at: (Utc::now() + Duration::seconds(LOGIN_STATE_TTL_SECS)).naive_utc(),
+        })
+        .execute(conn)?;
+
+    Ok(PendingLogin {
+        state,
+        nonce,
+        code_challenge,
+    })
+}
+
+// state یک‌بارمصرف است و باید متعلق به همین ارائه‌دهنده باشد
+pub fn consume_login_state(
+    conn: &mut PgConnection,
+    provider: &str,
+    state: &str,
+) -> Result<FederatedLoginState, FederationError> {
+    conn.transaction(|conn| {
+        let login = federated_login_states::table
+            .filter(federated_login_states::state_hash.eq(hash_secret(state)))
+            .for_update()
+            .select(FederatedLoginState::as_select())
+            .first(conn)
+            .optional()?;
+
+        let now = Utc::now().naive_utc();
+        let Some(login) = login.filter(|login| login.provider == provider) else {
+            return Err(FederationError::InvalidState("Invalid login state"));
+        };
+        if login.consumed_at.is_some() || login.expires_at <= now {
+            return Err(FederationError::InvalidState("Login state already used or expired"));
+        }
+
+        diesel::update(federated_login_states::table.find(login.id))
+            .set(federated_login_states::consumed_at.eq(Some(now)))
+            .execute(conn)?;
+        Ok(login)
+    })
+}
+
+fn available_username(conn: &mut PgConnection, base: &str) -> QueryResult<String> {
+    let base: String = base.chars().take(USERNAME_MAX_LEN).collect();
+    let mut candidate = base.clone();
+    let mut suffix = 1;
+    while diesel::select(diesel::dsl::exists(users::table.filter(users::username.eq(&candidate)))).get_result::<bool>(conn)? {
+        suffix += 1;
+        candidate = format!("{}_{}", base, suffix);
+    }
+    Ok(candidate)
+}
+
+// کاربر متصل به هویت بیرونی؛ در جریان اتصال هویت به کاربر link_user_id متصل می‌شود
+// و در غیر این صورت کاربر جدید با نقش‌های پیش‌فرض ارائه‌دهنده ساخته می‌شود
+pub fn resolve_user(
+    conn: &mut PgConnection,
+    provider: &ProviderConfig,
+    identity: &ExternalIdentity,
+    link_user_id: Option<i32>,
+) -> Result<User, FederationError> {
+    conn.transaction(|conn| {
+        let now = Utc::now().naive_utc();
+        let existing = user_identities::table
+            .filter(user_identities::provider.eq(&provider.name))
+            .filter(user_identities::subject.eq(&identity.subject))
+            .select(UserIdentity::as_select())
+            .first(conn)
+            .optional()?;
+
+        let user_id = match (existing, link_user_id) {
+            (Some(existing), Some(link_user_id)) if existing.user_id != link_user_id => {
+                return Err(FederationError::Conflict("This identity is linked to another account"));
+            }
+            (Some(existing), _) => {
+                diesel::update(user_identities::table.find(existing.id))
+                    .set((
+                        user_identities::last_login_at.eq(Some(now)),
+                        user_identities::email.eq(&identity.email),
+                    ))
+                    .execute(conn)?;
+                return Ok(users::table.find(existing.user_id).first::<User>(conn)?);
+            }
+            (None, Some(link_user_id)) => {
+                let already_linked = diesel::select(diesel::dsl::exists(
+                    user_identities::table
+                        .filter(user_identities::user_id.eq(link_user_id))
+                        .filter(user_identities::provider.eq(&provider.name)),
+                ))
+                .get_result::<bool>(conn)?;
+                if already_linked {
+                    return Err(FederationError::Conflict("Account is already linked to this provider"));
+                }
+                link_user_id
+            }
+            (None, None) => create_federated_user(conn, provider, identity)?,
+        };
+
+        diesel::insert_into(user_identities::table)
+            .values(&NewUserIdentity {
+                user_id,
+                provider: provider.name.clone(),
+                subject: identity.subject.clone(),
+                email: identity.email.clone(),
+                last_login_at: Some(now),
+            })
+            .execute(conn)?;
+
+        Ok(users::table.find(user_id).first::<User>(conn)?)
+    })
+}
+
+// ساخت کاربر در اولین ورود؛ ایمیل تکراری یعنی حساب محلی وجود دارد و باید از طریق اتصال هویت متصل شود
+fn create_federated_user(
+    conn: &mut PgConnection,
+    provider: &ProviderConfig,
+    identity: &ExternalIdentity,
+) -> Result<i32, FederationError> {
+    if let Some(email) = &identity.email {
+        let email_taken = diesel::select(diesel::dsl::exists(users::table.filter(users::email.eq(email))))
+            .get_result::<bool>(conn)?;
+        if email_taken {
+            return Err(FederationError::Conflict(
+                "An account with this email already exists; sign in and link the identity instead",
+            ));
+        }
+    }
+
+    let base = identity
+        .preferred_username
+        .clone()
+        .or_else(|| identity.email.as_ref().and_then(|email| email.split('@').next().map(str::to_string)))
+        .filter(|name| !name.trim().is_empty())
+        .unwrap_or_else(|| format!("{}_{}", provider.name, identity.subject));
+    let username = available_username(conn, &base)?;
+    let password = hash(random_string(UNUSABLE_PASSWORD_LEN), DEFAULT_COST)
+        .map_err(|err| FederationError::Internal(format!("Error hashing password: {}", err)))?;
+
+    let user = diesel::insert_into(users::table)
+        .values(&NewUser {
+            username,
+            password,
+            email: identity.email.clone(),
+        })
+        .get_result::<User>(conn)?;
+
+    for role_name in &provider.default_roles {
+        let role_id = roles::table
+            .filter(roles::name.eq(role_name))
+            .select(roles::id)
+            .first::<i32>(conn)
+            .optional()?;
+        let Some(role_id) = role_id else {
+            warn!("Default role '{}' of provider '{}' does not exist", role_name, provider.name);
+            continue;
+        };
+        diesel::insert_into(users_roles::table)
+            .values(&UserRole {
+                user_id: user.id,
+                role_id,
+                valid_from: None,
+                valid_until: None,
+                tenant_id: None,
+            })
+            .execute(conn)?;
+    }
+
+    info!("Created user {} from provider '{}'", user.id, provider.name);
+    Ok(user.id)
+}
@@ -0,0 +1,29 @@
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+use crate::schema::items;
+
+// همه‌ی کوئری‌ها به سازمان جاری محدودند؛ app.tenant_id نیز برای سیاست RLS جدول items تنظیم می‌شود
+pub fn in_tenant<T, F>(conn: &mut PgConnection, tenant: i32, query: F) -> QueryResult<T>
+where
+    F: FnOnce(&mut PgConnection) -> QueryResult<T>,
+{
+    conn.transaction(|conn| {
+        diesel::sql_query("SELECT set_config('app.tenant_id', $1, true)")
+            .bind::<Text, _>(tenant.to_string())
+            .execute(conn)?;
+        query(conn)
+    })
+}
+
+// مالک آیتم برای ویژگی `item.owner_id`؛ None برای آیتم ناموجود، بی‌مالک یا متعلق به سازمان دیگر
+pub fn item_owner(conn: &mut PgConnection, tenant: i32, item_id: i32) -> QueryResult<Option<i32>> {
+    in_tenant(conn, tenant, |conn| {
+        items::table
+            .find(item_id)
+            .filter(items::tenant_id.eq(tenant))
+            .select(items::owner_id)
+            .first::<Option<i32>>(conn)
+            .optional()
+            .map(Option::flatten)
+    })
+}
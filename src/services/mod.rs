@@ -1 +1,14 @@
-pub mod samfa;
\ No newline at end of file
+pub mod api_keys;
+pub mod assignment_sweeper;
+pub mod audit;
+pub mod audit_checkpoints;
+pub mod authorization;
+pub mod federation;
+pub mod items;
+pub mod oauth;
+pub mod oidc;
+pub mod permission_cache;
+pub mod pool_metrics;
+pub mod samfa;
+pub mod secrets;
+pub mod sessions;
\ No newline at end of file
@@ -0,0 +1,529 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use crate::middleware::jwt::{decode_token, encode_token};
+use crate::middleware::permissions::permission_matches;
+use crate::models::oauth::{
+    AuthorizationCode, GrantType, NewAuthorizationCode, NewRefreshToken, OAuthClient, RefreshToken, RevokedAccessToken,
+};
+use crate::models::user::{Claims, User};
+use crate::schema::{oauth_authorization_codes, oauth_clients, oauth_refresh_tokens, oauth_revoked_access_tokens, users};
+use crate::services::oidc::{OidcProvider, OIDC_SCOPES};
+use crate::services::secrets::{constant_time_eq, hash_secret, random_string};
+
+const ACCESS_TOKEN_TTL_SECS: i64 = 15 * 60;
+const REFRESH_TOKEN_TTL_DAYS: i64 = 30;
+const AUTHORIZATION_CODE_TTL_SECS: i64 = 10 * 60;
+
+const CODE_LEN: usize = 40;
+const REFRESH_TOKEN_LEN: usize = 48;
+const JTI_LEN: usize = 32;
+
+// خطاهای استاندارد OAuth 2.0 (RFC 6749 بخش 5.2)
+#[derive(Debug)]
+pub enum OAuthError {
+    InvalidRequest(String),
+    InvalidClient,
+    InvalidGrant(String),
+    UnauthorizedClient(String),
+    UnsupportedGrantType,
+    InvalidScope(String),
+    Database(diesel::result::Error),
+    Token(jsonwebtoken::errors::Error),
+}
+
+impl OAuthError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            OAuthError::InvalidRequest(_) => "invalid_request",
+            OAuthError::InvalidClient => "invalid_client",
+            OAuthError::InvalidGrant(_) => "invalid_grant",
+            OAuthError::UnauthorizedClient(_) => "unauthorized_client",
+            OAuthError::UnsupportedGrantType => "unsupported_grant_type",
+            OAuthError::InvalidScope(_) => "invalid_scope",
+            OAuthError::Database(_) | OAuthError::Token(_) => "server_error",
+        }
+    }
+}
+
+impl fmt::Display for OAuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OAuthError::InvalidRequest(reason)
+            | OAuthError::InvalidGrant(reason)
+            | OAuthError::UnauthorizedClient(reason)
+            | OAuthError::InvalidScope(reason) => write!(f, "{}", reason),
+            OAuthError::InvalidClient => write!(f, "Client authentication failed"),
+            OAuthError::UnsupportedGrantType => write!(f, "Unsupported grant_type"),
+            OAuthError::Database(err) => write!(f, "Query error: {}", err),
+            OAuthError::Token(err) => write!(f, "Error generating token: {}", err),
+        }
+    }
+}
+
+impl From<diesel::result::Error> for OAuthError {
+    fn from(err: diesel::result::Error) -> Self {
+        OAuthError::Database(err)
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for OAuthError {
+    fn from(err: jsonwebtoken::errors::Error) -> Self {
+        OAuthError::Token(err)
+    }
+}
+
+#[derive(Serialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: &'static str,
+    pub expires_in: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    pub scope: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+}
+
+// دسترسی اعطاشده به کلاینت از طرف یک کاربر؛ در کد مجوز ذخیره و هنگام صدور توکن استفاده می‌شود
+pub struct AuthorizationGrant {
+    pub user_id: i32,
+    pub tenant_id: Option<i32>,
+    pub scopes: Vec<String>,
+    pub nonce: Option<String>,
+}
+
+// پاسخ introspection (RFC 7662)؛ برای توکن نامعتبر فقط `active: false`
+#[derive(Serialize, Default)]
+pub struct Introspection {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenant_id: Option<i32>,
+}
+
+pub fn find_client(conn: &mut PgConnection, client_id: &str) -> QueryResult<Option<OAuthClient>> {
+    oauth_clients::table
+        .filter(oauth_clients::client_id.eq(client_id))
+        .select(OAuthClient::as_select())
+        .first(conn)
+        .optional()
+}
+
+// کلاینت محرمانه باید secret درست بفرستد؛ کلاینت عمومی نباید secret داشته باشد
+pub fn authenticate_client(
+    conn: &mut PgConnection,
+    client_id: &str,
+    client_secret: Option<&str>,
+) -> Result<OAuthClient, OAuthError> {
+    let client = find_client(conn, client_id)?.ok_or(OAuthError::InvalidClient)?;
+    let authenticated = match (&client.client_secret_hash, client_secret) {
+        (Some(expected), Some(secret)) => constant_time_eq(expected.as_bytes(), hash_secret(secret).as_bytes()),
+        (None, None) => true,
+        _ => false,
+    };
+    if authenticated {
+        Ok(client)
+    } else {
+        Err(OAuthError::InvalidClient)
+    }
+}
+
+// scopeهای درخواستی باید در scopeهای مجاز کلاینت بگنجند؛ بدون درخواست همه‌ی scopeهای کلاینت داده می‌شود
+// scopeهای OpenID Connect همیشه مجازند چون فقط اطلاعات هویتی خود کاربر را باز می‌کنند
+pub fn resolve_scopes(client: &OAuthClient, requested: Option<&str>) -> Result<Vec<String>, OAuthError> {
+    let Some(requested) = requested.filter(|scope| !scope.trim().is_empty()) else {
+        return Ok(client.scopes.clone());
+    };
+
+    let mut scopes = Vec::new();
+    for scope in requested.split_whitespace() {
+        let allowed = OIDC_SCOPES.contains(&scope) || client.scopes.iter().any(|allowed| permission_matches(allowed, scope));
+        if !allowed {
+            return Err(OAuthError::InvalidScope(format!("Scope '{}' is not allowed for this client", scope)));
+        }
+        if !scopes.iter().any(|existing| existing == scope) {
+            scopes.push(scope.to_string());
+        }
+    }
+    Ok(scopes)
+}
+
+// ساخت کد مجوز یک‌بارمصرف پس از رضایت کاربر
+pub fn create_authorization_code(
+    conn: &mut PgConnection,
+    client: &OAuthClient,
+    grant: AuthorizationGrant,
+    redirect_uri: &str,
+    code_challenge: Option<String>,
+) -> QueryResult<String> {
+    let code = random_string(CODE_LEN);
+    let new_code = NewAuthorizationCode {
+        code_hash: hash_secret(&code),
+        oauth_client_id: client.id,
+        user_id: grant.user_id,
+        tenant_id: grant.tenant_id,
+        redirect_uri: redirect_uri.to_string(),
+        scopes: grant.scopes,
+        code_challenge_method: code_challenge.as_ref().map(|_| "S256".to_string()),
+        code_challenge,
+        nonce: grant.nonce,
+        expires_at: (Utc::now() + Duration::seconds(AUTHORIZATION_CODE_TTL_SECS)).naive_utc(),
+    };
+    diesel::insert_into(oauth_authorization_codes::table)
+        .values(&new_code)
+        .execute(conn)?;
+    Ok(code)
+}
+
+// PKCE با روش S256 (RFC 7636)
+fn verify_pkce(challenge: &str, verifier: &str) -> bool {
+    (43..=128).contains(&verifier.len())
+        && constant_time_eq(URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes())).as_bytes(), challenge.as_bytes())
+}
+
+pub fn exchange_authorization_code(
+    conn: &mut PgConnection,
+    jwt_secret: &str,
+    client: &OAuthClient,
+    code: &str,
+    redirect_uri: Option<&str>,
+    code_verifier: Option<&str>,
+    oidc: &OidcProvider,
+) -> Result<TokenResponse, OAuthError> {
+    if !client.allows_grant(GrantType::AuthorizationCode) {
+        return Err(OAuthError::UnauthorizedClient("Client may not use authorization_code".to_string()));
+    }
+
+    conn.transaction(|conn| {
+        let stored = oauth_authorization_codes::table
+            .filter(oauth_authorization_codes::code_hash.eq(hash_secret(code)))
+            .for_update()
+            .select(AuthorizationCode::as_select())
+            .first(conn)
+            .optional()?;
+
+        let Some(stored) = stored.filter(|stored| stored.oauth_client_id == client.id) else {
+            return Err(OAuthError::InvalidGrant("Invalid authorization code".to_string()));
+        };
+        if stored.consumed_at.is_some() {
+            return Err(OAuthError::InvalidGrant("Authorization code already used".to_string()));
+        }
+        if stored.expires_at <= Utc::now().naive_utc() {
+            return Err(OAuthError::InvalidGrant("Authorization code expired".to_string()));
+        }
+        if redirect_uri != Some(stored.redirect_uri.as_str()) {
+            return Err(OAuthError::InvalidGrant("redirect_uri does not match".to_string()));
+        }
+        if let Some(challenge) = &stored.code_challenge {
+            let verifier = code_verifier.ok_or_else(|| OAuthError::InvalidRequest("code_verifier is required".to_string()))?;
+            if !verify_pkce(challenge, verifier) {
+                return Err(OAuthError::InvalidGrant("PKCE verification failed".to_string()));
+            }
+        }
+
+        diesel::update(oauth_authorization_codes::table.find(stored.id))
+            .set(oauth_authorization_codes::consumed_at.eq(Some(Utc::now().naive_utc())))
+            .execute(conn)?;
+
+        let with_refresh = client.allows_grant(GrantType::RefreshToken);
+        let grant = AuthorizationGrant {
+            user_id: stored.user_id,
+            tenant_id: stored.tenant_id,
+            scopes: stored.scopes,
+            nonce: stored.nonce,
+        };
+        issue_tokens(conn, jwt_secret, client, grant, with_refresh, Some(oidc))
+    })
+}
+
+// چرخش refresh token: توکن قبلی باطل و توکن جدید صادر می‌شود؛ scope فقط می‌تواند محدودتر شود
+pub fn refresh_access_token(
+    conn: &mut PgConnection,
+    jwt_secret: &str,
+    client: &OAuthClient,
+    refresh_token: &str,
+    requested_scope: Option<&str>,
+    oidc: &OidcProvider,
+) -> Result<TokenResponse, OAuthError> {
+    if !client.allows_grant(GrantType::RefreshToken) {
+        return Err(OAuthError::UnauthorizedClient("Client may not use refresh_token".to_string()));
+    }
+
+    conn.transaction(|conn| {
+        let stored = oauth_refresh_tokens::table
+            .filter(oauth_refresh_tokens::token_hash.eq(hash_secret(refresh_token)))
+            .for_update()
+            .select(RefreshToken::as_select())
+            .first(conn)
+            .optional()?;
+
+        let now = Utc::now().naive_utc();
+        let Some(stored) = stored.filter(|stored| {
+            stored.oauth_client_id == client.id && stored.revoked_at.is_none() && stored.expires_at > now
+        }) else {
+            return Err(OAuthError::InvalidGrant("Invalid refresh token".to_string()));
+        };
+
+        let scopes = match requested_scope.filter(|scope| !scope.trim().is_empty()) {
+            None => stored.scopes.clone(),
+            Some(requested) => {
+                let requested: Vec<String> = requested.split_whitespace().map(str::to_string).collect();
+                if let Some(extra) = requested.iter().find(|scope| !stored.scopes.contains(scope)) {
+                    return Err(OAuthError::InvalidScope(format!("Scope '{}' was not originally granted", extra)));
+                }
+                requested
+            }
+        };
+
+        diesel::update(oauth_refresh_tokens::table.find(stored.id))
+            .set(oauth_refresh_tokens::revoked_at.eq(Some(now)))
+            .execute(conn)?;
+
+        let grant = AuthorizationGrant {
+            user_id: stored.user_id,
+            tenant_id: stored.tenant_id,
+            scopes,
+            nonce: None,
+        };
+        issue_tokens(conn, jwt_secret, client, grant, true, Some(oidc))
+    })
+}
+
+// توکن ماشینی به نام کاربر سرویس کلاینت؛ refresh token صادر نمی‌شود
+pub fn client_credentials_token(
+    conn: &mut PgConnection,
+    jwt_secret: &str,
+    client: &OAuthClient,
+    requested_scope: Option<&str>,
+) -> Result<TokenResponse, OAuthError> {
+    if !client.is_confidential() || !client.allows_grant(GrantType::ClientCredentials) {
+        return Err(OAuthError::UnauthorizedClient("Client may not use client_credentials".to_string()));
+    }
+    let Some(service_user_id) = client.service_user_id else {
+        return Err(OAuthError::UnauthorizedClient("Client has no service user".to_string()));
+    };
+
+    let grant = AuthorizationGrant {
+        user_id: service_user_id,
+        tenant_id: None,
+        scopes: resolve_scopes(client, requested_scope)?,
+        nonce: None,
+    };
+    issue_tokens(conn, jwt_secret, client, grant, false, None)
+}
+
+// ID token فقط وقتی صادر می‌شود که کاربر واقعی scope `openid` را اعطا کرده باشد
+fn issue_tokens(
+    conn: &mut PgConnection,
+    jwt_secret: &str,
+    client: &OAuthClient,
+    grant: AuthorizationGrant,
+    with_refresh: bool,
+    oidc: Option<&OidcProvider>,
+) -> Result<TokenResponse, OAuthError> {
+    let AuthorizationGrant { user_id, tenant_id, scopes, nonce } = grant;
+    let now = Utc::now();
+    let scope = scopes.join(" ");
+    let claims = Claims {
+        sub: user_id,
+        exp: (now + Duration::seconds(ACCESS_TOKEN_TTL_SECS)).timestamp() as usize,
+        tenant: tenant_id,
+        jti: Some(random_string(JTI_LEN)),
+        client_id: Some(client.client_id.clone()),
+        scope: Some(scope.clone()),
+        ..Default::default()
+    };
+    let access_token = encode_token(jwt_secret, &claims)?;
+
+    let id_token = match oidc.filter(|_| scopes.iter().any(|scope| scope == "openid")) {
+        Some(oidc) => {
+            let user = users::table.find(user_id).first::<User>(conn)?;
+            Some(oidc.id_token(&user, &client.client_id, &scopes, nonce.as_deref())?)
+        }
+        None => None,
+    };
+
+    let refresh_token = if with_refresh {
+        let token = random_string(REFRESH_TOKEN_LEN);
+        diesel::insert_into(oauth_refresh_tokens::table)
+            .values(&NewRefreshToken {
+                token_hash: hash_secret(&token),
+                oauth_client_id: client.id,
+                user_id,
+                tenant_id,
+                scopes,
+                expires_at: (now + Duration::days(REFRESH_TOKEN_TTL_DAYS)).naive_utc(),
+            })
+            .execute(conn)?;
+        Some(token)
+    } else {
+        None
+    };
+
+    Ok(TokenResponse {
+        access_token,
+        token_type: "Bearer",
+        expires_in: ACCESS_TOKEN_TTL_SECS,
+        refresh_token,
+        scope,
+        id_token,
+    })
+}
+
+pub fn is_access_token_revoked(conn: &mut PgConnection, jti: &str) -> QueryResult<bool> {
+    diesel::select(diesel::dsl::exists(
+        oauth_revoked_access_tokens::table.filter(oauth_revoked_access_tokens::jti.eq(jti)),
+    ))
+    .get_result(conn)
+}
+
+pub fn introspect(conn: &mut PgConnection, jwt_secret: &str, token: &str) -> QueryResult<Introspection> {
+    if let Ok(claims) = decode_token(jwt_secret, token) {
+        if let Some(jti) = &claims.jti {
+            if is_access_token_revoked(conn, jti)? {
+                return Ok(Introspection::default());
+            }
+        }
+        return Ok(Introspection {
+            active: true,
+            token_type: Some("access_token"),
+            sub: Some(claims.sub.to_string()),
+            client_id: claims.client_id,
+            scope: claims.scope,
+            exp: Some(claims.exp as i64),
+            tenant_id: claims.tenant,
+        });
+    }
+
+    let now = Utc::now().naive_utc();
+    let stored = oauth_refresh_tokens::table
+        .inner_join(oauth_clients::table)
+        .filter(oauth_refresh_tokens::token_hash.eq(hash_secret(token)))
+        .filter(oauth_refresh_tokens::revoked_at.is_null())
+        .filter(oauth_refresh_tokens::expires_at.gt(now))
+        .select((RefreshToken::as_select(), oauth_clients::client_id))
+        .first::<(RefreshToken, String)>(conn)
+        .optional()?;
+
+    Ok(match stored {
+        Some((refresh, client_id)) => Introspection {
+            active: true,
+            token_type: Some("refresh_token"),
+            sub: Some(refresh.user_id.to_string()),
+            client_id: Some(client_id),
+            scope: Some(refresh.scopes.join(" ")),
+            exp: Some(refresh.expires_at.and_utc().timestamp()),
+            tenant_id: refresh.tenant_id,
+        },
+        None => Introspection::default(),
+    })
+}
+
+// ابطال (RFC 7009): توکن ناشناخته یا متعلق به کلاینت دیگر بی‌صدا نادیده گرفته می‌شود
+pub fn revoke(conn: &mut PgConnection, jwt_secret: &str, client: &OAuthClient, token: &str) -> QueryResult<()> {
+    let now = Utc::now().naive_utc();
+
+    if let Ok(claims) = decode_token(jwt_secret, token) {
+        if let (Some(jti), Some(client_id)) = (claims.jti, claims.client_id) {
+            if client_id == client.client_id {
+                // ردیف‌های منقضی‌شده دیگر لازم نیستند چون خود توکن دیگر پذیرفته نمی‌شود
+                diesel::delete(oauth_revoked_access_tokens::table.filter(oauth_revoked_access_tokens::expires_at.le(now)))
+                    .execute(conn)?;
+                diesel::insert_into(oauth_revoked_access_tokens::table)
+                    .values(&RevokedAccessToken {
+                        jti,
+                        expires_at: timestamp_to_naive(claims.exp as i64),
+                    })
+                    .on_conflict_do_nothing()
+                    .execute(conn)?;
+            }
+        }
+        return Ok(());
+    }
+
+    diesel::update(oauth_refresh_tokens::table)
+        .filter(oauth_refresh_tokens::token_hash.eq(hash_secret(token)))
+        .filter(oauth_refresh_tokens::oauth_client_id.eq(client.id))
+        .filter(oauth_refresh_tokens::revoked_at.is_null())
+        .set(oauth_refresh_tokens::revoked_at.eq(Some(now)))
+        .execute(conn)?;
+    Ok(())
+}
+
+fn timestamp_to_naive(timestamp: i64) -> NaiveDateTime {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .map(|at| at.naive_utc())
+        .unwrap_or_else(|| Utc::now().naive_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+    use chrono::Utc;
+    use sha2::{Digest, Sha256};
+    use super::{resolve_scopes, verify_pkce, OAuthError};
+    use crate::models::oauth::OAuthClient;
+
+    fn client(scopes: &[&str]) -> OAuthClient {
+        OAuthClient {
+            id: 1,
+            client_id: "client".to_string(),
+            client_secret_hash: None,
+            name: "client".to_string(),
+            redirect_uris: vec![],
+            grant_types: vec!["authorization_code".to_string()],
+            scopes: scopes.iter().map(|scope| scope.to_string()).collect(),
+            service_user_id: None,
+            created_at: Utc::now().naive_utc(),
+        }
+    }
+
+    #[test]
+    fn pkce_accepts_only_the_matching_s256_verifier() {
+        let verifier = "dBjftJeZ4CVP-mJ92K6jL3l6Q4oFtfSfpKLIZ6lhNL4";
+        let challenge = "vroKj_O3_cH3q5Fg8Xak-720j7L_jFLfJxPLs0J-oBY";
+        assert!(verify_pkce(challenge, verifier));
+        assert!(!verify_pkce(challenge, "dBjftJeZ4CVP-mJ92K6jL3l6Q4oFtfSfpKLIZ6lhNL5"));
+        // روش plain پشتیبانی نمی‌شود: خود verifier به‌عنوان challenge پذیرفته نیست
+        assert!(!verify_pkce(verifier, verifier));
+    }
+
+    #[test]
+    fn pkce_verifier_length_is_bounded() {
+        let short = "a".repeat(42);
+        let long = "a".repeat(129);
+        let encode = |verifier: &str| URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+        assert!(!verify_pkce(&encode(&short), &short));
+        assert!(!verify_pkce(&encode(&long), &long));
+        assert!(verify_pkce(&encode(&"a".repeat(43)), &"a".repeat(43)));
+        assert!(verify_pkce(&encode(&"a".repeat(128)), &"a".repeat(128)));
+    }
+
+    #[test]
+    fn requested_scopes_must_fit_the_client() {
+        let client = client(&["items.*", "users.read"]);
+
+        assert_eq!(resolve_scopes(&client, None).unwrap(), ["items.*", "users.read"]);
+        assert_eq!(resolve_scopes(&client, Some("  ")).unwrap(), ["items.*", "users.read"]);
+        assert_eq!(resolve_scopes(&client, Some("items.read items.read openid")).unwrap(), ["items.read", "openid"]);
+        assert!(matches!(resolve_scopes(&client, Some("users.write")), Err(OAuthError::InvalidScope(_))));
+        // scope گسترده‌تر از الگوی کلاینت پذیرفته نمی‌شود
+        assert!(matches!(resolve_scopes(&client, Some("*")), Err(OAuthError::InvalidScope(_))));
+        assert!(matches!(resolve_scopes(&client, Some("users.*")), Err(OAuthError::InvalidScope(_))));
+    }
+}
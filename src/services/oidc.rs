@@ -0,0 +1,177 @@
+use anyhow::Result;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{Duration, Utc};
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use log::warn;
+use rsa::pkcs1::DecodeRsaPrivateKey;
+use rsa::pkcs8::{DecodePrivateKey, EncodePrivateKey, LineEnding};
+use rsa::traits::PublicKeyParts;
+use rsa::RsaPrivateKey;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+use crate::models::user::User;
+
+// scopeهای استاندارد OpenID Connect؛ دسترسی اعطا نمی‌کنند و برای همه‌ی کلاینت‌ها مجازند
+pub const OIDC_SCOPES: [&str; 3] = ["openid", "profile", "email"];
+
+const ID_TOKEN_TTL_SECS: i64 = 15 * 60;
+const EPHEMERAL_KEY_BITS: usize = 2048;
+
+#[derive(Serialize)]
+struct IdTokenClaims<'a> {
+    iss: &'a str,
+    sub: String,
+    aud: &'a str,
+    exp: i64,
+    iat: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nonce: Option<&'a str>,
+    #[serde(flatten)]
+    profile: UserInfo,
+}
+
+// claimهای کاربر بر اساس scopeها؛ هم در ID token و هم در پاسخ /userinfo
+#[derive(Serialize)]
+pub struct UserInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preferred_username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+}
+
+impl UserInfo {
+    // scopes = None یعنی توکن ورود مستقیم بدون محدودیت scope
+    pub fn for_user(user: &User, scopes: Option<&[String]>) -> Self {
+        let granted = |scope: &str| scopes.is_none_or(|scopes| scopes.iter().any(|s| s == scope));
+        UserInfo {
+            sub: Some(user.id.to_string()),
+            preferred_username: granted("profile").then(|| user.username.clone()),
+            email: if granted("email") { user.email.clone() } else { None },
+        }
+    }
+}
+
+// ارائه‌دهنده‌ی OpenID Connect: ID tokenها با RS256 امضا و کلید عمومی در JWKS منتشر می‌شود
+pub struct OidcProvider {
+    issuer: String,
+    kid: String,
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    jwk: Value,
+}
+
+impl OidcProvider {
+    // کلید خصوصی RSA با قالب PKCS#8 یا PKCS#1؛ بدون فایل، کلید موقت ساخته می‌شود که با راه‌اندازی مجدد عوض می‌شود
+    pub fn load(issuer: String, key_file: Option<&Path>) -> Result<Self> {
+        let private_key = match key_file {
+            Some(path) => {
+                let pem = fs::read_to_string(path)?;
+                RsaPrivateKey::from_pkcs8_pem(&pem).or_else(|_| RsaPrivateKey::from_pkcs1_pem(&pem))?
+            }
+            None => {
+                warn!("OIDC_SIGNING_KEY_FILE is not set; ID tokens are signed with an ephemeral key");
+                RsaPrivateKey::new(&mut rand::thread_rng(), EPHEMERAL_KEY_BITS)?
+            }
+        };
+
+        let encoding_key = EncodingKey::from_rsa_pem(private_key.to_pkcs8_pem(LineEnding::LF)?.as_bytes())?;
+        let modulus = private_key.n().to_bytes_be();
+        let kid = hex::encode(&Sha256::digest(&modulus)[..8]);
+        let n = URL_SAFE_NO_PAD.encode(&modulus);
+        let e = URL_SAFE_NO_PAD.encode(private_key.e().to_bytes_be());
+        let decoding_key = DecodingKey::from_rsa_components(&n, &e)?;
+        let jwk = json!({
+            "kty": "RSA",
+            "use": "sig",
+            "alg": "RS256",
+            "kid": kid,
+            "n": n,
+            "e": e,
+        });
+
+        Ok(OidcProvider {
+            issuer: issuer.trim_end_matches('/').to_string(),
+            kid,
+            encoding_key,
+            decoding_key,
+            jwk,
+        })
+    }
+
+    // آدرس عمومی سرویس؛ پایه‌ی endpointهای منتشرشده و آدرس‌های بازگشت
+    pub fn issuer(&self) -> &str {
+        &self.issuer
+    }
+
+    pub fn jwks(&self) -> Value {
+        json!({ "keys": [self.jwk] })
+    }
+
+    pub fn discovery(&self) -> Value {
+        let endpoint = |path: &str| format!("{}{}", self.issuer, path);
+        json!({
+            "issuer": self.issuer,
+            "authorization_endpoint": endpoint("/oauth/authorize"),
+            "token_endpoint": endpoint("/oauth/token"),
+            "userinfo_endpoint": endpoint("/userinfo"),
+            "jwks_uri": endpoint("/.well-known/jwks.json"),
+            "introspection_endpoint": endpoint("/oauth/introspect"),
+            "revocation_endpoint": endpoint("/oauth/revoke"),
+            "response_types_supported": ["code"],
+            "grant_types_supported": ["authorization_code", "client_credentials", "refresh_token"],
+            "subject_types_supported": ["public"],
+            "id_token_signing_alg_values_supported": ["RS256"],
+            "scopes_supported": OIDC_SCOPES,
+            "token_endpoint_auth_methods_supported": ["client_secret_basic", "client_secret_post", "none"],
+            "code_challenge_methods_supported": ["S256"],
+            "claims_supported": ["iss", "sub", "aud", "exp", "iat", "nonce", "preferred_username", "email"],
+        })
+    }
+
+    pub fn id_token(
+        &self,
+        user: &User,
+        client_id: &str,
+        scopes: &[String],
+        nonce: Option<&str>,
+    ) -> Result<String, jsonwebtoken::errors::Error> {
+        let now = Utc::now();
+        let claims = IdTokenClaims {
+            iss: &self.issuer,
+            sub: user.id.to_string(),
+            aud: client_id,
+            exp: (now + Duration::seconds(ID_TOKEN_TTL_SECS)).timestamp(),
+            iat: now.timestamp(),
+            nonce,
+            profile: UserInfo {
+                sub: None,
+                ..UserInfo::for_user(user, Some(scopes))
+            },
+        };
+
+        self.sign(&claims)
+    }
+
+    // امضای claims با کلید سرویس (RS256)؛ برای ID tokenها و checkpointهای سابقه‌ی امنیتی
+    pub fn sign<T: Serialize>(&self, claims: &T) -> Result<String, jsonwebtoken::errors::Error> {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(self.kid.clone());
+        encode(&header, claims, &self.encoding_key)
+    }
+
+    // بررسی امضای توکنی که با همین کلید امضا شده است؛ انقضا بررسی نمی‌شود
+    pub fn verify<T: DeserializeOwned>(&self, token: &str) -> Result<T, jsonwebtoken::errors::Error> {
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.validate_exp = false;
+        validation.required_spec_claims.clear();
+        validation.set_issuer(&[&self.issuer]);
+        decode::<T>(token, &self.decoding_key, &validation).map(|data| data.claims)
+    }
+}
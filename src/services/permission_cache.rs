@@ -0,0 +1,118 @@
+use actix_web::web;
+use anyhow::Result;
+use diesel::prelude::*;
+use log::{error, info};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::middleware::permissions::UserAccess;
+
+// کانال NOTIFY که تریگرهای users_roles / role_permissions / role_parents / permissions روی آن اعلان می‌فرستند
+pub const PERMISSION_CHANGES_CHANNEL: &str = "permission_changes";
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+struct CachedAccess {
+    loaded_at: Instant,
+    access: UserAccess,
+}
+
+struct CacheState {
+    // با هر ابطال افزایش می‌یابد تا نتیجه‌ی بارگذاری‌ای که پیش از ابطال شروع شده ذخیره نشود
+    generation: u64,
+    // کلید: (شناسه‌ی کاربر، سازمان انتخاب‌شده)
+    users: HashMap<(i32, Option<i32>), CachedAccess>,
+}
+
+// کش نقش‌ها و دسترسی‌های هر کاربر با TTL
+pub struct PermissionCache {
+    ttl: Duration,
+    state: Mutex<CacheState>,
+}
+
+impl PermissionCache {
+    pub fn new(ttl: Duration) -> Self {
+        PermissionCache {
+            ttl,
+            state: Mutex::new(CacheState {
+                generation: 0,
+                users: HashMap::new(),
+            }),
+        }
+    }
+
+    pub fn get(&self, user_id: i32, tenant: Option<i32>) -> Option<UserAccess> {
+        let state = self.state.lock().unwrap();
+        state
+            .users
+            .get(&(user_id, tenant))
+            .filter(|cached| cached.loaded_at.elapsed() < self.ttl)
+            .map(|cached| cached.access.clone())
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.state.lock().unwrap().generation
+    }
+
+    // generation باید پیش از شروع بارگذاری از دیتابیس گرفته شده باشد
+    pub fn insert(&self, user_id: i32, tenant: Option<i32>, generation: u64, access: UserAccess) {
+        let mut state = self.state.lock().unwrap();
+        if state.generation == generation {
+            state.users.insert(
+                (user_id, tenant),
+                CachedAccess {
+                    loaded_at: Instant::now(),
+                    access,
+                },
+            );
+        }
+    }
+
+    // همه‌ی سازمان‌های کاربر باطل می‌شوند
+    pub fn invalidate_user(&self, user_id: i32) {
+        let mut state = self.state.lock().unwrap();
+        state.generation += 1;
+        state.users.retain(|(cached_user, _), _| *cached_user != user_id);
+    }
+
+    pub fn invalidate_all(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.generation += 1;
+        state.users.clear();
+    }
+}
+
+// شنونده‌ی LISTEN/NOTIFY روی یک اتصال اختصاصی (خارج از pool) در thread جداگانه
+pub fn spawn_invalidation_listener(database_url: String, cache: web::Data<PermissionCache>) {
+    thread::spawn(move || loop {
+        if let Err(err) = listen_for_changes(&database_url, &cache) {
+            error!("Permission change listener failed: {}", err);
+        }
+        // در زمان قطع اتصال ممکن است اعلان‌هایی از دست رفته باشد
+        cache.invalidate_all();
+        thread::sleep(RECONNECT_DELAY);
+    });
+}
+
+fn listen_for_changes(database_url: &str, cache: &PermissionCache) -> Result<()> {
+    let mut conn = PgConnection::establish(database_url)?;
+    diesel::sql_query(format!("LISTEN {}", PERMISSION_CHANGES_CHANNEL)).execute(&mut conn)?;
+    info!("Listening for permission changes on {}", PERMISSION_CHANGES_CHANNEL);
+
+    // هر چیزی که پیش از LISTEN کش شده ممکن است قدیمی باشد
+    cache.invalidate_all();
+
+    loop {
+        for notification in conn.notifications_iter() {
+            let notification = notification?;
+            // payload شناسه‌ی کاربر است یا `*` برای تغییراتی که ممکن است روی همه اثر بگذارد
+            match notification.payload.parse::<i32>() {
+                Ok(user_id) => cache.invalidate_user(user_id),
+                Err(_) => cache.invalidate_all(),
+            }
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
@@ -0,0 +1,98 @@
+use r2d2::event::{AcquireEvent, CheckoutEvent, ReleaseEvent, TimeoutEvent};
+use r2d2::{HandleEvent, State};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+// شمارنده‌های رویدادهای pool اتصال پایگاه داده؛ وضعیت لحظه‌ای از `pool.state()` خوانده می‌شود
+#[derive(Debug, Default)]
+pub struct PoolMetrics {
+    connections_opened: AtomicU64,
+    connections_closed: AtomicU64,
+    checkouts: AtomicU64,
+    checkout_timeouts: AtomicU64,
+    checkout_wait_micros: AtomicU64,
+}
+
+impl PoolMetrics {
+    // پاسخ `/metrics` در قالب متنی Prometheus
+    pub fn render(&self, state: State, max_size: u32) -> String {
+        let counter = |value: &AtomicU64| value.load(Ordering::Relaxed);
+        let gauges = [
+            ("db_pool_max_size", "Maximum number of connections", max_size as u64),
+            ("db_pool_connections", "Connections currently managed by the pool", state.connections as u64),
+            ("db_pool_idle_connections", "Idle connections", state.idle_connections as u64),
+            (
+                "db_pool_in_use_connections",
+                "Connections checked out by requests",
+                state.connections.saturating_sub(state.idle_connections) as u64,
+            ),
+        ];
+        let counters = [
+            ("db_pool_connections_opened_total", "Connections opened", counter(&self.connections_opened)),
+            ("db_pool_connections_closed_total", "Connections closed", counter(&self.connections_closed)),
+            ("db_pool_checkouts_total", "Successful connection checkouts", counter(&self.checkouts)),
+            ("db_pool_checkout_timeouts_total", "Checkouts that timed out", counter(&self.checkout_timeouts)),
+        ];
+
+        let mut out = String::new();
+        for (name, help, value) in gauges {
+            let _ = writeln!(out, "# HELP {} {}\n# TYPE {} gauge\n{} {}", name, help, name, name, value);
+        }
+        for (name, help, value) in counters {
+            let _ = writeln!(out, "# HELP {} {}\n# TYPE {} counter\n{} {}", name, help, name, name, value);
+        }
+        let wait_seconds = counter(&self.checkout_wait_micros) as f64 / 1_000_000.0;
+        let _ = writeln!(
+            out,
+            "# HELP db_pool_checkout_wait_seconds_total Time spent waiting for a connection\n\
+             # TYPE db_pool_checkout_wait_seconds_total counter\n\
+             db_pool_checkout_wait_seconds_total {}",
+            wait_seconds
+        );
+        out
+    }
+}
+
+// اتصال شمارنده‌ها به رویدادهای r2d2؛ `event_handler` مالکیت Box را می‌گیرد
+#[derive(Debug)]
+pub struct PoolEventRecorder(pub Arc<PoolMetrics>);
+
+impl HandleEvent for PoolEventRecorder {
+    fn handle_acquire(&self, _event: AcquireEvent) {
+        self.0.connections_opened.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn handle_release(&self, _event: ReleaseEvent) {
+        self.0.connections_closed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn handle_checkout(&self, event: CheckoutEvent) {
+        self.0.checkouts.fetch_add(1, Ordering::Relaxed);
+        self.0.checkout_wait_micros.fetch_add(event.duration().as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn handle_timeout(&self, event: TimeoutEvent) {
+        self.0.checkout_timeouts.fetch_add(1, Ordering::Relaxed);
+        self.0.checkout_wait_micros.fetch_add(event.timeout().as_micros() as u64, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use super::{PoolEventRecorder, PoolMetrics};
+    use crate::test_support::unavailable_pool_with_events;
+
+    #[test]
+    fn checkout_timeouts_are_counted() {
+        let metrics = Arc::new(PoolMetrics::default());
+        let pool = unavailable_pool_with_events(Box::new(PoolEventRecorder(metrics.clone())));
+
+        assert!(pool.get().is_err());
+        let rendered = metrics.render(pool.state(), pool.max_size());
+        assert!(rendered.contains("db_pool_checkout_timeouts_total 1"), "{}", rendered);
+        assert!(rendered.contains("db_pool_checkouts_total 0"), "{}", rendered);
+        assert!(rendered.contains("db_pool_max_size 1"), "{}", rendered);
+    }
+}
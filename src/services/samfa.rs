@@ -2,6 +2,7 @@ use reqwest::{header::HeaderValue, Client};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use anyhow::{Result, Context};
+use crate::config::SamfaSettings;
 
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -13,15 +14,15 @@ pub struct ApiResponse {
 pub struct ApiClient {
     client: Client,
     base_url: String,
-    token:String
+    token: Option<String>,
 }
 
 impl ApiClient {
-    pub fn new() -> Self {
+    pub fn new(settings: &SamfaSettings) -> Self {
         Self {
             client: Client::new(),
-            base_url: "https://seller.samfaa.ir/api/v1/".to_string(),
-            token: "token".to_string(),
+            base_url: settings.base_url.clone(),
+            token: settings.token.clone(),
         }
     }
 
@@ -31,9 +32,12 @@ impl ApiClient {
         let mut headers = reqwest::header::HeaderMap::new();
         headers.insert(reqwest::header::ACCEPT, HeaderValue::from_static("application/json"));
 
-        let auth_header = HeaderValue::from_str(&self.token)
-            .context("Invalid Authorization Token")?;
-        headers.insert(reqwest::header::AUTHORIZATION, auth_header);
+        // بدون توکن درخواست بدون سرآیند Authorization فرستاده می‌شود
+        if let Some(token) = &self.token {
+            let auth_header = HeaderValue::from_str(token)
+                .context("Invalid Authorization Token")?;
+            headers.insert(reqwest::header::AUTHORIZATION, auth_header);
+        }
 
         let response = self.client.get(url).headers(headers).send().await
             .context("Failed to send request")?;
@@ -0,0 +1,18 @@
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+
+// رشته‌ی تصادفی حرفی-عددی برای کلیدها، کدها و توکن‌های مات
+pub fn random_string(len: usize) -> String {
+    rand::thread_rng().sample_iter(&Alphanumeric).take(len).map(char::from).collect()
+}
+
+// مقادیر محرمانه تصادفی و طولانی‌اند، پس SHA-256 بدون salt کافی است و بررسی هر درخواست سریع می‌ماند
+pub fn hash_secret(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+// مقایسه با زمان ثابت تا از روی زمان پاسخ نتوان هش را حدس زد
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
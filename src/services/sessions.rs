@@ -0,0 +1,91 @@
+use actix_web::http::header;
+use actix_web::HttpRequest;
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use crate::models::session::{NewSession, Session};
+use crate::schema::sessions::dsl::*;
+
+// عمر نشست و توکن ورود آن
+const SESSION_TTL_DAYS: i64 = 1;
+// نشست جعل هویت کوتاه‌عمر است تا دسترسی کارمند پشتیبانی خودبه‌خود تمام شود
+const IMPERSONATION_TTL_MINUTES: i64 = 60;
+// فاصله‌ی حداقل بین دو به‌روزرسانی last_seen_at برای یک نشست (ثانیه)
+const LAST_SEEN_RESOLUTION_SECS: i64 = 60;
+const USER_AGENT_MAX_LEN: usize = 512;
+
+// دستگاه و IP درخواست ورود؛ جدا از HttpRequest تا بتوان آن را به db::run فرستاد
+pub struct SessionOrigin {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+impl SessionOrigin {
+    pub fn from_request(req: &HttpRequest) -> Self {
+        SessionOrigin {
+            user_agent: req
+                .headers()
+                .get(header::USER_AGENT)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.chars().take(USER_AGENT_MAX_LEN).collect()),
+            ip_address: req.peer_addr().map(|addr| addr.ip().to_string()),
+        }
+    }
+}
+
+pub fn create_session(conn: &mut PgConnection, owner: i32, origin: SessionOrigin) -> QueryResult<Session> {
+    diesel::insert_into(sessions)
+        .values(&NewSession {
+            user_id: owner,
+            user_agent: origin.user_agent,
+            ip_address: origin.ip_address,
+            expires_at: (Utc::now() + Duration::days(SESSION_TTL_DAYS)).naive_utc(),
+            impersonator_id: None,
+            impersonation_reason: None,
+        })
+        .returning(Session::as_returning())
+        .get_result(conn)
+}
+
+// نشست کاربر هدف که توسط actor ساخته شده است؛ توکن آن claim `act` دارد
+pub fn create_impersonation_session(
+    conn: &mut PgConnection,
+    target: i32,
+    actor: i32,
+    reason: &str,
+    origin: SessionOrigin,
+) -> QueryResult<Session> {
+    diesel::insert_into(sessions)
+        .values(&NewSession {
+            user_id: target,
+            user_agent: origin.user_agent,
+            ip_address: origin.ip_address,
+            expires_at: (Utc::now() + Duration::minutes(IMPERSONATION_TTL_MINUTES)).naive_utc(),
+            impersonator_id: Some(actor),
+            impersonation_reason: Some(reason.to_string()),
+        })
+        .returning(Session::as_returning())
+        .get_result(conn)
+}
+
+// نشست باید متعلق به همان کاربر (و همان actor در جعل هویت)، ابطال‌نشده و منقضی‌نشده باشد؛ زمان آخرین فعالیت هم ثبت می‌شود
+pub fn touch_session(conn: &mut PgConnection, session_id: i32, owner: i32, actor: Option<i32>) -> QueryResult<bool> {
+    let now = Utc::now().naive_utc();
+    let active = diesel::select(diesel::dsl::exists(
+        sessions
+            .find(session_id)
+            .filter(user_id.eq(owner))
+            .filter(impersonator_id.is_not_distinct_from(actor))
+            .filter(revoked_at.is_null())
+            .filter(expires_at.gt(now)),
+    ))
+    .get_result::<bool>(conn)?;
+
+    if active {
+        let stale_before = now - Duration::seconds(LAST_SEEN_RESOLUTION_SECS);
+        diesel::update(sessions.find(session_id))
+            .filter(last_seen_at.lt(stale_before))
+            .set(last_seen_at.eq(now))
+            .execute(conn)?;
+    }
+    Ok(active)
+}
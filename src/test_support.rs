@@ -0,0 +1,135 @@
+use actix_web::body::{to_bytes, MessageBody};
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::{header, StatusCode};
+use actix_web::{web, Error};
+use diesel::prelude::*;
+use diesel::r2d2::{self, ConnectionManager, CustomizeConnection};
+use serde_json::Value;
+use std::env;
+use std::time::Duration;
+use crate::config::{DbPool, Settings};
+use crate::errors::PROBLEM_JSON;
+use crate::middleware::jwt::encode_token;
+use crate::middleware::permissions::{PermissionSet, UserAccess};
+use crate::models::user::{Claims, PermissionEffect};
+use crate::services::permission_cache::PermissionCache;
+
+pub const TEST_JWT_SECRET: &str = "test-secret";
+
+// تنظیمات آزمون‌ها؛ فقط کلید امضای توکن برای middleware لازم است
+pub fn test_settings() -> Settings {
+    Settings {
+        jwt_secret: TEST_JWT_SECRET.to_string(),
+        ..Settings::default()
+    }
+}
+
+// pool بدون پایگاه داده‌ی در دسترس؛ هر pool.get() پس از timeout کوتاه خطا می‌دهد
+pub fn unavailable_pool() -> DbPool {
+    unavailable_pool_with_events(Box::new(r2d2::NopEventHandler))
+}
+
+pub fn unavailable_pool_with_events(event_handler: Box<dyn r2d2::HandleEvent>) -> DbPool {
+    let manager = ConnectionManager::<PgConnection>::new("postgres://nobody@127.0.0.1:1/unavailable");
+    r2d2::Pool::builder()
+        .max_size(1)
+        .min_idle(Some(0))
+        .connection_timeout(Duration::from_millis(200))
+        .event_handler(event_handler)
+        .build_unchecked(manager)
+}
+
+// هر اتصال در حالت فقط‌خواندنی؛ هر نوشتن با خطای پایگاه داده رد می‌شود
+#[derive(Debug)]
+struct ReadOnly;
+
+impl CustomizeConnection<PgConnection, r2d2::Error> for ReadOnly {
+    fn on_acquire(&self, conn: &mut PgConnection) -> Result<(), r2d2::Error> {
+        diesel::sql_query("SET default_transaction_read_only = on")
+            .execute(conn)
+            .map(|_| ())
+            .map_err(r2d2::Error::QueryError)
+    }
+}
+
+// pool فقط‌خواندنی روی TEST_DATABASE_URL؛ آزمون‌های استفاده‌کننده با `#[ignore = "needs TEST_DATABASE_URL"]` علامت می‌خورند
+// و با `cargo test -- --include-ignored` اجرا می‌شوند
+pub fn read_only_pool() -> DbPool {
+    let database_url = env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL must be set to run database tests");
+    r2d2::Pool::builder()
+        .max_size(1)
+        .connection_customizer(Box::new(ReadOnly))
+        .build(ConnectionManager::<PgConnection>::new(database_url))
+        .expect("TEST_DATABASE_URL is not reachable")
+}
+
+// pool قابل نوشتن روی TEST_DATABASE_URL برای آزمون‌هایی که داده می‌سازند؛ داده‌ها با نام یکتا ساخته می‌شوند و باقی می‌مانند
+pub fn database_pool() -> DbPool {
+    let database_url = env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL must be set to run database tests");
+    r2d2::Pool::builder()
+        .max_size(2)
+        .build(ConnectionManager::<PgConnection>::new(database_url))
+        .expect("TEST_DATABASE_URL is not reachable")
+}
+
+// جدول‌ها روی TEST_DATABASE_URL دیده نمی‌شوند؛ حتی کوئری‌های خواندنی با خطای پایگاه داده (نه NotFound) شکست می‌خورند
+#[derive(Debug)]
+struct MissingTables;
+
+impl CustomizeConnection<PgConnection, r2d2::Error> for MissingTables {
+    fn on_acquire(&self, conn: &mut PgConnection) -> Result<(), r2d2::Error> {
+        diesel::sql_query("SET search_path TO missing_schema")
+            .execute(conn)
+            .map(|_| ())
+            .map_err(r2d2::Error::QueryError)
+    }
+}
+
+pub fn missing_tables_pool() -> DbPool {
+    let database_url = env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL must be set to run database tests");
+    r2d2::Pool::builder()
+        .max_size(1)
+        .connection_customizer(Box::new(MissingTables))
+        .build(ConnectionManager::<PgConnection>::new(database_url))
+        .expect("TEST_DATABASE_URL is not reachable")
+}
+
+// توکن ورود آزمایشی؛ بدون sid تا middleware سراغ جدول نشست‌ها نرود
+pub fn token_for(claims: Claims) -> String {
+    encode_token(TEST_JWT_SECRET, &Claims {
+        exp: chrono::Utc::now().timestamp() as usize + 60 * 60,
+        ..claims
+    })
+    .expect("Error encoding test token")
+}
+
+// کش دسترسی از پیش پرشده برای کاربر؛ extractor کاربر و RbacMiddleware بدون پایگاه داده کار می‌کنند
+pub fn cached_access(user_id: i32, permissions: &[&str]) -> web::Data<PermissionCache> {
+    let cache = PermissionCache::new(Duration::from_secs(60));
+    let access = UserAccess {
+        permissions: PermissionSet::new(permissions.iter().map(|name| (name.to_string(), PermissionEffect::Allow))),
+        ..Default::default()
+    };
+    cache.insert(user_id, None, cache.generation(), access);
+    web::Data::new(cache)
+}
+
+// پاسخ خطای درخواست آزمایشی: وضعیت و `code` بدنه‌ی problem+json
+// خطای برگشتی از middleware همان‌طور که سرور انجام می‌دهد به پاسخ تبدیل می‌شود
+pub async fn problem<S, R, B>(app: &S, req: R) -> (StatusCode, String)
+where
+    S: Service<R, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody + 'static,
+{
+    let response = match app.call(req).await {
+        Ok(response) => response.into_parts().1.map_into_boxed_body(),
+        Err(err) => err.error_response(),
+    };
+    let status = response.status();
+    let content_type = response.headers().get(header::CONTENT_TYPE).and_then(|value| value.to_str().ok()).map(str::to_string);
+    assert_eq!(content_type.as_deref(), Some(PROBLEM_JSON), "status {}", status);
+
+    let body = to_bytes(response.into_body()).await.expect("Error reading response body");
+    let body: Value = serde_json::from_slice(&body).expect("Invalid problem+json body");
+    (status, body["code"].as_str().unwrap_or_default().to_string())
+}
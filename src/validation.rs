@@ -0,0 +1,88 @@
+use regex::Regex;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::LazyLock;
+
+// نام کاربری، نام نقش و نام دسترسی؛ دسترسی‌ها می‌توانند الگوی `*` داشته باشند
+pub static USERNAME: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^[A-Za-z0-9_.-]+$").unwrap());
+pub static IDENTIFIER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^[A-Za-z0-9_.:-]+$").unwrap());
+pub static PERMISSION_NAME: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^[A-Za-z0-9_.:*-]+$").unwrap());
+pub static EMAIL: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^[^@\s]+@[^@\s]+\.[^@\s]+$").unwrap());
+
+// قانون اعتبارسنجی یک فیلد متنی؛ طول بر حسب کاراکتر شمرده می‌شود
+pub enum Rule {
+    Length { min: usize, max: usize },
+    Pattern(&'static LazyLock<Regex>, &'static str),
+    OneOf(&'static [&'static str]),
+}
+
+impl Rule {
+    fn check(&self, value: &str) -> Option<String> {
+        match self {
+            Rule::Length { min, max } => {
+                let length = value.chars().count();
+                (length < *min || length > *max).then(|| format!("must be between {} and {} characters", min, max))
+            }
+            Rule::Pattern(pattern, description) => {
+                (!pattern.is_match(value)).then(|| format!("must be {}", description))
+            }
+            Rule::OneOf(allowed) => {
+                (!allowed.contains(&value)).then(|| format!("must be one of: {}", allowed.join(", ")))
+            }
+        }
+    }
+}
+
+// خطاهای اعتبارسنجی به تفکیک فیلد؛ در پاسخ 422 زیر کلید `errors` برگردانده می‌شوند
+#[derive(Debug, Default, Serialize)]
+pub struct ValidationErrors(BTreeMap<&'static str, Vec<String>>);
+
+impl ValidationErrors {
+    pub fn add(&mut self, field: &'static str, message: impl Into<String>) {
+        self.0.entry(field).or_default().push(message.into());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+// بدنه‌ی درخواستی که پیش از رسیدن به handler بررسی می‌شود (ValidatedJson)
+pub trait Validate {
+    fn validate(&self) -> Result<(), ValidationErrors>;
+}
+
+// جمع‌آوری خطاهای همه‌ی فیلدها تا کلاینت همه را یک‌جا ببیند
+#[derive(Default)]
+pub struct Validator {
+    errors: ValidationErrors,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Validator::default()
+    }
+
+    pub fn field(mut self, name: &'static str, value: &str, rules: &[Rule]) -> Self {
+        // با اولین قانون نقض‌شده پیام‌های بعدی (مثلاً الگو برای رشته‌ی خالی) اضافه نمی‌شوند
+        if let Some(message) = rules.iter().find_map(|rule| rule.check(value)) {
+            self.errors.add(name, message);
+        }
+        self
+    }
+
+    pub fn optional(self, name: &'static str, value: Option<&str>, rules: &[Rule]) -> Self {
+        match value {
+            Some(value) => self.field(name, value, rules),
+            None => self,
+        }
+    }
+
+    pub fn finish(self) -> Result<(), ValidationErrors> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors)
+        }
+    }
+}